@@ -0,0 +1,72 @@
+use crate::connection::{use_server_connection, ServerConnection};
+use crate::protocol::{ClientCommand, CommandResult};
+use leptos::prelude::*;
+use std::marker::PhantomData;
+
+/// Issues commands of type `C` and tracks the outcome of the last one, see `use_command`
+pub struct CommandHandle<C> {
+    connection: ServerConnection,
+    pending: RwSignal<bool>,
+    result: RwSignal<Option<CommandResult>>,
+    marker: PhantomData<fn(C)>,
+}
+
+impl<C> Clone for CommandHandle<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for CommandHandle<C> {}
+
+impl<C: ClientCommand> CommandHandle<C> {
+    /// Send `command` to the server
+    ///
+    /// Failing to send it is reported as a rejection in `result`.
+    pub fn issue(&self, command: &C) {
+        let (pending, result) = (self.pending, self.result);
+        pending.set(true);
+        let sent = self.connection.send_command(command, move |reply| {
+            pending.set(false);
+            result.set(Some(reply));
+        });
+        if let Err(err) = sent {
+            pending.set(false);
+            result.set(Some(CommandResult::Rejected {
+                reason: err.to_string(),
+            }));
+        }
+    }
+
+    /// Whether a command was issued and the server did not reply yet
+    pub fn pending(&self) -> Signal<bool> {
+        self.pending.into()
+    }
+
+    /// Outcome of the last command, `None` until the server replies to the first one
+    pub fn result(&self) -> Signal<Option<CommandResult>> {
+        self.result.into()
+    }
+}
+
+/// Issue commands of type `C` on the provided `ServerConnection`
+///
+/// # Example
+/// ```ignore
+/// let jog = use_command::<JogAxis>();
+/// view! {
+///     <button disabled=jog.pending() on:click=move |_| jog.issue(&JogAxis { axis: 0, distance: 1.0 })>"+X"</button>
+///     {move || match jog.result().get() {
+///         Some(CommandResult::Rejected { reason }) => reason,
+///         _ => String::new(),
+///     }}
+/// }
+/// ```
+pub fn use_command<C: ClientCommand>() -> CommandHandle<C> {
+    CommandHandle {
+        connection: use_server_connection(),
+        pending: RwSignal::new(false),
+        result: RwSignal::new(None),
+        marker: PhantomData,
+    }
+}
@@ -0,0 +1,231 @@
+use crate::protocol::{ClientCommand, CommandReply, CommandRequest, CommandResult, Envelope};
+use leptos::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, WebSocket};
+
+type Handler = Rc<dyn Fn(Value)>;
+type ReplyHandler = Box<dyn FnOnce(CommandResult)>;
+
+/// WebSocket connection from the browser to the `WebSocketPlugin` endpoint of an async_bevy_web server
+///
+/// Frames are JSON envelopes, so the server must use the `WsCodec::Json` codec. Incoming messages are
+/// dispatched by kind to the handlers registered with `on_message`. The connection is not reopened
+/// when it closes; watch `is_connected` to reconnect or show it to the user.
+///
+/// # Example
+/// ```ignore
+/// let connection = provide_server_connection("ws://localhost:3000/ws");
+/// connection.on_message::<NewChatMessage>("chat:NewChatMessage", move |chat| set_log.update(|log| log.push(chat)));
+/// connection.send("chat:UserChatMessage", &UserChatMessage { message: "hello".into() })?;
+/// ```
+#[derive(Clone, Copy)]
+pub struct ServerConnection {
+    socket: StoredValue<Option<WebSocket>, LocalStorage>,
+    handlers: StoredValue<HashMap<String, Vec<Handler>>, LocalStorage>,
+    connected: RwSignal<bool>,
+    /// Commands waiting for their reply, by request id
+    pending_commands: StoredValue<HashMap<u64, ReplyHandler>, LocalStorage>,
+    next_command_id: StoredValue<u64, LocalStorage>,
+}
+
+/// Error returned by `ServerConnection::send`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SendError {
+    /// The message could not be serialized
+    Encode(String),
+    /// The socket is not open
+    NotConnected,
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Encode(err) => write!(f, "failed to encode message: {err}"),
+            SendError::NotConnected => f.write_str("the server connection is not open"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+impl ServerConnection {
+    /// Open a WebSocket to `url`, e.g. `ws://localhost:3000/ws`
+    ///
+    /// Opening only fails for malformed urls, which is logged and leaves the connection closed.
+    pub fn connect(url: &str) -> Self {
+        let connection = Self {
+            socket: StoredValue::new_local(None),
+            handlers: StoredValue::new_local(HashMap::new()),
+            connected: RwSignal::new(false),
+            pending_commands: StoredValue::new_local(HashMap::new()),
+            next_command_id: StoredValue::new_local(1),
+        };
+        connection.on_message(CommandReply::KIND, move |reply: CommandReply| {
+            let on_reply = connection
+                .pending_commands
+                .try_update_value(|pending| pending.remove(&reply.id))
+                .flatten();
+            if let Some(on_reply) = on_reply {
+                on_reply(reply.result);
+            }
+        });
+
+        let socket = match WebSocket::new(url) {
+            Ok(socket) => socket,
+            Err(err) => {
+                leptos::logging::error!("failed to open WebSocket to {url}: {err:?}");
+                return connection;
+            }
+        };
+
+        let connected = connection.connected;
+        let on_open = Closure::<dyn FnMut()>::new(move || connected.set(true));
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        on_open.forget();
+
+        let on_close = Closure::<dyn FnMut()>::new(move || {
+            connected.set(false);
+            connection.reject_pending_commands();
+        });
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        on_close.forget();
+
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            if let Some(text) = event.data().as_string() {
+                connection.dispatch(&text);
+            }
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        on_message.forget();
+
+        connection.socket.set_value(Some(socket));
+        connection
+    }
+
+    /// Call `handler` with every message of `kind` received from now on
+    pub fn on_message<M: DeserializeOwned + 'static>(
+        &self,
+        kind: &str,
+        handler: impl Fn(M) + 'static,
+    ) {
+        let kind_name = kind.to_string();
+        let handler: Handler = Rc::new(move |data| match serde_json::from_value(data) {
+            Ok(message) => handler(message),
+            Err(err) => leptos::logging::warn!("failed to decode `{kind_name}` message: {err}"),
+        });
+        self.handlers
+            .update_value(|handlers| handlers.entry(kind.to_string()).or_default().push(handler));
+    }
+
+    /// Send `message` as a message of `kind`
+    pub fn send<M: Serialize>(&self, kind: &str, message: &M) -> Result<(), SendError> {
+        let envelope = Envelope {
+            kind: kind.to_string(),
+            data: message,
+        };
+        let text =
+            serde_json::to_string(&envelope).map_err(|err| SendError::Encode(err.to_string()))?;
+        self.socket.with_value(|socket| match socket {
+            Some(socket) if socket.ready_state() == WebSocket::OPEN => socket
+                .send_with_str(&text)
+                .map_err(|_| SendError::NotConnected),
+            _ => Err(SendError::NotConnected),
+        })
+    }
+
+    /// Issue `command` to the server's `ClientCommandPlugin` and call `on_reply` with its outcome
+    ///
+    /// If the socket closes before the server replies, `on_reply` is called with a rejection; the
+    /// command may have been applied nonetheless.
+    pub fn send_command<C: ClientCommand>(
+        &self,
+        command: &C,
+        on_reply: impl FnOnce(CommandResult) + 'static,
+    ) -> Result<(), SendError> {
+        let payload =
+            serde_json::to_value(command).map_err(|err| SendError::Encode(err.to_string()))?;
+        let id = self
+            .next_command_id
+            .try_update_value(|next| {
+                *next += 1;
+                *next - 1
+            })
+            .unwrap_or_default();
+        let request = CommandRequest {
+            id,
+            command: C::NAME.to_string(),
+            payload,
+        };
+        self.send(CommandRequest::KIND, &request)?;
+        self.pending_commands.update_value(|pending| {
+            pending.insert(id, Box::new(on_reply));
+        });
+        Ok(())
+    }
+
+    /// Whether the socket is open
+    pub fn is_connected(&self) -> Signal<bool> {
+        self.connected.into()
+    }
+
+    /// Close the socket
+    pub fn close(&self) {
+        self.socket.with_value(|socket| {
+            if let Some(socket) = socket {
+                let _ = socket.close();
+            }
+        });
+    }
+
+    fn reject_pending_commands(&self) {
+        let pending = self
+            .pending_commands
+            .try_update_value(std::mem::take)
+            .unwrap_or_default();
+        for (_, on_reply) in pending {
+            on_reply(CommandResult::Rejected {
+                reason: "the server connection closed before replying".to_string(),
+            });
+        }
+    }
+
+    fn dispatch(&self, text: &str) {
+        let envelope = match serde_json::from_str::<Envelope<Value>>(text) {
+            Ok(envelope) => envelope,
+            Err(err) => {
+                leptos::logging::warn!("dropped malformed server message: {err}");
+                return;
+            }
+        };
+        // Cloned so that handlers can register other handlers.
+        let handlers = self
+            .handlers
+            .with_value(|handlers| handlers.get(&envelope.kind).cloned())
+            .unwrap_or_default();
+        for handler in handlers {
+            handler(envelope.data.clone());
+        }
+    }
+}
+
+/// Open a `ServerConnection` to `url` and provide it to the component tree
+pub fn provide_server_connection(url: &str) -> ServerConnection {
+    let connection = ServerConnection::connect(url);
+    provide_context(connection);
+    connection
+}
+
+/// Get the `ServerConnection` provided by `provide_server_connection`
+///
+/// # Panics
+/// Panics if no connection was provided by a parent component.
+pub fn use_server_connection() -> ServerConnection {
+    expect_context::<ServerConnection>()
+}
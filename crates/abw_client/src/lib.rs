@@ -0,0 +1,19 @@
+/// Wire format shared by the `async_bevy_web` server plugins and this client
+///
+/// Every WebSocket frame is an `Envelope` `{ "kind": ..., "data": ... }`, the format of the server's
+/// `WebSocketPlugin` with the JSON codec.
+pub mod protocol;
+
+#[cfg(feature = "leptos")]
+mod command;
+#[cfg(feature = "leptos")]
+mod connection;
+#[cfg(feature = "leptos")]
+mod replication;
+
+#[cfg(feature = "leptos")]
+pub use command::*;
+#[cfg(feature = "leptos")]
+pub use connection::*;
+#[cfg(feature = "leptos")]
+pub use replication::*;
@@ -0,0 +1,342 @@
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// A component replicated from the Bevy world to clients
+///
+/// Implemented by the component types of a crate shared by the server and the client. On the server
+/// the type is also a Bevy `Component` registered with `app.replicate::<C>()`.
+///
+/// # Example
+/// ```ignore
+/// #[derive(Serialize, Deserialize, Clone, PartialEq)]
+/// #[cfg_attr(feature = "server", derive(Component))]
+/// pub struct AxisPosition { pub x: f64, pub y: f64 }
+///
+/// impl ReplicatedComponent for AxisPosition {
+///     const NAME: &'static str = "AxisPosition";
+/// }
+/// ```
+pub trait ReplicatedComponent: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Unique name identifying the component type on the wire
+    const NAME: &'static str;
+}
+
+/// Envelope of every WebSocket frame
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Envelope<T> {
+    /// Name of the message type
+    pub kind: String,
+    /// The message
+    pub data: T,
+}
+
+/// Server generated id of a replicated entity, the bits of its Bevy `Entity`
+pub type EntityId = u64;
+
+/// The serialized components of one entity, by component name
+pub type EntityComponents = BTreeMap<String, Value>;
+
+/// Replication message sent by the server, see [`ReplicationMessage::KIND`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReplicationMessage {
+    /// Every entity visible to the client, sent once when it connects; replaces the client's world
+    Snapshot {
+        /// The visible entities with all their replicated components
+        #[serde(deserialize_with = "deserialize_entity_map")]
+        entities: BTreeMap<EntityId, EntityComponents>,
+    },
+    /// Changes since the previous message
+    Delta {
+        /// Entities that changed or became visible
+        #[serde(deserialize_with = "deserialize_entity_map")]
+        changed: BTreeMap<EntityId, EntityDelta>,
+        /// Entities that were despawned, stopped being replicated or are no longer visible
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        despawned: Vec<EntityId>,
+    },
+}
+
+impl ReplicationMessage {
+    /// Envelope kind of replication messages
+    pub const KIND: &'static str = "abw:replication";
+}
+
+/// An entity id used as a map key, which JSON turns into a string
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(untagged)]
+enum EntityKey {
+    Id(EntityId),
+    Text(String),
+}
+
+/// Deserialize a map keyed by entity ids
+///
+/// Internally tagged enums buffer their fields, which loses serde_json's conversion of string keys
+/// back to integers, so the keys are parsed here.
+fn deserialize_entity_map<'de, D, V>(deserializer: D) -> Result<BTreeMap<EntityId, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    BTreeMap::<EntityKey, V>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| {
+            let id = match key {
+                EntityKey::Id(id) => id,
+                EntityKey::Text(text) => text.parse().map_err(D::Error::custom)?,
+            };
+            Ok((id, value))
+        })
+        .collect()
+}
+
+/// Changes of the components of one entity
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EntityDelta {
+    /// Components replaced by a new value, including components that were added
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub set: EntityComponents,
+    /// JSON merge patches (RFC 7396) to apply to the current value of components
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub patch: EntityComponents,
+    /// Components that were removed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+}
+
+impl EntityDelta {
+    /// Whether the delta changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.patch.is_empty() && self.removed.is_empty()
+    }
+
+    /// Apply the delta to the components of an entity
+    pub fn apply(self, components: &mut EntityComponents) {
+        for name in self.removed {
+            components.remove(&name);
+        }
+        for (name, patch) in self.patch {
+            merge_patch(components.entry(name).or_insert(Value::Null), patch);
+        }
+        components.extend(self.set);
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7396) to `target`
+pub fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let target = target
+        .as_object_mut()
+        .expect("target was just made an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Compute the JSON merge patch turning `old` into `new`
+///
+/// Returns `None` when the values are equal or when a merge patch cannot express the change, i.e.
+/// when `new` contains a `null` that is not in `old`; send `new` whole in that case.
+pub fn merge_diff(old: &Value, new: &Value) -> Option<Value> {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        return None;
+    };
+    let mut patch = Map::new();
+    for (key, value) in new {
+        match old.get(key) {
+            Some(previous) if previous == value => {}
+            _ if value.is_null() => return None,
+            Some(previous @ Value::Object(_)) if value.is_object() => {
+                patch.insert(key.clone(), merge_diff(previous, value)?);
+            }
+            _ => {
+                patch.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    for key in old.keys() {
+        if !new.contains_key(key) {
+            patch.insert(key.clone(), Value::Null);
+        }
+    }
+    (!patch.is_empty()).then_some(Value::Object(patch))
+}
+
+/// A command issued by a client and applied by the server
+///
+/// Implemented by the command types of a crate shared by the server and the client. On the server the
+/// type is registered with `app.add_client_command::<C>(handler)`.
+///
+/// # Example
+/// ```ignore
+/// #[derive(Serialize, Deserialize, Clone, Debug)]
+/// pub struct JogAxis { pub axis: u8, pub distance: f64 }
+///
+/// impl ClientCommand for JogAxis {
+///     const NAME: &'static str = "JogAxis";
+/// }
+/// ```
+pub trait ClientCommand: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Unique name identifying the command type on the wire
+    const NAME: &'static str;
+}
+
+/// Command issued by a client, see [`CommandRequest::KIND`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CommandRequest {
+    /// Id chosen by the client, echoed in the `CommandReply`
+    pub id: u64,
+    /// `ClientCommand::NAME` of the command
+    pub command: String,
+    /// The serialized command
+    pub payload: Value,
+}
+
+impl CommandRequest {
+    /// Envelope kind of command requests
+    pub const KIND: &'static str = "abw:command";
+}
+
+/// Answer of the server to a `CommandRequest`, see [`CommandReply::KIND`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CommandReply {
+    /// Id of the request
+    pub id: u64,
+    /// Whether the command was applied
+    pub result: CommandResult,
+}
+
+impl CommandReply {
+    /// Envelope kind of command replies
+    pub const KIND: &'static str = "abw:command_reply";
+}
+
+/// Outcome of a command
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CommandResult {
+    /// The command passed validation and was applied
+    Accepted,
+    /// The command was not applied
+    Rejected {
+        /// Why, for the operator
+        reason: String,
+    },
+}
+
+impl CommandResult {
+    /// Whether the command was applied
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, CommandResult::Accepted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn components(value: Value) -> EntityComponents {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn apply_diff(old: Value, new: Value) -> Value {
+        let mut value = old.clone();
+        merge_patch(&mut value, merge_diff(&old, &new).unwrap());
+        value
+    }
+
+    #[test]
+    fn merge_diff_sends_only_changed_fields() {
+        let old = json!({ "x": 1.0, "y": 2.0, "limits": { "min": 0, "max": 10 } });
+        let new = json!({ "x": 1.5, "y": 2.0, "limits": { "min": 0, "max": 20 } });
+        assert_eq!(
+            merge_diff(&old, &new),
+            Some(json!({ "x": 1.5, "limits": { "max": 20 } }))
+        );
+        assert_eq!(apply_diff(old, new.clone()), new);
+    }
+
+    #[test]
+    fn merge_diff_removes_missing_fields() {
+        let old = json!({ "x": 1.0, "label": "a" });
+        let new = json!({ "x": 1.0 });
+        assert_eq!(merge_diff(&old, &new), Some(json!({ "label": null })));
+        assert_eq!(apply_diff(old, new.clone()), new);
+    }
+
+    #[test]
+    fn merge_diff_replaces_non_object_fields() {
+        let old = json!({ "path": [1, 2], "mode": { "auto": true } });
+        let new = json!({ "path": [3], "mode": "manual" });
+        assert_eq!(apply_diff(old, new.clone()), new);
+    }
+
+    #[test]
+    fn merge_diff_gives_up_on_unrepresentable_changes() {
+        let old = json!({ "x": 1.0 });
+        assert_eq!(merge_diff(&old, &old), None);
+        assert_eq!(merge_diff(&old, &json!({ "x": null })), None);
+        assert_eq!(merge_diff(&json!(1), &json!(2)), None);
+    }
+
+    #[test]
+    fn replication_messages_round_trip_through_json() {
+        let message = ReplicationMessage::Delta {
+            changed: BTreeMap::from([(
+                u64::MAX,
+                EntityDelta {
+                    set: components(json!({ "Mode": "manual" })),
+                    ..Default::default()
+                },
+            )]),
+            despawned: vec![7],
+        };
+        let envelope = Envelope {
+            kind: ReplicationMessage::KIND.to_string(),
+            data: message.clone(),
+        };
+        let text = serde_json::to_string(&envelope).unwrap();
+        let envelope: Envelope<Value> = serde_json::from_str(&text).unwrap();
+        assert_eq!(
+            serde_json::from_value::<ReplicationMessage>(envelope.data).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn entity_delta_removes_patches_and_sets_components() {
+        let mut current = components(json!({
+            "Pose": { "x": 1.0, "y": 2.0 },
+            "Mode": "auto",
+            "Fault": { "code": 3 },
+        }));
+        let delta = EntityDelta {
+            set: components(json!({ "Mode": "manual", "Target": { "x": 5.0 } })),
+            patch: components(json!({ "Pose": { "x": 1.5 } })),
+            removed: vec!["Fault".to_string()],
+        };
+        delta.apply(&mut current);
+        assert_eq!(
+            current,
+            components(json!({
+                "Pose": { "x": 1.5, "y": 2.0 },
+                "Mode": "manual",
+                "Target": { "x": 5.0 },
+            }))
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use crate::connection::ServerConnection;
+use crate::protocol::{EntityComponents, EntityId, ReplicatedComponent, ReplicationMessage};
+use leptos::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Reactive copy of the entities the server's `ReplicationPlugin` makes visible to this client
+///
+/// Holds every replicated entity as its serialized components, each in its own signal: changing an
+/// entity only notifies the readers of that entity, and the set of entities only notifies when
+/// entities are spawned or despawned. Read it through `use_entity` and `use_query`, which only
+/// notify their subscribers when their own result changes.
+#[derive(Clone, Copy)]
+pub struct ReplicatedWorld {
+    entities: RwSignal<BTreeMap<EntityId, RwSignal<EntityComponents>>>,
+}
+
+impl Default for ReplicatedWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplicatedWorld {
+    /// Create an empty world, to be filled with `apply`
+    pub fn new() -> Self {
+        Self {
+            entities: RwSignal::new(BTreeMap::new()),
+        }
+    }
+
+    /// Apply a replication message received from the server
+    pub fn apply(&self, message: ReplicationMessage) {
+        let mut spawned = BTreeMap::new();
+        let despawned: BTreeSet<EntityId>;
+        match message {
+            ReplicationMessage::Snapshot { entities } => {
+                despawned = self.entities.with_untracked(|current| {
+                    current
+                        .keys()
+                        .filter(|id| !entities.contains_key(id))
+                        .copied()
+                        .collect()
+                });
+                for (id, components) in entities {
+                    match self.entity_untracked(id) {
+                        Some(signal) => {
+                            if signal.with_untracked(|current| *current != components) {
+                                signal.set(components);
+                            }
+                        }
+                        None => {
+                            spawned.insert(id, components);
+                        }
+                    }
+                }
+            }
+            ReplicationMessage::Delta {
+                changed,
+                despawned: removed,
+            } => {
+                despawned = removed.into_iter().collect();
+                for (id, delta) in changed {
+                    // An entity despawned and changed by the same delta starts over from the delta.
+                    let signal = self
+                        .entity_untracked(id)
+                        .filter(|_| !despawned.contains(&id));
+                    match signal {
+                        Some(signal) => signal.update(|components| delta.apply(components)),
+                        None => delta.apply(spawned.entry(id).or_default()),
+                    }
+                }
+            }
+        }
+
+        if spawned.is_empty() && despawned.is_empty() {
+            return;
+        }
+        self.entities.update(|entities| {
+            for id in despawned {
+                if let Some(signal) = entities.remove(&id) {
+                    signal.dispose();
+                }
+            }
+            for (id, components) in spawned {
+                entities.insert(id, RwSignal::new(components));
+            }
+        });
+    }
+
+    fn entity_untracked(&self, id: EntityId) -> Option<RwSignal<EntityComponents>> {
+        self.entities
+            .with_untracked(|entities| entities.get(&id).copied())
+    }
+
+    /// Ids of the replicated entities
+    pub fn entity_ids(&self) -> Memo<Vec<EntityId>> {
+        let entities = self.entities;
+        Memo::new(move |_| entities.with(|entities| entities.keys().copied().collect()))
+    }
+}
+
+/// One replicated entity with its serialized components
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplicatedEntity {
+    /// Server id of the entity
+    pub id: EntityId,
+    /// Serialized components, by `ReplicatedComponent::NAME`
+    pub components: EntityComponents,
+}
+
+impl ReplicatedEntity {
+    /// Deserialize the component `C`, if the entity has it
+    pub fn get<C: ReplicatedComponent>(&self) -> Option<C> {
+        let value = self.components.get(C::NAME)?;
+        serde_json::from_value(value.clone())
+            .inspect_err(|err| {
+                leptos::logging::warn!("failed to decode component `{}`: {err}", C::NAME)
+            })
+            .ok()
+    }
+
+    /// Whether the entity has the component `C`
+    pub fn contains<C: ReplicatedComponent>(&self) -> bool {
+        self.components.contains_key(C::NAME)
+    }
+}
+
+/// Create a `ReplicatedWorld` fed by `connection` and provide it to the component tree
+///
+/// The server sends a snapshot of every visible entity when the socket connects, then deltas.
+///
+/// # Example
+/// ```ignore
+/// #[component]
+/// pub fn App() -> impl IntoView {
+///     let connection = provide_server_connection("ws://localhost:3000/ws");
+///     provide_replicated_world(connection);
+///     view! { <AxisList/> }
+/// }
+///
+/// #[component]
+/// fn AxisList() -> impl IntoView {
+///     let axes = use_query::<AxisPosition>();
+///     view! { <For each=move || axes.get() key=|(id, _)| *id let:axis> <li>{axis.1.x}</li> </For> }
+/// }
+/// ```
+pub fn provide_replicated_world(connection: ServerConnection) -> ReplicatedWorld {
+    let world = ReplicatedWorld::new();
+    connection.on_message(ReplicationMessage::KIND, move |message| {
+        world.apply(message)
+    });
+    provide_context(world);
+    world
+}
+
+/// Get the `ReplicatedWorld` provided by `provide_replicated_world`
+///
+/// # Panics
+/// Panics if no world was provided by a parent component.
+pub fn use_replicated_world() -> ReplicatedWorld {
+    expect_context::<ReplicatedWorld>()
+}
+
+/// Track the entity `id`, `None` while it is not replicated to this client
+pub fn use_entity(id: EntityId) -> Memo<Option<ReplicatedEntity>> {
+    let entities = use_replicated_world().entities;
+    Memo::new(move |_| {
+        let signal = entities.with(|entities| entities.get(&id).copied())?;
+        Some(ReplicatedEntity {
+            id,
+            components: signal.get(),
+        })
+    })
+}
+
+/// Track every replicated entity having the component `C`, with its value, ordered by id
+pub fn use_query<C: ReplicatedComponent + Clone + PartialEq>() -> Memo<Vec<(EntityId, C)>> {
+    let entities = use_replicated_world().entities;
+    Memo::new(move |_| {
+        entities.with(|entities| {
+            entities
+                .iter()
+                .filter_map(|(id, signal)| {
+                    let value = signal.with(|components| components.get(C::NAME).cloned())?;
+                    Some((*id, serde_json::from_value(value).ok()?))
+                })
+                .collect()
+        })
+    })
+}
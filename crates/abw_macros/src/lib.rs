@@ -1,23 +1,131 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use proc_macro_crate::{crate_name, FoundCrate};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, FnArg, Ident, ItemFn, Type};
 
+/// Turns an `async fn` into a function returning the `LeptosAppFn` to give to `LeptosAppPlugin`
+///
+/// The function keeps its visibility, attributes and doc comments. Its parameters are resolved with
+/// `LeptosAppParam`, e.g. the `TaskContext` of the Bevy world, the `CancellationToken` cancelled when
+/// the app is stopped or the `LeptosServerConfig`. It returns `()` or `Result<(), E>`, an error marking
+/// the app failed in `WebServerStatus`.
+///
+/// # Example
+/// ```ignore
+/// /// The operator UI
+/// #[leptos_app]
+/// pub async fn operator_app(config: LeptosServerConfig) -> std::io::Result<()> {
+///     // build the router
+///     serve_leptos_app(app).await
+/// }
+///
+/// app.add_plugins(LeptosAppPlugin::new(operator_app()).with_config(config));
+/// ```
 #[proc_macro_attribute]
-pub fn leptos_app(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn leptos_app(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as ItemFn);
+    let attr = TokenStream2::from(attr);
 
+    let mut errors = Vec::new();
+    if !attr.is_empty() {
+        errors.push(syn::Error::new_spanned(
+            attr,
+            "`#[leptos_app]` takes no arguments",
+        ));
+    }
+    if input.sig.asyncness.is_none() {
+        errors.push(syn::Error::new_spanned(
+            input.sig.fn_token,
+            "`#[leptos_app]` can only be used on an `async fn`",
+        ));
+    }
+    if !input.sig.generics.params.is_empty() || input.sig.generics.where_clause.is_some() {
+        errors.push(syn::Error::new_spanned(
+            &input.sig.generics,
+            "`#[leptos_app]` functions cannot be generic, their parameters are resolved with `LeptosAppParam`",
+        ));
+    }
+    let mut param_types = Vec::new();
+    for param in &input.sig.inputs {
+        match param {
+            FnArg::Receiver(receiver) => errors.push(syn::Error::new_spanned(
+                receiver,
+                "`#[leptos_app]` cannot be used on methods, remove the `self` parameter",
+            )),
+            FnArg::Typed(typed) if matches!(*typed.ty, Type::ImplTrait(_)) => {
+                errors.push(syn::Error::new_spanned(
+                    &typed.ty,
+                    "`#[leptos_app]` functions cannot be generic, their parameters are resolved with `LeptosAppParam`",
+                ))
+            }
+            FnArg::Typed(typed) => param_types.push(&typed.ty),
+        }
+    }
+    if let Some(error) = errors.into_iter().reduce(|mut combined, error| {
+        combined.combine(error);
+        combined
+    }) {
+        return error.to_compile_error().into();
+    }
+
+    let krate = bevy_leptos_path();
+    let attrs = &input.attrs;
+    let vis = &input.vis;
     let fn_name = &input.sig.ident;
+    let inputs = &input.sig.inputs;
+    let output = &input.sig.output;
     let fn_block = &input.block;
+    let args: Vec<Ident> = (0..param_types.len())
+        .map(|i| format_ident!("__arg{}", i))
+        .collect();
 
     let expanded = quote! {
-        pub fn #fn_name() -> ::std::sync::Arc<dyn Fn() -> ::std::pin::Pin<Box<dyn ::std::future::Future<Output = ()> + Send>> + Send + Sync> {
+        #(#attrs)*
+        #vis fn #fn_name() -> #krate::LeptosAppFn {
+            async fn __leptos_app(#inputs) #output #fn_block
+
             ::std::sync::Arc::new(|| {
-                Box::pin(async move #fn_block) as ::std::pin::Pin<Box<dyn ::std::future::Future<Output = ()> + Send>>
+                ::std::boxed::Box::pin(async move {
+                    #(
+                        let #args = match <#param_types as #krate::LeptosAppParam>::from_leptos_app() {
+                            ::std::result::Result::Ok(value) => value,
+                            ::std::result::Result::Err(error) => {
+                                #krate::report_failure(error);
+                                return;
+                            }
+                        };
+                    )*
+                    let output = __leptos_app(#(#args),*).await;
+                    if let ::std::option::Option::Some(error) = #krate::LeptosAppOutput::into_failure(output) {
+                        #krate::report_failure(error);
+                    }
+                }) as ::std::pin::Pin<::std::boxed::Box<dyn ::std::future::Future<Output = ()> + ::std::marker::Send>>
             })
         }
     };
 
     TokenStream::from(expanded)
 }
+
+/// Path to the items of bevy-leptos, also found in the prelude of async-bevy-web
+fn bevy_leptos_path() -> TokenStream2 {
+    match crate_name("bevy-leptos") {
+        Ok(FoundCrate::Itself) => return quote!(crate),
+        Ok(FoundCrate::Name(name)) => {
+            let name = Ident::new(&name, Span::call_site());
+            return quote!(::#name);
+        }
+        Err(_) => {}
+    }
+    match crate_name("async-bevy-web") {
+        Ok(FoundCrate::Itself) => quote!(crate::prelude),
+        Ok(FoundCrate::Name(name)) => {
+            let name = Ident::new(&name, Span::call_site());
+            quote!(::#name::prelude)
+        }
+        Err(_) => quote!(::bevy_leptos),
+    }
+}
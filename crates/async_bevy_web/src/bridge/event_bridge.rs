@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use std::marker::PhantomData;
+use std::ops::Range;
+use tokio::sync::{broadcast, mpsc};
+
+/// Bridges the Bevy message `E` to and from Tokio channels
+///
+/// Outbound: every `E` written in the ECS is forwarded, in the order it was written, to a
+/// `tokio::sync::broadcast` channel during `Last`. Subscribers that fall more than the outbound
+/// capacity behind receive `RecvError::Lagged` and continue with the oldest retained message.
+///
+/// Inbound: messages sent on the `mpsc` sender returned by `EventBridge::sender` are written as `E`
+/// during `PreUpdate`, in the order they were received. Everything received before a frame starts is
+/// visible to that frame's systems ordered after `EventBridgeSystems::Inbound`.
+///
+/// # Example
+/// ```ignore
+/// app.add_plugins(EventBridgePlugin::<ChatMessage>::default().with_inbound_capacity(256));
+///
+/// // From any Tokio task, e.g. a websocket handler:
+/// let bridge = ctx.resource::<EventBridge<ChatMessage>>().await?;
+/// bridge.sender().send(ChatMessage::new("hello")).await?;
+/// let mut messages = bridge.subscribe();
+/// while let Ok(message) = messages.recv().await { /* ... */ }
+/// ```
+pub struct EventBridgePlugin<E> {
+    outbound_capacity: usize,
+    inbound_capacity: usize,
+    echo_inbound: bool,
+    _marker: PhantomData<fn() -> E>,
+}
+
+impl<E> Default for EventBridgePlugin<E> {
+    fn default() -> Self {
+        Self {
+            outbound_capacity: 1024,
+            inbound_capacity: 1024,
+            echo_inbound: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E> EventBridgePlugin<E> {
+    /// Set how many messages the outbound broadcast channel retains for slow subscribers (default 1024)
+    pub fn with_outbound_capacity(mut self, capacity: usize) -> Self {
+        self.outbound_capacity = capacity;
+        self
+    }
+
+    /// Set how many inbound messages can be queued before `send` waits for the next frame (default 1024)
+    pub fn with_inbound_capacity(mut self, capacity: usize) -> Self {
+        self.inbound_capacity = capacity;
+        self
+    }
+
+    /// Also forward messages that came in through the inbound sender to outbound subscribers
+    ///
+    /// Disabled by default, so that a client sending a message does not receive it back.
+    pub fn echo_inbound(mut self, echo: bool) -> Self {
+        self.echo_inbound = echo;
+        self
+    }
+}
+
+impl<E: Message + Clone> Plugin for EventBridgePlugin<E> {
+    fn build(&self, app: &mut App) {
+        let (outbound_tx, _) = broadcast::channel::<E>(self.outbound_capacity.max(1));
+        let (inbound_tx, inbound_rx) = mpsc::channel(self.inbound_capacity.max(1));
+
+        app.add_message::<E>()
+            .insert_resource(EventBridge {
+                outbound_tx,
+                inbound_tx,
+            })
+            .insert_resource(EventBridgeInbound {
+                inbound_rx,
+                echo: self.echo_inbound,
+                written: Vec::new(),
+            })
+            .add_systems(
+                PreUpdate,
+                write_inbound_messages::<E>.in_set(EventBridgeSystems::Inbound),
+            )
+            .add_systems(
+                Last,
+                forward_outbound_messages::<E>.in_set(EventBridgeSystems::Outbound),
+            );
+    }
+}
+
+/// System sets of the systems added by every `EventBridgePlugin`
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventBridgeSystems {
+    /// Writes inbound messages as Bevy messages, in `PreUpdate`
+    Inbound,
+    /// Forwards Bevy messages to outbound subscribers, in `Last`
+    Outbound,
+}
+
+/// Resource holding both ends of the bridge for the message `E` that async code uses
+///
+/// Cheap to clone, so web handlers can keep a copy.
+#[derive(Resource)]
+pub struct EventBridge<E> {
+    outbound_tx: broadcast::Sender<E>,
+    inbound_tx: mpsc::Sender<E>,
+}
+
+impl<E> Clone for EventBridge<E> {
+    fn clone(&self) -> Self {
+        Self {
+            outbound_tx: self.outbound_tx.clone(),
+            inbound_tx: self.inbound_tx.clone(),
+        }
+    }
+}
+
+impl<E: Message + Clone> EventBridge<E> {
+    /// Subscribe to the messages written in the ECS from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<E> {
+        self.outbound_tx.subscribe()
+    }
+
+    /// Get a sender whose messages are written as `E` at the start of the next frame
+    pub fn sender(&self) -> mpsc::Sender<E> {
+        self.inbound_tx.clone()
+    }
+
+    /// Number of live outbound subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.outbound_tx.receiver_count()
+    }
+}
+
+#[derive(Resource)]
+struct EventBridgeInbound<E> {
+    inbound_rx: mpsc::Receiver<E>,
+    echo: bool,
+    /// Ids of the messages written by `write_inbound_messages` this frame, skipped by
+    /// `forward_outbound_messages` unless inbound messages are echoed.
+    written: Vec<Range<usize>>,
+}
+
+fn write_inbound_messages<E: Message>(
+    mut inbound: ResMut<EventBridgeInbound<E>>,
+    mut writer: MessageWriter<E>,
+) {
+    let mut received = Vec::new();
+    while let Ok(message) = inbound.inbound_rx.try_recv() {
+        received.push(message);
+    }
+    if received.is_empty() {
+        return;
+    }
+
+    let ids: Vec<usize> = writer.write_batch(received).map(|id| id.id).collect();
+    if !inbound.echo {
+        if let (Some(first), Some(last)) = (ids.first(), ids.last()) {
+            inbound.written.push(*first..*last + 1);
+        }
+    }
+}
+
+fn forward_outbound_messages<E: Message + Clone>(
+    mut inbound: ResMut<EventBridgeInbound<E>>,
+    mut reader: MessageReader<E>,
+    bridge: Res<EventBridge<E>>,
+) {
+    let written = std::mem::take(&mut inbound.written);
+    for (message, id) in reader.read_with_id() {
+        if written.iter().any(|range| range.contains(&id.id)) {
+            continue;
+        }
+        // Sending only fails when there are no subscribers, in which case the message is dropped.
+        let _ = bridge.outbound_tx.send(message.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Chat(&'static str);
+
+    #[test]
+    fn messages_round_trip() {
+        let mut app = App::new();
+        app.add_plugins(EventBridgePlugin::<Chat>::default());
+        let bridge = app.world().resource::<EventBridge<Chat>>().clone();
+        let mut outbound = bridge.subscribe();
+
+        app.world_mut().write_message(Chat("from bevy"));
+        app.update();
+        assert_eq!(outbound.try_recv(), Ok(Chat("from bevy")));
+
+        bridge.sender().try_send(Chat("from tokio")).unwrap();
+        app.update();
+        let written: Vec<&Chat> = app
+            .world()
+            .resource::<Messages<Chat>>()
+            .iter_current_update_messages()
+            .collect();
+        assert_eq!(written, [&Chat("from tokio")]);
+        // Inbound messages are not echoed by default.
+        assert_eq!(outbound.try_recv(), Err(TryRecvError::Empty));
+    }
+}
@@ -0,0 +1,4 @@
+mod event_bridge;
+mod rpc;
+pub use event_bridge::*;
+pub use rpc::*;
@@ -0,0 +1,275 @@
+use bevy::ecs::system::SystemId;
+use bevy::prelude::*;
+use std::fmt;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// Default time a call waits for its response before failing with `RpcError::Timeout`
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum number of calls of one RPC that can be queued before `call` waits for the next frame
+const RPC_QUEUE_CAPACITY: usize = 1024;
+
+/// Registers request/response handlers which async code can call into
+pub trait AppRpcExt {
+    /// Register `handler` as the ECS side of the RPC `Req -> Resp`
+    ///
+    /// The handler is an ordinary Bevy system taking the request as its `In` parameter. Queued calls are
+    /// executed every frame in the `RpcSystems` set of `Update`, one handler run per call, in the order
+    /// the calls were made. Async code calls it through the `Rpc<Req, Resp>` resource.
+    ///
+    /// Registering the same RPC again replaces its handler; existing `Rpc` handles keep working.
+    ///
+    /// # Example
+    /// ```ignore
+    /// fn get_robot_state(In(request): In<GetRobotState>, robots: Query<&RobotState>) -> RobotState {
+    ///     robots.get(request.robot).cloned().unwrap_or_default()
+    /// }
+    ///
+    /// app.add_rpc(get_robot_state);
+    /// // or, naming the request and response types
+    /// app.add_rpc::<GetRobotState, RobotState, _, _>(get_robot_state);
+    ///
+    /// // From a Tokio task or an Axum handler:
+    /// let state = rpc.call(GetRobotState { robot }).await?;
+    /// ```
+    fn add_rpc<Req, Resp, S, M>(&mut self, handler: S) -> &mut Self
+    where
+        Req: Send + 'static,
+        Resp: Send + 'static,
+        S: IntoSystem<In<Req>, Resp, M> + 'static;
+}
+
+impl AppRpcExt for App {
+    fn add_rpc<Req, Resp, S, M>(&mut self, handler: S) -> &mut Self
+    where
+        Req: Send + 'static,
+        Resp: Send + 'static,
+        S: IntoSystem<In<Req>, Resp, M> + 'static,
+    {
+        let handler = self.world_mut().register_system(handler);
+        let world = self.world_mut();
+        if let Some(mut endpoint) = world.get_resource_mut::<RpcEndpoint<Req, Resp>>() {
+            log::warn!(
+                "RPC `{} -> {}` is registered more than once, the last handler is used",
+                std::any::type_name::<Req>(),
+                std::any::type_name::<Resp>()
+            );
+            let previous = std::mem::replace(&mut endpoint.handler, handler);
+            let _ = world.unregister_system(previous);
+            return self;
+        }
+        let (call_tx, call_rx) = mpsc::channel(RPC_QUEUE_CAPACITY);
+
+        self.insert_resource(Rpc {
+            call_tx,
+            timeout: DEFAULT_RPC_TIMEOUT,
+        })
+        .insert_resource(RpcEndpoint { handler, call_rx })
+        .add_systems(Update, run_rpc_calls::<Req, Resp>.in_set(RpcSystems))
+    }
+}
+
+/// System set in `Update` containing the systems which execute queued RPC calls
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RpcSystems;
+
+/// Error returned by `Rpc::call`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpcError {
+    /// The handler did not respond within the call's timeout. The call is skipped if it has not
+    /// started yet.
+    Timeout(Duration),
+    /// The Bevy app has shut down, or was not running when the call was made
+    Closed,
+    /// The handler could not be run, e.g. because its parameters failed validation
+    Failed(String),
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Timeout(timeout) => write!(f, "RPC call timed out after {timeout:?}"),
+            RpcError::Closed => f.write_str("RPC call failed because the Bevy app has shut down"),
+            RpcError::Failed(err) => write!(f, "RPC handler failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+struct RpcCall<Req, Resp> {
+    request: Req,
+    response_tx: oneshot::Sender<Result<Resp, RpcError>>,
+}
+
+/// Resource used to call the RPC `Req -> Resp` registered with `AppRpcExt::add_rpc`
+///
+/// Cheap to clone, so web handlers can keep a copy.
+#[derive(Resource)]
+pub struct Rpc<Req, Resp> {
+    call_tx: mpsc::Sender<RpcCall<Req, Resp>>,
+    timeout: Duration,
+}
+
+impl<Req, Resp> Clone for Rpc<Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            call_tx: self.call_tx.clone(),
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl<Req: Send + 'static, Resp: Send + 'static> Rpc<Req, Resp> {
+    /// Get a copy of this handle whose calls time out after `timeout`
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            call_tx: self.call_tx.clone(),
+            timeout,
+        }
+    }
+
+    /// Queue a call and wait for the handler's response
+    ///
+    /// Must be awaited from within the Tokio runtime, since the timeout uses Tokio's timer.
+    pub async fn call(&self, request: Req) -> Result<Resp, RpcError> {
+        let (response_tx, response_rx) = oneshot::channel();
+        let call = async {
+            self.call_tx
+                .send(RpcCall {
+                    request,
+                    response_tx,
+                })
+                .await
+                .map_err(|_| RpcError::Closed)?;
+            response_rx.await.map_err(|_| RpcError::Closed)?
+        };
+        tokio::time::timeout(self.timeout, call)
+            .await
+            .map_err(|_| RpcError::Timeout(self.timeout))?
+    }
+}
+
+#[derive(Resource)]
+struct RpcEndpoint<Req: 'static, Resp: 'static> {
+    handler: SystemId<In<Req>, Resp>,
+    call_rx: mpsc::Receiver<RpcCall<Req, Resp>>,
+}
+
+fn run_rpc_calls<Req: Send + 'static, Resp: Send + 'static>(world: &mut World) {
+    let mut endpoint = world.resource_mut::<RpcEndpoint<Req, Resp>>();
+    let handler = endpoint.handler;
+    let mut calls = Vec::new();
+    while let Ok(call) = endpoint.call_rx.try_recv() {
+        calls.push(call);
+    }
+
+    for RpcCall {
+        request,
+        response_tx,
+    } in calls
+    {
+        // The caller gave up (timed out or was dropped), don't run the handler for nothing.
+        if response_tx.is_closed() {
+            continue;
+        }
+        let response = world
+            .run_system_with(handler, request)
+            .map_err(|err| RpcError::Failed(err.to_string()));
+        let _ = response_tx.send(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_tokio_tasks::{TokioTasksPlugin, TokioTasksRuntime};
+    use tokio::task::JoinHandle;
+
+    struct Double(u32);
+
+    #[derive(Component)]
+    struct Robot;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default());
+        app
+    }
+
+    fn call<Req, Resp>(
+        app: &App,
+        rpc: &Rpc<Req, Resp>,
+        request: Req,
+    ) -> JoinHandle<Result<Resp, RpcError>>
+    where
+        Req: Send + 'static,
+        Resp: Send + 'static,
+    {
+        let rpc = rpc.clone();
+        app.world()
+            .resource::<TokioTasksRuntime>()
+            .runtime()
+            .spawn(async move { rpc.call(request).await })
+    }
+
+    /// Update the app until the call completed
+    fn complete<T>(app: &mut App, handle: JoinHandle<T>) -> T {
+        while !handle.is_finished() {
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let runtime = app.world().resource::<TokioTasksRuntime>();
+        runtime.runtime().block_on(handle).unwrap()
+    }
+
+    #[test]
+    fn calls_run_the_handler() {
+        let mut app = app();
+        app.add_rpc::<Double, u32, _, _>(|In(Double(n))| n * 2);
+        let rpc = app.world().resource::<Rpc<Double, u32>>().clone();
+
+        let first = call(&app, &rpc, Double(21));
+        let second = call(&app, &rpc, Double(4));
+        assert_eq!(complete(&mut app, first), Ok(42));
+        assert_eq!(complete(&mut app, second), Ok(8));
+    }
+
+    #[test]
+    fn registering_again_replaces_the_handler() {
+        let mut app = app();
+        app.add_rpc(|In(Double(n)): In<Double>| n * 2);
+        let rpc = app.world().resource::<Rpc<Double, u32>>().clone();
+        app.add_rpc(|In(Double(n)): In<Double>| n * 3);
+
+        let handle = call(&app, &rpc, Double(5));
+        assert_eq!(complete(&mut app, handle), Ok(15));
+    }
+
+    #[test]
+    fn failing_handlers_are_reported() {
+        let mut app = app();
+        app.add_rpc(|In(_): In<Double>, _robot: Single<&Robot>| 0u32);
+        let rpc = app.world().resource::<Rpc<Double, u32>>().clone();
+
+        let handle = call(&app, &rpc, Double(1));
+        assert!(matches!(
+            complete(&mut app, handle),
+            Err(RpcError::Failed(_))
+        ));
+    }
+
+    #[test]
+    fn calls_time_out_without_frames() {
+        let mut app = app();
+        app.add_rpc(|In(Double(n)): In<Double>| n);
+        let rpc = app.world().resource::<Rpc<Double, u32>>().clone();
+        let timeout = Duration::from_millis(10);
+
+        let handle = call(&app, &rpc.with_timeout(timeout), Double(1));
+        let runtime = app.world().resource::<TokioTasksRuntime>();
+        let result = runtime.runtime().block_on(handle).unwrap();
+        assert_eq!(result, Err(RpcError::Timeout(timeout)));
+    }
+}
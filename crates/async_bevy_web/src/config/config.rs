@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 use bevy::app::ScheduleRunnerPlugin;
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
 use bevy_tokio_tasks::TokioTasksPlugin;
 use std::time::Duration;
 
@@ -18,14 +19,12 @@ pub enum TimeMode {
 pub struct ABWConfigPlugin {
     frame_rate: f64,
     time_mode: TimeMode,
+    tick_schedules: Vec<InternedScheduleLabel>,
 }
 
 impl Default for ABWConfigPlugin {
     fn default() -> Self {
-        Self {
-            frame_rate: 60.0,
-            time_mode: TimeMode::Variable,
-        }
+        Self::new(60.0)
     }
 }
 
@@ -35,10 +34,7 @@ impl ABWConfigPlugin {
     /// # Arguments
     /// * `frame_rate` - Target frames per second (e.g., 60.0 for 60 FPS)
     pub fn new(frame_rate: f64) -> Self {
-        Self {
-            frame_rate,
-            time_mode: TimeMode::Variable,
-        }
+        Self::with_mode(frame_rate, TimeMode::Variable)
     }
 
     /// Create a new config with specified time mode
@@ -58,6 +54,7 @@ impl ABWConfigPlugin {
         Self {
             frame_rate,
             time_mode,
+            tick_schedules: vec![Update.intern()],
         }
     }
 
@@ -76,6 +73,24 @@ impl ABWConfigPlugin {
     pub fn variable(frame_rate: f64) -> Self {
         Self::with_mode(frame_rate, TimeMode::Variable)
     }
+
+    /// Set the schedule main thread callbacks of Tokio tasks run in (default `Update`)
+    ///
+    /// With `TimeMode::Fixed`, ticking in `FixedPreUpdate` makes data sent by background tasks
+    /// visible to the `FixedUpdate` systems of the same step, and makes `sleep_updates` count
+    /// fixed steps.
+    ///
+    /// # Example
+    /// ```
+    /// use async_bevy_web::prelude::*;
+    /// use bevy::prelude::*;
+    ///
+    /// let config = ABWConfigPlugin::fixed(20.0).tick_tasks_in(FixedPreUpdate);
+    /// ```
+    pub fn tick_tasks_in(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.tick_schedules = vec![schedule.intern()];
+        self
+    }
 }
 
 impl Plugin for ABWConfigPlugin {
@@ -87,7 +102,10 @@ impl Plugin for ABWConfigPlugin {
                     ScheduleRunnerPlugin::run_loop(frame_duration)
                 )
             )
-            .add_plugins(TokioTasksPlugin::default());
+            .add_plugins(TokioTasksPlugin {
+                tick_schedules: self.tick_schedules.clone(),
+                ..TokioTasksPlugin::default()
+            });
 
         // Configure fixed timestep if requested
         if self.time_mode == TimeMode::Fixed {
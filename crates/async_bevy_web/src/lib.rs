@@ -1,2 +1,7 @@
+mod bridge;
 mod config;
-pub mod prelude;
\ No newline at end of file
+mod server;
+pub mod prelude;
+
+/// Re-exported so that routes are built against the same Axum version as the server
+pub use axum;
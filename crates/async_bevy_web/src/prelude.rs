@@ -1,4 +1,6 @@
+pub use crate::bridge::*;
 pub use crate::config::*;
+pub use crate::server::*;
 pub use bevy_leptos::*;
 pub use bevy_tokio_tasks::*;
 
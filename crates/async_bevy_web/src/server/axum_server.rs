@@ -0,0 +1,212 @@
+use crate::server::EcsHandle;
+use axum::{Extension, Router};
+use bevy::prelude::*;
+use bevy_leptos::leptos_task_context;
+use bevy_tokio_tasks::{CancellationToken, TaskContext, TokioTasksRuntime};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Serves the routes contributed with `add_routes` on an Axum server owned by the Bevy app
+///
+/// The server is started in `PostStartup`, once every plugin had a chance to contribute routes, as a
+/// background task named `axum_server` on the `TokioTasksRuntime`. It stops accepting connections when
+/// the app exits and finishes in-flight requests within the runtime's shutdown timeout. Handlers can
+/// access the ECS through the `EcsHandle` extractor.
+///
+/// # Example
+/// ```ignore
+/// App::new()
+///     .add_plugins(ABWConfigPlugin::default())
+///     .add_plugins(AxumServerPlugin::new(([0, 0, 0, 0], 8080)))
+///     .add_routes(Router::new().route("/health", get(|| async { "ok" })))
+///     .run();
+/// ```
+pub struct AxumServerPlugin {
+    addr: SocketAddr,
+}
+
+impl Default for AxumServerPlugin {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], 3000)),
+        }
+    }
+}
+
+impl AxumServerPlugin {
+    /// Create a server listening on `addr`
+    ///
+    /// # Arguments
+    /// * `addr` - Address to bind, e.g. `([127, 0, 0, 1], 3000)` or a parsed `SocketAddr`
+    pub fn new(addr: impl Into<SocketAddr>) -> Self {
+        Self { addr: addr.into() }
+    }
+}
+
+impl Plugin for AxumServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WebRoutes>()
+            .insert_resource(AxumServerConfig { addr: self.addr })
+            .add_systems(PostStartup, start_axum_server);
+    }
+}
+
+/// Address the `AxumServerPlugin` server binds to
+#[derive(Resource, Clone, Debug)]
+pub struct AxumServerConfig {
+    /// Address the server binds to
+    pub addr: SocketAddr,
+}
+
+/// Resource collecting the `Router` fragments served by `AxumServerPlugin`
+///
+/// Fragments are merged with `Router::merge`, so they must not define the same route twice and at
+/// most one of them may set a fallback.
+#[derive(Resource, Default, Clone)]
+pub struct WebRoutes {
+    router: Router,
+}
+
+impl WebRoutes {
+    /// Merge a router fragment into the served routes
+    pub fn add(&mut self, router: Router) {
+        self.router = std::mem::take(&mut self.router).merge(router);
+    }
+
+    /// Get the merged router
+    pub fn router(&self) -> Router {
+        self.router.clone()
+    }
+
+    /// Get the merged router with the `EcsHandle` extractor backed by `ctx`
+    pub fn router_with_ecs(&self, ctx: TaskContext) -> Router {
+        self.router().layer(Extension(EcsHandle::new(ctx)))
+    }
+}
+
+/// Lets plugins contribute routes to the `AxumServerPlugin` server
+pub trait AppRoutesExt {
+    /// Merge `router` into the routes served by `AxumServerPlugin`
+    ///
+    /// Routes added after the server started are not served.
+    fn add_routes(&mut self, router: Router) -> &mut Self;
+}
+
+impl AppRoutesExt for App {
+    fn add_routes(&mut self, router: Router) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<WebRoutes>()
+            .add(router);
+        self
+    }
+}
+
+/// Start the Axum server with the routes collected in `WebRoutes`
+pub fn start_axum_server(
+    runtime: Res<TokioTasksRuntime>,
+    routes: Res<WebRoutes>,
+    config: Res<AxumServerConfig>,
+) {
+    let routes = routes.clone();
+    let addr = config.addr;
+    runtime
+        .task()
+        .name("axum_server")
+        .spawn_fallible(move |ctx| {
+            let shutdown = ctx.shutdown_token();
+            serve(addr, routes.router_with_ecs(ctx), shutdown)
+        });
+}
+
+/// Get the routes contributed with `add_routes`, to serve them from the Axum server of a Leptos app
+///
+/// Call it inside a `#[leptos_app]` function started by `LeptosAppPlugin` and merge the result into
+/// the Leptos router, instead of adding `AxumServerPlugin` and opening a second port. Returns an empty
+/// router when called elsewhere.
+///
+/// # Example
+/// ```ignore
+/// #[leptos_app]
+/// pub async fn start_leptos_app() {
+///     let app = Router::new()
+///         .leptos_routes(&leptos_options, routes, MyApp)
+///         .with_state(leptos_options)
+///         .merge(ecs_routes().await);
+///     // bind and serve as usual
+/// }
+/// ```
+pub async fn ecs_routes() -> Router {
+    let Some(mut ctx) = leptos_task_context() else {
+        log::warn!("ecs_routes() called outside of a Leptos app started by LeptosAppPlugin");
+        return Router::new();
+    };
+    match ctx.get_resource::<WebRoutes>().await {
+        Ok(Some(routes)) => routes.router_with_ecs(ctx),
+        _ => Router::new(),
+    }
+}
+
+async fn serve(
+    addr: SocketAddr,
+    router: Router,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    log::info!("listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use bevy_tokio_tasks::TokioTasksPlugin;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[derive(Resource, Clone)]
+    struct RobotCount(usize);
+
+    async fn robot_count(mut ecs: EcsHandle) -> Result<String, StatusCode> {
+        let count = ecs
+            .resource::<RobotCount>()
+            .await
+            .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+        Ok(count.0.to_string())
+    }
+
+    #[test]
+    fn contributed_routes_read_the_world() {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default())
+            .insert_resource(RobotCount(3))
+            .add_routes(Router::new().route("/robots/count", get(robot_count)));
+        let routes = app.world().resource::<WebRoutes>().clone();
+
+        let handle = app
+            .world()
+            .resource::<TokioTasksRuntime>()
+            .spawn_background_task(|ctx| async move {
+                let request = Request::get("/robots/count").body(Body::empty()).unwrap();
+                let response = routes.router_with_ecs(ctx).oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, body)
+            });
+        while !handle.is_finished() {
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let runtime = app.world().resource::<TokioTasksRuntime>();
+        let (status, body) = runtime.runtime().block_on(handle).unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(&body[..], b"3");
+    }
+}
@@ -0,0 +1,557 @@
+use crate::server::{AppWebSocketExt, Connection, WsMessage, WsReceived, WsSystems};
+use abw_client::protocol::{CommandReply, CommandRequest};
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
+use bevy::ecs::system::SystemId;
+use bevy::prelude::*;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::time::SystemTime;
+
+pub use abw_client::protocol::{ClientCommand, CommandResult};
+
+/// Applies the commands clients send over the `WebSocketPlugin` endpoint
+///
+/// Clients send `CommandRequest`s naming a command registered with `app.add_client_command::<C>()`.
+/// Requests are queued in the order they were received and processed one after the other by an
+/// exclusive system in the `ClientCommandSystems` set of `FixedPreUpdate` (see `in_schedule`), so
+/// commands are applied at the same point of every tick whatever the network timing. Each command
+/// is decoded, checked by the validators registered for its type, in registration order, and applied
+/// by its handler if they all pass. The client gets a `CommandReply` with the outcome: accepted, or
+/// rejected with the reason given by the first failing validator.
+///
+/// Every outcome, including malformed and unknown commands, is logged, kept in the
+/// `CommandAuditLog` resource and written as a `CommandAudited` message, with the connection and the
+/// `Operator` that issued it.
+///
+/// # Example
+/// ```ignore
+/// app.add_plugins(WebSocketPlugin::new("/ws"))
+///     .add_plugins(ClientCommandPlugin::new())
+///     .add_client_command::<JogAxis, _>(|In(jog): In<IssuedCommand<JogAxis>>, mut axes: Query<&mut AxisPosition>| {
+///         axes.get_mut(jog.command.axis).unwrap().x += jog.command.distance;
+///     })
+///     .add_command_validator::<JogAxis, _>(|jog: InRef<IssuedCommand<JogAxis>>| {
+///         if jog.command.distance.abs() > 10.0 {
+///             return Err("jog distance is limited to 10 mm".into());
+///         }
+///         Ok(())
+///     });
+/// ```
+pub struct ClientCommandPlugin {
+    schedule: InternedScheduleLabel,
+    audit_capacity: usize,
+}
+
+impl Default for ClientCommandPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientCommandPlugin {
+    /// Apply commands in `FixedPreUpdate` and keep the last 1000 audit entries
+    pub fn new() -> Self {
+        Self {
+            schedule: FixedPreUpdate.intern(),
+            audit_capacity: 1000,
+        }
+    }
+
+    /// Set the schedule commands are applied in (default `FixedPreUpdate`)
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+
+    /// Set how many entries the `CommandAuditLog` keeps (default 1000)
+    pub fn with_audit_capacity(mut self, capacity: usize) -> Self {
+        self.audit_capacity = capacity;
+        self
+    }
+}
+
+impl Plugin for ClientCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_ws_message::<CommandRequest>()
+            .add_message::<CommandAudited>()
+            .init_resource::<CommandRegistry>()
+            .init_resource::<PendingCommands>()
+            .insert_resource(CommandAuditLog {
+                entries: VecDeque::new(),
+                capacity: self.audit_capacity,
+            })
+            .add_systems(PreUpdate, queue_command_requests.after(WsSystems))
+            .add_systems(
+                self.schedule,
+                apply_client_commands.in_set(ClientCommandSystems),
+            );
+    }
+}
+
+/// System set containing the exclusive system which validates and applies client commands
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClientCommandSystems;
+
+impl WsMessage for CommandRequest {
+    const NAME: &'static str = CommandRequest::KIND;
+}
+
+impl WsMessage for CommandReply {
+    const NAME: &'static str = CommandReply::KIND;
+}
+
+/// Identity of the operator using a connection
+///
+/// Insert it on the `Connection` entity when the client authenticates. It is passed to validators
+/// with every command and recorded in the audit log.
+#[derive(Component, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Operator(pub String);
+
+/// Who issued a command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandIssuer {
+    /// The `Connection` entity of the client
+    pub connection: Entity,
+    /// The `Operator` of the connection when the command was processed
+    pub operator: Option<Operator>,
+}
+
+/// A decoded client command, passed to its validators and handler
+#[derive(Clone, Debug)]
+pub struct IssuedCommand<C> {
+    /// Id of the request, chosen by the client
+    pub id: u64,
+    /// Who issued the command
+    pub issuer: CommandIssuer,
+    /// The command
+    pub command: C,
+}
+
+/// Lets plugins register client command types, their handler and their validators
+pub trait AppClientCommandExt {
+    /// Apply every valid `C` with the system `handler`, requires `ClientCommandPlugin`
+    ///
+    /// Registering a command type again replaces its handler.
+    fn add_client_command<C: ClientCommand, M>(
+        &mut self,
+        handler: impl IntoSystem<In<IssuedCommand<C>>, (), M> + 'static,
+    ) -> &mut Self;
+
+    /// Reject the commands `C` for which the system `validator` returns an error, with the error as reason
+    ///
+    /// Validators can read the world, e.g. to check the issuer's permissions or the current state,
+    /// and run in registration order right before the handler, so they see the state the command is
+    /// applied to.
+    fn add_command_validator<C: ClientCommand, M>(
+        &mut self,
+        validator: impl IntoSystem<InRef<'static, IssuedCommand<C>>, Result<(), String>, M> + 'static,
+    ) -> &mut Self;
+}
+
+impl AppClientCommandExt for App {
+    fn add_client_command<C: ClientCommand, M>(
+        &mut self,
+        handler: impl IntoSystem<In<IssuedCommand<C>>, (), M> + 'static,
+    ) -> &mut Self {
+        let handler = self.world_mut().register_system(handler);
+        let world = self.world_mut();
+        let previous = world
+            .get_resource_or_init::<CommandSystems<C>>()
+            .handler
+            .replace(handler);
+        if let Some(previous) = previous {
+            log::warn!("client command `{}` is registered more than once", C::NAME);
+            let _ = world.unregister_system(previous);
+        }
+        world
+            .get_resource_or_init::<CommandRegistry>()
+            .processors
+            .insert(C::NAME, process_command::<C>);
+        self
+    }
+
+    fn add_command_validator<C: ClientCommand, M>(
+        &mut self,
+        validator: impl IntoSystem<InRef<'static, IssuedCommand<C>>, Result<(), String>, M> + 'static,
+    ) -> &mut Self {
+        let validator = self.world_mut().register_system(validator);
+        self.world_mut()
+            .get_resource_or_init::<CommandSystems<C>>()
+            .validators
+            .push(validator);
+        self
+    }
+}
+
+/// One processed command
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    /// When the command was processed
+    pub at: SystemTime,
+    /// Who issued the command
+    pub issuer: CommandIssuer,
+    /// Id of the request, chosen by the client
+    pub id: u64,
+    /// Name of the command, as sent by the client
+    pub command: String,
+    /// The command as sent by the client
+    pub payload: Value,
+    /// Whether the command was applied
+    pub result: CommandResult,
+}
+
+/// Written for every processed command, see `ClientCommandPlugin`
+#[derive(Message, Clone, Debug)]
+pub struct CommandAudited(pub AuditEntry);
+
+/// The last processed commands, oldest first
+#[derive(Resource, Debug)]
+pub struct CommandAuditLog {
+    entries: VecDeque<AuditEntry>,
+    capacity: usize,
+}
+
+impl CommandAuditLog {
+    /// Iterate over the kept entries, oldest first
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &AuditEntry> + '_ {
+        self.entries.iter()
+    }
+
+    /// Iterate over the kept entries of the commands issued by `operator`, oldest first
+    pub fn by_operator<'a>(
+        &'a self,
+        operator: &'a str,
+    ) -> impl Iterator<Item = &'a AuditEntry> + 'a {
+        self.entries.iter().filter(move |entry| {
+            entry
+                .issuer
+                .operator
+                .as_ref()
+                .is_some_and(|issuer| issuer.0 == operator)
+        })
+    }
+
+    fn push(&mut self, entry: AuditEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+type ValidatorId<C> = SystemId<InRef<'static, IssuedCommand<C>>, Result<(), String>>;
+
+#[derive(Resource)]
+struct CommandSystems<C: ClientCommand> {
+    handler: Option<SystemId<In<IssuedCommand<C>>>>,
+    validators: Vec<ValidatorId<C>>,
+}
+
+impl<C: ClientCommand> Default for CommandSystems<C> {
+    fn default() -> Self {
+        Self {
+            handler: None,
+            validators: Vec::new(),
+        }
+    }
+}
+
+type ProcessFn = fn(&mut World, u64, CommandIssuer, Value) -> CommandResult;
+
+#[derive(Resource, Default)]
+struct CommandRegistry {
+    processors: HashMap<&'static str, ProcessFn>,
+}
+
+/// Requests received but not processed yet, in the order they were received
+#[derive(Resource, Default)]
+struct PendingCommands(VecDeque<WsReceived<CommandRequest>>);
+
+fn queue_command_requests(
+    mut received: MessageReader<WsReceived<CommandRequest>>,
+    mut pending: ResMut<PendingCommands>,
+) {
+    pending.0.extend(received.read().cloned());
+}
+
+fn apply_client_commands(world: &mut World) {
+    let requests = std::mem::take(&mut world.resource_mut::<PendingCommands>().0);
+    for WsReceived { from, message } in requests {
+        let issuer = CommandIssuer {
+            connection: from,
+            operator: world.get::<Operator>(from).cloned(),
+        };
+        let process = world
+            .resource::<CommandRegistry>()
+            .processors
+            .get(message.command.as_str())
+            .copied();
+        let result = match process {
+            Some(process) => process(world, message.id, issuer.clone(), message.payload.clone()),
+            None => CommandResult::Rejected {
+                reason: format!("unknown command `{}`", message.command),
+            },
+        };
+
+        let operator = issuer.operator.as_ref().map_or("-", |operator| &operator.0);
+        match &result {
+            CommandResult::Accepted => log::info!(
+                "command `{}` #{} from {} (operator {operator}) accepted",
+                message.command,
+                message.id,
+                issuer.connection
+            ),
+            CommandResult::Rejected { reason } => log::info!(
+                "command `{}` #{} from {} (operator {operator}) rejected: {reason}",
+                message.command,
+                message.id,
+                issuer.connection
+            ),
+        }
+
+        // The client may have disconnected in the meantime, the command is audited anyway.
+        if let Some(connection) = world.get::<Connection>(from) {
+            let reply = CommandReply {
+                id: message.id,
+                result: result.clone(),
+            };
+            if let Err(err) = connection.send(&reply) {
+                log::warn!(
+                    "failed to reply to command #{} of {from}: {err}",
+                    message.id
+                );
+            }
+        }
+
+        let entry = AuditEntry {
+            at: SystemTime::now(),
+            issuer,
+            id: message.id,
+            command: message.command,
+            payload: message.payload,
+            result,
+        };
+        world.resource_mut::<CommandAuditLog>().push(entry.clone());
+        world.write_message(CommandAudited(entry));
+    }
+}
+
+fn process_command<C: ClientCommand>(
+    world: &mut World,
+    id: u64,
+    issuer: CommandIssuer,
+    payload: Value,
+) -> CommandResult {
+    let command = match serde_json::from_value(payload) {
+        Ok(command) => command,
+        Err(err) => {
+            return CommandResult::Rejected {
+                reason: format!("malformed `{}` command: {err}", C::NAME),
+            }
+        }
+    };
+    let issued = IssuedCommand {
+        id,
+        issuer,
+        command,
+    };
+
+    let systems = world.resource::<CommandSystems<C>>();
+    let (validators, handler) = (systems.validators.clone(), systems.handler);
+    for validator in validators {
+        match world.run_system_with(validator, &issued) {
+            Ok(Ok(())) => {}
+            Ok(Err(reason)) => return CommandResult::Rejected { reason },
+            Err(err) => {
+                log::error!("validator of command `{}` failed to run: {err}", C::NAME);
+                return CommandResult::Rejected {
+                    reason: "internal error".to_string(),
+                };
+            }
+        }
+    }
+
+    let Some(handler) = handler else {
+        return CommandResult::Rejected {
+            reason: format!("unknown command `{}`", C::NAME),
+        };
+    };
+    match world.run_system_with(handler, issued) {
+        Ok(()) => CommandResult::Accepted,
+        Err(err) => {
+            log::error!("handler of command `{}` failed to run: {err}", C::NAME);
+            CommandResult::Rejected {
+                reason: "internal error".to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abw_client::protocol::Envelope;
+    use axum::extract::ws::Message as WsFrame;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    #[derive(Serialize, Deserialize)]
+    struct Jog {
+        distance: f64,
+    }
+
+    impl ClientCommand for Jog {
+        const NAME: &'static str = "Jog";
+    }
+
+    /// What ran, in order
+    #[derive(Resource, Default)]
+    struct Calls(Vec<&'static str>);
+
+    fn check_operator(
+        jog: InRef<IssuedCommand<Jog>>,
+        mut calls: ResMut<Calls>,
+    ) -> Result<(), String> {
+        calls.0.push("check_operator");
+        match jog.issuer.operator {
+            Some(_) => Ok(()),
+            None => Err("not authenticated".to_string()),
+        }
+    }
+
+    fn check_distance(
+        jog: InRef<IssuedCommand<Jog>>,
+        mut calls: ResMut<Calls>,
+    ) -> Result<(), String> {
+        calls.0.push("check_distance");
+        if jog.command.distance.abs() > 10.0 {
+            return Err("jog distance is limited to 10 mm".to_string());
+        }
+        Ok(())
+    }
+
+    fn jog(In(_): In<IssuedCommand<Jog>>, mut calls: ResMut<Calls>) {
+        calls.0.push("jog");
+    }
+
+    fn app() -> (App, Entity, mpsc::Receiver<WsFrame>) {
+        let mut app = App::new();
+        app.add_plugins(ClientCommandPlugin::new().in_schedule(Update))
+            .init_resource::<Calls>()
+            .add_client_command::<Jog, _>(jog)
+            .add_command_validator::<Jog, _>(check_operator)
+            .add_command_validator::<Jog, _>(check_distance);
+        let (connection, frames) = Connection::for_test(1);
+        let client = app.world_mut().spawn(connection).id();
+        (app, client, frames)
+    }
+
+    fn issue(app: &mut App, from: Entity, id: u64, command: &str, payload: Value) {
+        app.world_mut().write_message(WsReceived {
+            from,
+            message: CommandRequest {
+                id,
+                command: command.to_string(),
+                payload,
+            },
+        });
+        app.update();
+    }
+
+    fn reply(frames: &mut mpsc::Receiver<WsFrame>) -> CommandReply {
+        let WsFrame::Text(text) = frames.try_recv().expect("expected a reply") else {
+            panic!("expected a JSON text frame");
+        };
+        let envelope: Envelope<CommandReply> = serde_json::from_str(&text).unwrap();
+        assert_eq!(envelope.kind, CommandReply::KIND);
+        envelope.data
+    }
+
+    fn rejected(id: u64, reason: &str) -> CommandReply {
+        CommandReply {
+            id,
+            result: CommandResult::Rejected {
+                reason: reason.to_string(),
+            },
+        }
+    }
+
+    fn calls(app: &mut App) -> Vec<&'static str> {
+        std::mem::take(&mut app.world_mut().resource_mut::<Calls>().0)
+    }
+
+    #[test]
+    fn validators_run_in_order_before_the_handler() {
+        let (mut app, client, mut frames) = app();
+        app.world_mut()
+            .entity_mut(client)
+            .insert(Operator("ada".to_string()));
+
+        issue(&mut app, client, 1, "Jog", json!({ "distance": 2.0 }));
+        assert_eq!(calls(&mut app), ["check_operator", "check_distance", "jog"]);
+        assert_eq!(
+            reply(&mut frames),
+            CommandReply {
+                id: 1,
+                result: CommandResult::Accepted,
+            }
+        );
+    }
+
+    #[test]
+    fn the_first_failing_validator_rejects_the_command() {
+        let (mut app, client, mut frames) = app();
+
+        issue(&mut app, client, 1, "Jog", json!({ "distance": 20.0 }));
+        assert_eq!(calls(&mut app), ["check_operator"]);
+        assert_eq!(reply(&mut frames), rejected(1, "not authenticated"));
+
+        app.world_mut()
+            .entity_mut(client)
+            .insert(Operator("ada".to_string()));
+        issue(&mut app, client, 2, "Jog", json!({ "distance": 20.0 }));
+        assert_eq!(calls(&mut app), ["check_operator", "check_distance"]);
+        assert_eq!(
+            reply(&mut frames),
+            rejected(2, "jog distance is limited to 10 mm")
+        );
+
+        let audit = app.world().resource::<CommandAuditLog>();
+        let results: Vec<_> = audit.entries().map(|entry| &entry.result).collect();
+        assert_eq!(
+            results,
+            [
+                &CommandResult::Rejected {
+                    reason: "not authenticated".to_string()
+                },
+                &CommandResult::Rejected {
+                    reason: "jog distance is limited to 10 mm".to_string()
+                },
+            ]
+        );
+        assert_eq!(audit.by_operator("ada").count(), 1);
+    }
+
+    #[test]
+    fn malformed_and_unknown_commands_are_rejected() {
+        let (mut app, client, mut frames) = app();
+
+        issue(&mut app, client, 1, "Jog", json!({ "distance": "far" }));
+        let CommandResult::Rejected { reason } = reply(&mut frames).result else {
+            panic!("expected a rejection");
+        };
+        assert!(reason.starts_with("malformed `Jog` command"), "{reason}");
+
+        issue(&mut app, client, 2, "Fly", json!({}));
+        assert_eq!(reply(&mut frames), rejected(2, "unknown command `Fly`"));
+        assert!(calls(&mut app).is_empty());
+        assert_eq!(
+            app.world().resource::<CommandAuditLog>().entries().count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use bevy_leptos::LeptosAppParam;
+use bevy_tokio_tasks::TaskContext;
+use std::ops::{Deref, DerefMut};
+
+/// Axum extractor giving request handlers async access to the Bevy world
+///
+/// Wraps the `TaskContext` of the server task started by `AxumServerPlugin`, so every accessor of
+/// `TaskContext` (`resource`, `query`, `run_system`, `run_on_main_thread`, ...) is available and is
+/// executed on the main thread during the next `tick_runtime_update`.
+///
+/// # Example
+/// ```ignore
+/// async fn robot_count(mut ecs: EcsHandle) -> Result<String, StatusCode> {
+///     let robots = ecs.query::<Entity>().await.map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+///     Ok(robots.len().to_string())
+/// }
+///
+/// app.add_routes(Router::new().route("/robots/count", get(robot_count)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct EcsHandle(TaskContext);
+
+impl EcsHandle {
+    /// Wrap a task context, e.g. to serve a router outside of `AxumServerPlugin`
+    ///
+    /// The handle is picked up by the extractor when it is added to the router as an
+    /// `axum::Extension`.
+    pub fn new(ctx: TaskContext) -> Self {
+        Self(ctx)
+    }
+
+    /// Unwrap the underlying task context
+    pub fn into_inner(self) -> TaskContext {
+        self.0
+    }
+}
+
+impl Deref for EcsHandle {
+    type Target = TaskContext;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for EcsHandle {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for EcsHandle {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<EcsHandle>().cloned().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "EcsHandle is not available, serve the router through AxumServerPlugin",
+        ))
+    }
+}
+
+/// Lets a `#[leptos_app]` function take the handle to the Bevy world as a parameter
+impl LeptosAppParam for EcsHandle {
+    fn from_leptos_app() -> Result<Self, String> {
+        TaskContext::from_leptos_app().map(EcsHandle)
+    }
+}
@@ -0,0 +1,18 @@
+mod axum_server;
+mod commands;
+mod ecs_handle;
+mod reflect_api;
+#[cfg(feature = "remote")]
+mod remote;
+mod replication;
+mod sse;
+mod websocket;
+pub use axum_server::*;
+pub use commands::*;
+pub use ecs_handle::*;
+pub use reflect_api::*;
+#[cfg(feature = "remote")]
+pub use remote::*;
+pub use replication::*;
+pub use sse::*;
+pub use websocket::*;
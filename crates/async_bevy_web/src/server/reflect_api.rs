@@ -0,0 +1,765 @@
+use crate::server::{AppRoutesExt, EcsHandle};
+use abw_client::protocol::merge_patch;
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use bevy::ecs::reflect::{ReflectComponent, ReflectResource};
+use bevy::prelude::*;
+use bevy::reflect::serde::{TypedReflectDeserializer, TypedReflectSerializer};
+use bevy::reflect::{FromReflect, GetTypeRegistration, TypePath, TypeRegistry};
+use serde::de::DeserializeSeed;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Whether an exposed type can be modified over HTTP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReflectAccess {
+    /// Only `GET` is allowed
+    ReadOnly,
+    /// `GET`, `PUT` (resources only) and `PATCH` are allowed
+    Writable,
+}
+
+/// Exposes an allowlist of reflected resources and components as JSON under `/ecs`
+///
+/// Routes, added to the `AxumServerPlugin` server:
+/// * `GET|PUT|PATCH /ecs/resources/{type}` - read, replace or merge-patch a resource
+/// * `GET /ecs/entities?with=A,B` - list the entities having all the listed components, with their values
+/// * `GET|PATCH /ecs/entities/{id}/{component}` - read or merge-patch one component of an entity
+///
+/// `{type}` and `{component}` are the short type path (`ControlGains`) or the full type path of an
+/// exposed type, and `{id}` is the value returned by `Entity::to_bits`. When several exposed types
+/// share a short type path, only their full type paths are accepted. Types which are not exposed
+/// respond with `404 Not Found`, writes to read-only types with `403 Forbidden`. `PATCH` bodies are
+/// JSON merge patches (RFC 7396) applied to the serialized value.
+///
+/// # Example
+/// ```ignore
+/// #[derive(Resource, Reflect, Clone)]
+/// #[reflect(Resource)]
+/// struct ControlGains { kp: f32, ki: f32, kd: f32 }
+///
+/// app.add_plugins(
+///     ReflectApiPlugin::new()
+///         .resource::<ControlGains>(ReflectAccess::Writable)
+///         .resource::<SensorData>(ReflectAccess::ReadOnly)
+///         .component::<Transform>(ReflectAccess::ReadOnly),
+/// );
+/// // curl -X PATCH localhost:3000/ecs/resources/ControlGains -d '{"kp": 1.5}'
+/// ```
+#[derive(Default)]
+pub struct ReflectApiPlugin {
+    types: Vec<ExposedTypeRegistration>,
+}
+
+struct ExposedTypeRegistration {
+    exposed: ExposedType,
+    register: fn(&mut App),
+}
+
+impl ReflectApiPlugin {
+    /// Create a plugin exposing no types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expose the resource `R`
+    ///
+    /// The type is registered in the `AppTypeRegistry` together with its `ReflectResource` data, so
+    /// `#[reflect(Resource)]` is not required.
+    pub fn resource<R>(mut self, access: ReflectAccess) -> Self
+    where
+        R: Resource + Reflect + FromReflect + TypePath + GetTypeRegistration,
+    {
+        self.types.push(ExposedTypeRegistration {
+            exposed: ExposedType::new::<R>(ExposedKind::Resource, access),
+            register: |app| {
+                app.register_type::<R>()
+                    .register_type_data::<R, ReflectResource>();
+            },
+        });
+        self
+    }
+
+    /// Expose the component `C`
+    ///
+    /// The type is registered in the `AppTypeRegistry` together with its `ReflectComponent` data, so
+    /// `#[reflect(Component)]` is not required.
+    pub fn component<C>(mut self, access: ReflectAccess) -> Self
+    where
+        C: Component + Reflect + FromReflect + TypePath + GetTypeRegistration,
+    {
+        self.types.push(ExposedTypeRegistration {
+            exposed: ExposedType::new::<C>(ExposedKind::Component, access),
+            register: |app| {
+                app.register_type::<C>()
+                    .register_type_data::<C, ReflectComponent>();
+            },
+        });
+        self
+    }
+}
+
+impl Plugin for ReflectApiPlugin {
+    fn build(&self, app: &mut App) {
+        for registration in &self.types {
+            (registration.register)(app);
+        }
+        let allowlist = ReflectApiAllowlist::new(
+            self.types
+                .iter()
+                .map(|registration| registration.exposed.clone()),
+        );
+
+        app.insert_resource(allowlist).add_routes(
+            Router::new()
+                .route(
+                    "/ecs/resources/{type}",
+                    get(get_resource).put(put_resource).patch(patch_resource),
+                )
+                .route("/ecs/entities", get(list_entities))
+                .route(
+                    "/ecs/entities/{id}/{component}",
+                    get(get_component).patch(patch_component),
+                ),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExposedKind {
+    Resource,
+    Component,
+}
+
+#[derive(Debug, Clone)]
+struct ExposedType {
+    type_id: TypeId,
+    /// The short type path, or the full one if another exposed type has the same short path
+    name: &'static str,
+    short_path: &'static str,
+    path: &'static str,
+    kind: ExposedKind,
+    access: ReflectAccess,
+}
+
+impl ExposedType {
+    fn new<T: TypePath + 'static>(kind: ExposedKind, access: ReflectAccess) -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            name: T::short_type_path(),
+            short_path: T::short_type_path(),
+            path: T::type_path(),
+            kind,
+            access,
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), ApiError> {
+        match self.access {
+            ReflectAccess::Writable => Ok(()),
+            ReflectAccess::ReadOnly => {
+                Err(ApiError::Forbidden(format!("{} is read-only", self.name)))
+            }
+        }
+    }
+}
+
+/// Resource holding the types exposed by `ReflectApiPlugin`
+#[derive(Resource, Default, Clone)]
+pub struct ReflectApiAllowlist {
+    by_name: HashMap<&'static str, Arc<ExposedType>>,
+}
+
+impl ReflectApiAllowlist {
+    /// Index `types` by full type path, and by short type path when it names a single type
+    fn new(types: impl IntoIterator<Item = ExposedType>) -> Self {
+        // Exposing a type twice keeps the last registration.
+        let mut by_type: HashMap<TypeId, ExposedType> = HashMap::new();
+        for exposed in types {
+            by_type.insert(exposed.type_id, exposed);
+        }
+        let mut by_short_path: HashMap<&'static str, Vec<&'static str>> = HashMap::new();
+        for exposed in by_type.values() {
+            by_short_path
+                .entry(exposed.name)
+                .or_default()
+                .push(exposed.path);
+        }
+        for (short_path, paths) in &by_short_path {
+            if paths.len() > 1 {
+                log::warn!(
+                    "exposed types {paths:?} share the short type path `{short_path}`, only their full type paths are accepted"
+                );
+            }
+        }
+
+        let mut by_name = HashMap::new();
+        for (_, mut exposed) in by_type {
+            if by_short_path[exposed.short_path].len() > 1 {
+                exposed.name = exposed.path;
+            }
+            let exposed = Arc::new(exposed);
+            by_name.insert(exposed.name, exposed.clone());
+            by_name.insert(exposed.path, exposed);
+        }
+        Self { by_name }
+    }
+
+    fn get(&self, name: &str, kind: ExposedKind) -> Result<Arc<ExposedType>, ApiError> {
+        self.by_name
+            .get(name)
+            .filter(|exposed| exposed.kind == kind)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("{name} is not exposed")))
+    }
+
+    /// Whether the type named `name` (short or full type path) is exposed
+    pub fn is_exposed(&self, name: &str) -> bool {
+        self.by_name.contains_key(name)
+    }
+
+    /// Whether the type named `name` (short or full type path) is exposed and writable
+    pub fn is_writable(&self, name: &str) -> bool {
+        self.by_name
+            .get(name)
+            .is_some_and(|exposed| exposed.access == ReflectAccess::Writable)
+    }
+}
+
+enum ApiError {
+    BadRequest(String),
+    Forbidden(String),
+    NotFound(String),
+    Internal(String),
+    Unavailable,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::Forbidden(message) => (StatusCode::FORBIDDEN, message),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ApiError::Internal(message) => (StatusCode::INTERNAL_SERVER_ERROR, message),
+            ApiError::Unavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "the Bevy app is shutting down".to_string(),
+            ),
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+/// How a request body is applied to the current value
+#[derive(Clone, Copy)]
+enum Write {
+    Replace,
+    MergePatch,
+}
+
+/// Run `f` on the main thread with the world, the type registry and the allowlist
+async fn with_world<F>(ecs: &mut EcsHandle, f: F) -> Result<Json<Value>, ApiError>
+where
+    F: FnOnce(&mut World, &TypeRegistry, &ReflectApiAllowlist) -> Result<Value, ApiError>
+        + Send
+        + 'static,
+{
+    ecs.try_run_on_main_thread(move |ctx| {
+        let registry = ctx.world.resource::<AppTypeRegistry>().clone();
+        let allowlist = ctx.world.resource::<ReflectApiAllowlist>().clone();
+        let registry = registry.read();
+        f(ctx.world, &registry, &allowlist)
+    })
+    .await
+    .map_err(|_| ApiError::Unavailable)?
+    .map(Json)
+}
+
+async fn get_resource(
+    mut ecs: EcsHandle,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    with_world(&mut ecs, move |world, registry, allowlist| {
+        let exposed = allowlist.get(&name, ExposedKind::Resource)?;
+        read_resource(world, registry, &exposed)
+    })
+    .await
+}
+
+async fn put_resource(
+    ecs: EcsHandle,
+    Path(name): Path<String>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    write_resource(ecs, name, body, Write::Replace).await
+}
+
+async fn patch_resource(
+    ecs: EcsHandle,
+    Path(name): Path<String>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    write_resource(ecs, name, body, Write::MergePatch).await
+}
+
+async fn write_resource(
+    mut ecs: EcsHandle,
+    name: String,
+    body: Value,
+    write: Write,
+) -> Result<Json<Value>, ApiError> {
+    with_world(&mut ecs, move |world, registry, allowlist| {
+        let exposed = allowlist.get(&name, ExposedKind::Resource)?;
+        exposed.check_writable()?;
+        let reflect_resource = type_data::<ReflectResource>(registry, &exposed)?;
+        let value = match write {
+            Write::Replace => body,
+            Write::MergePatch => {
+                let mut current = read_resource(world, registry, &exposed)?;
+                merge_patch(&mut current, body);
+                current
+            }
+        };
+        let value = deserialize(registry, &exposed, value)?;
+        reflect_resource.apply_or_insert(world, value.as_ref(), registry);
+        read_resource(world, registry, &exposed)
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct EntitiesQuery {
+    with: Option<String>,
+}
+
+async fn list_entities(
+    mut ecs: EcsHandle,
+    Query(query): Query<EntitiesQuery>,
+) -> Result<Json<Value>, ApiError> {
+    with_world(&mut ecs, move |world, registry, allowlist| {
+        let components = match &query.with {
+            Some(with) => {
+                let mut listed: Vec<Arc<ExposedType>> = Vec::new();
+                for name in with.split(',') {
+                    let exposed = allowlist.get(name.trim(), ExposedKind::Component)?;
+                    // The short and the full type path name the same component.
+                    if !listed.iter().any(|l| l.type_id == exposed.type_id) {
+                        listed.push(exposed);
+                    }
+                }
+                listed
+            }
+            None => {
+                let mut all: Vec<_> = allowlist
+                    .by_name
+                    .values()
+                    .filter(|exposed| exposed.kind == ExposedKind::Component)
+                    .cloned()
+                    .collect();
+                all.sort_by_key(|exposed| exposed.path);
+                all.dedup_by_key(|exposed| exposed.type_id);
+                all
+            }
+        };
+        let components = components
+            .into_iter()
+            .map(|exposed| Ok((type_data::<ReflectComponent>(registry, &exposed)?, exposed)))
+            .collect::<Result<Vec<_>, ApiError>>()?;
+        // Without a filter, list the entities having any exposed component.
+        let require_all = query.with.is_some();
+
+        let mut entities = Vec::new();
+        let mut all_entities = world.query::<EntityRef>();
+        for entity in all_entities.iter(world) {
+            let mut values = Map::new();
+            for (reflect_component, exposed) in &components {
+                if let Some(value) = reflect_component.reflect(entity) {
+                    values.insert(
+                        exposed.name.to_string(),
+                        serialize(registry, value.as_partial_reflect())?,
+                    );
+                }
+            }
+            let matches = if require_all {
+                values.len() == components.len()
+            } else {
+                !values.is_empty()
+            };
+            if matches {
+                entities.push(json!({ "entity": entity.id().to_bits(), "components": values }));
+            }
+        }
+        Ok(Value::Array(entities))
+    })
+    .await
+}
+
+async fn get_component(
+    mut ecs: EcsHandle,
+    Path((id, name)): Path<(u64, String)>,
+) -> Result<Json<Value>, ApiError> {
+    with_world(&mut ecs, move |world, registry, allowlist| {
+        let exposed = allowlist.get(&name, ExposedKind::Component)?;
+        read_component(world, registry, &exposed, id)
+    })
+    .await
+}
+
+async fn patch_component(
+    mut ecs: EcsHandle,
+    Path((id, name)): Path<(u64, String)>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    with_world(&mut ecs, move |world, registry, allowlist| {
+        let exposed = allowlist.get(&name, ExposedKind::Component)?;
+        exposed.check_writable()?;
+        let reflect_component = type_data::<ReflectComponent>(registry, &exposed)?;
+        let mut current = read_component(world, registry, &exposed, id)?;
+        merge_patch(&mut current, body);
+        let value = deserialize(registry, &exposed, current)?;
+        let entity = find_entity(world, id)?;
+        reflect_component.apply(world.entity_mut(entity), value.as_ref());
+        read_component(world, registry, &exposed, id)
+    })
+    .await
+}
+
+fn find_entity(world: &World, id: u64) -> Result<Entity, ApiError> {
+    Entity::try_from_bits(id)
+        .filter(|entity| world.entities().contains(*entity))
+        .ok_or_else(|| ApiError::NotFound(format!("entity {id} does not exist")))
+}
+
+fn type_data<'r, T: bevy::reflect::TypeData>(
+    registry: &'r TypeRegistry,
+    exposed: &ExposedType,
+) -> Result<&'r T, ApiError> {
+    registry
+        .get_type_data::<T>(exposed.type_id)
+        .ok_or_else(|| ApiError::NotFound(format!("{} is not registered", exposed.name)))
+}
+
+fn read_resource(
+    world: &World,
+    registry: &TypeRegistry,
+    exposed: &ExposedType,
+) -> Result<Value, ApiError> {
+    let value = type_data::<ReflectResource>(registry, exposed)?
+        .reflect(world)
+        .map_err(|_| ApiError::NotFound(format!("{} does not exist", exposed.name)))?;
+    serialize(registry, value.as_partial_reflect())
+}
+
+fn read_component(
+    world: &World,
+    registry: &TypeRegistry,
+    exposed: &ExposedType,
+    id: u64,
+) -> Result<Value, ApiError> {
+    let entity = find_entity(world, id)?;
+    let value = type_data::<ReflectComponent>(registry, exposed)?
+        .reflect(world.entity(entity))
+        .ok_or_else(|| ApiError::NotFound(format!("entity {id} has no {}", exposed.name)))?;
+    serialize(registry, value.as_partial_reflect())
+}
+
+fn serialize(registry: &TypeRegistry, value: &dyn PartialReflect) -> Result<Value, ApiError> {
+    serde_json::to_value(TypedReflectSerializer::new(value, registry))
+        .map_err(|err| ApiError::Internal(err.to_string()))
+}
+
+fn deserialize(
+    registry: &TypeRegistry,
+    exposed: &ExposedType,
+    value: Value,
+) -> Result<Box<dyn PartialReflect>, ApiError> {
+    let registration = registry
+        .get(exposed.type_id)
+        .ok_or_else(|| ApiError::NotFound(format!("{} is not registered", exposed.name)))?;
+    TypedReflectDeserializer::new(registration, registry)
+        .deserialize(value)
+        .map_err(|err| ApiError::BadRequest(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::WebRoutes;
+    use axum::body::Body;
+    use axum::http::Request;
+    use bevy_tokio_tasks::{TokioTasksPlugin, TokioTasksRuntime};
+    use std::collections::BTreeSet;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[derive(Resource, Reflect, Debug, PartialEq)]
+    struct ControlGains {
+        kp: f32,
+        ki: f32,
+    }
+
+    #[derive(Resource, Reflect)]
+    struct SensorData {
+        value: f32,
+    }
+
+    #[derive(Resource, Reflect)]
+    struct Secret {
+        value: f32,
+    }
+
+    #[derive(Component, Reflect, Debug, PartialEq)]
+    struct Motor {
+        rpm: f32,
+        enabled: bool,
+    }
+
+    #[derive(Component, Reflect)]
+    struct Label {
+        name: String,
+    }
+
+    mod arm {
+        #[derive(bevy::reflect::TypePath)]
+        pub struct Gains;
+    }
+
+    mod wheel {
+        #[derive(bevy::reflect::TypePath)]
+        pub struct Gains;
+    }
+
+    #[derive(TypePath)]
+    struct Pose;
+
+    #[test]
+    fn ambiguous_short_paths_require_the_full_path() {
+        let allowlist = ReflectApiAllowlist::new([
+            ExposedType::new::<arm::Gains>(ExposedKind::Resource, ReflectAccess::Writable),
+            ExposedType::new::<wheel::Gains>(ExposedKind::Resource, ReflectAccess::ReadOnly),
+            ExposedType::new::<Pose>(ExposedKind::Component, ReflectAccess::ReadOnly),
+        ]);
+
+        assert!(!allowlist.is_exposed("Gains"));
+        assert!(allowlist.is_writable(arm::Gains::type_path()));
+        assert!(!allowlist.is_writable(wheel::Gains::type_path()));
+        assert!(allowlist.is_exposed("Pose"));
+        assert!(allowlist.is_exposed(Pose::type_path()));
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default())
+            .add_plugins(
+                ReflectApiPlugin::new()
+                    .resource::<ControlGains>(ReflectAccess::Writable)
+                    .resource::<SensorData>(ReflectAccess::ReadOnly)
+                    .component::<Motor>(ReflectAccess::Writable)
+                    .component::<Label>(ReflectAccess::ReadOnly),
+            )
+            .insert_resource(ControlGains { kp: 1.0, ki: 0.5 })
+            .insert_resource(SensorData { value: 3.0 })
+            .insert_resource(Secret { value: 4.0 });
+        app
+    }
+
+    /// Send `method uri` with an optional JSON body to the routes of `app`, returning the status and the JSON response
+    fn request(app: &mut App, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => request
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+        let routes = app.world().resource::<WebRoutes>().clone();
+
+        let handle = app
+            .world()
+            .resource::<TokioTasksRuntime>()
+            .spawn_background_task(|ctx| async move {
+                let response = routes.router_with_ecs(ctx).oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, serde_json::from_slice(&body).unwrap())
+            });
+        while !handle.is_finished() {
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let runtime = app.world().resource::<TokioTasksRuntime>();
+        runtime.runtime().block_on(handle).unwrap()
+    }
+
+    /// The ids of the listed entities
+    fn listed(entities: &Value) -> BTreeSet<u64> {
+        entities
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entity| entity["entity"].as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn resources_are_read_replaced_and_patched() {
+        let mut app = app();
+
+        let response = request(&mut app, "GET", "/ecs/resources/ControlGains", None);
+        assert_eq!(response, (StatusCode::OK, json!({ "kp": 1.0, "ki": 0.5 })));
+
+        let body = json!({ "kp": 2.0, "ki": 0.0 });
+        let response = request(&mut app, "PUT", "/ecs/resources/ControlGains", Some(body));
+        assert_eq!(response, (StatusCode::OK, json!({ "kp": 2.0, "ki": 0.0 })));
+
+        let path = format!("/ecs/resources/{}", ControlGains::type_path());
+        let response = request(&mut app, "PATCH", &path, Some(json!({ "ki": 0.25 })));
+        assert_eq!(response, (StatusCode::OK, json!({ "kp": 2.0, "ki": 0.25 })));
+        assert_eq!(
+            app.world().resource::<ControlGains>(),
+            &ControlGains { kp: 2.0, ki: 0.25 }
+        );
+    }
+
+    #[test]
+    fn writes_to_read_only_types_are_forbidden() {
+        let mut app = app();
+        let robot = app.world_mut().spawn(Label { name: "arm".into() }).id();
+        let label = format!("/ecs/entities/{}/Label", robot.to_bits());
+
+        let (status, _) = request(&mut app, "GET", "/ecs/resources/SensorData", None);
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = request(&mut app, "GET", &label, None);
+        assert_eq!(status, StatusCode::OK);
+
+        let body = json!({ "value": 1.0 });
+        for method in ["PUT", "PATCH"] {
+            let (status, _) = request(
+                &mut app,
+                method,
+                "/ecs/resources/SensorData",
+                Some(body.clone()),
+            );
+            assert_eq!(status, StatusCode::FORBIDDEN, "{method}");
+        }
+        let (status, _) = request(&mut app, "PATCH", &label, Some(json!({ "name": "wheel" })));
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(app.world().resource::<SensorData>().value, 3.0);
+        assert_eq!(app.world().get::<Label>(robot).unwrap().name, "arm");
+    }
+
+    #[test]
+    fn types_that_are_not_exposed_are_not_found() {
+        let mut app = app();
+        let robot = app
+            .world_mut()
+            .spawn(Motor {
+                rpm: 0.0,
+                enabled: true,
+            })
+            .id();
+
+        let uris = [
+            "/ecs/resources/Secret".to_string(),
+            // Components are not resources and the other way around.
+            "/ecs/resources/Motor".to_string(),
+            format!("/ecs/entities/{}/ControlGains", robot.to_bits()),
+            "/ecs/entities?with=Motor,Secret".to_string(),
+        ];
+        for uri in uris {
+            let (status, _) = request(&mut app, "GET", &uri, None);
+            assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+        }
+        let body = json!({ "value": 1.0 });
+        let (status, _) = request(&mut app, "PATCH", "/ecs/resources/Secret", Some(body));
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(app.world().resource::<Secret>().value, 4.0);
+    }
+
+    #[test]
+    fn entities_are_listed_by_component() {
+        let mut app = app();
+        let motor = || Motor {
+            rpm: 10.0,
+            enabled: true,
+        };
+        let both = app
+            .world_mut()
+            .spawn((motor(), Label { name: "arm".into() }))
+            .id();
+        let motor_only = app.world_mut().spawn(motor()).id();
+        let label_only = app
+            .world_mut()
+            .spawn(Label {
+                name: "wheel".into(),
+            })
+            .id();
+        app.world_mut().spawn_empty();
+
+        let (status, entities) = request(&mut app, "GET", "/ecs/entities?with=Motor,Label", None);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            entities,
+            json!([{
+                "entity": both.to_bits(),
+                "components": { "Motor": { "rpm": 10.0, "enabled": true }, "Label": { "name": "arm" } },
+            }])
+        );
+
+        // Naming a component by its short and its full type path requires it once.
+        let uri = format!("/ecs/entities?with=Motor,{}", Motor::type_path());
+        let (status, entities) = request(&mut app, "GET", &uri, None);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            listed(&entities),
+            BTreeSet::from([both.to_bits(), motor_only.to_bits()])
+        );
+
+        let (_, entities) = request(&mut app, "GET", "/ecs/entities", None);
+        assert_eq!(
+            listed(&entities),
+            BTreeSet::from([both.to_bits(), motor_only.to_bits(), label_only.to_bits()])
+        );
+    }
+
+    #[test]
+    fn components_are_patched() {
+        let mut app = app();
+        let robot = app
+            .world_mut()
+            .spawn(Motor {
+                rpm: 10.0,
+                enabled: true,
+            })
+            .id();
+        let uri = format!("/ecs/entities/{}/Motor", robot.to_bits());
+
+        let response = request(&mut app, "PATCH", &uri, Some(json!({ "rpm": 20.0 })));
+        assert_eq!(
+            response,
+            (StatusCode::OK, json!({ "rpm": 20.0, "enabled": true }))
+        );
+        assert_eq!(
+            app.world().get::<Motor>(robot),
+            Some(&Motor {
+                rpm: 20.0,
+                enabled: true
+            })
+        );
+
+        let response = request(&mut app, "PATCH", &uri, Some(json!({ "rpm": "fast" })));
+        assert_eq!(response.0, StatusCode::BAD_REQUEST);
+        app.world_mut().despawn(robot);
+        let (status, _) = request(&mut app, "PATCH", &uri, Some(json!({ "rpm": 30.0 })));
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::server::AppRoutesExt;
+use async_channel::{Receiver, Sender};
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Extension, Json, Router};
+use bevy::prelude::*;
+use bevy::remote::{
+    error_codes, BrpBatch, BrpError, BrpMessage, BrpRequest, BrpResponse, BrpResult, BrpSender,
+    RemotePlugin,
+};
+use futures_lite::StreamExt;
+use serde_json::Value;
+use std::convert::Infallible;
+use std::sync::{Arc, OnceLock};
+use subtle::ConstantTimeEq;
+
+/// Serves the Bevy Remote Protocol (BRP) from the ABW web server
+///
+/// Requires the `remote` feature.
+///
+/// Adds Bevy's `RemotePlugin` (unless it was already added, e.g. with custom methods) and contributes a
+/// `POST` route accepting JSON-RPC 2.0 requests and batches, like Bevy's own `RemoteHttpPlugin` but
+/// without opening a second port. The route is served by `AxumServerPlugin`, or by a Leptos app that
+/// merges `ecs_routes()` into its router, so inspector tooling can connect to headless servers.
+/// Watching methods (`+watch`) are streamed as server-sent events, like `RemoteHttpPlugin` does.
+///
+/// # Example
+/// ```ignore
+/// App::new()
+///     .add_plugins(ABWConfigPlugin::default())
+///     .add_plugins(AxumServerPlugin::default())
+///     .add_plugins(BrpEndpointPlugin::new(BrpAuth::BearerToken(std::env::var("BRP_TOKEN")?)).at("/remote"))
+///     .run();
+/// ```
+pub struct BrpEndpointPlugin {
+    path: String,
+    auth: BrpAuth,
+}
+
+impl BrpEndpointPlugin {
+    /// Create an endpoint at `/brp` protected by `auth`
+    ///
+    /// # Arguments
+    /// * `auth` - How requests are authenticated, use `BrpAuth::None` only on trusted networks
+    pub fn new(auth: BrpAuth) -> Self {
+        Self {
+            path: "/brp".to_string(),
+            auth,
+        }
+    }
+
+    /// Mount the endpoint at `path` instead of `/brp`
+    pub fn at(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+}
+
+/// How `BrpEndpointPlugin` authenticates requests
+///
+/// Unauthenticated requests are answered with `401 Unauthorized`.
+#[derive(Clone)]
+pub enum BrpAuth {
+    /// Accept every request
+    None,
+    /// Require an `Authorization: Bearer <token>` header
+    BearerToken(String),
+    /// Accept the requests for which the function returns `true`
+    Custom(Arc<dyn Fn(&HeaderMap) -> bool + Send + Sync>),
+}
+
+impl BrpAuth {
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        match self {
+            BrpAuth::None => true,
+            BrpAuth::BearerToken(token) => headers
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                // Compared in constant time, so response times do not reveal the token.
+                .is_some_and(|provided| provided.as_bytes().ct_eq(token.as_bytes()).into()),
+            BrpAuth::Custom(authorize) => authorize(headers),
+        }
+    }
+}
+
+/// The sender of Bevy's BRP mailbox, captured once it has been created in `PreStartup`
+#[derive(Clone, Default)]
+struct BrpMailbox(Arc<OnceLock<Sender<BrpMessage>>>);
+
+impl Plugin for BrpEndpointPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<RemotePlugin>() {
+            app.add_plugins(RemotePlugin::default());
+        }
+
+        let mailbox = BrpMailbox::default();
+        let capture = mailbox.clone();
+        app.add_systems(Startup, move |sender: Res<BrpSender>| {
+            let _ = capture.0.set((**sender).clone());
+        })
+        .add_routes(
+            Router::new()
+                .route(&self.path, post(handle_brp_request))
+                .layer(Extension(mailbox))
+                .layer(Extension(self.auth.clone())),
+        );
+    }
+}
+
+async fn handle_brp_request(
+    Extension(mailbox): Extension<BrpMailbox>,
+    Extension(auth): Extension<BrpAuth>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !auth.is_authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let Some(sender) = mailbox.0.get() else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    let batch = match serde_json::from_slice::<BrpBatch>(&body) {
+        Ok(batch) => batch,
+        Err(err) => {
+            return Json(BrpResponse::new(
+                None,
+                Err(BrpError {
+                    code: error_codes::PARSE_ERROR,
+                    message: err.to_string(),
+                    data: None,
+                }),
+            ))
+            .into_response()
+        }
+    };
+
+    match batch {
+        BrpBatch::Single(request) => match process_request(request, sender).await {
+            Some(BrpReply::Complete(response)) => Json(response).into_response(),
+            Some(BrpReply::Stream(id, results)) => {
+                let events = results.map(move |result| {
+                    let response = serde_json::to_string(&BrpResponse::new(id.clone(), result))
+                        .unwrap_or_default();
+                    Ok::<_, Infallible>(format!("data: {response}\n\n"))
+                });
+                (
+                    [(header::CONTENT_TYPE, "text/event-stream")],
+                    Body::from_stream(events),
+                )
+                    .into_response()
+            }
+            None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+        },
+        BrpBatch::Batch(requests) => {
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in requests {
+                let response = match process_request(request, sender).await {
+                    Some(BrpReply::Complete(response)) => response,
+                    // Watching methods cannot be streamed as part of a batch, answer with their first result.
+                    Some(BrpReply::Stream(id, results)) => match results.recv().await {
+                        Ok(result) => BrpResponse::new(id, result),
+                        Err(_) => return StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                    },
+                    None => return StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                };
+                responses.push(response);
+            }
+            Json(responses).into_response()
+        }
+    }
+}
+
+enum BrpReply {
+    Complete(BrpResponse),
+    Stream(Option<Value>, Receiver<BrpResult>),
+}
+
+/// Forward one request to the BRP mailbox, returns `None` if the app has shut down
+async fn process_request(request: Value, sender: &Sender<BrpMessage>) -> Option<BrpReply> {
+    // Reach in and get the request ID early so that we can report it even when parsing fails.
+    let id = request.as_object().and_then(|map| map.get("id")).cloned();
+    let request = match serde_json::from_value::<BrpRequest>(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(_) => {
+            return Some(invalid_request(
+                id,
+                "JSON-RPC request requires `\"jsonrpc\": \"2.0\"`",
+            ))
+        }
+        Err(err) => return Some(invalid_request(id, &err.to_string())),
+    };
+
+    let watch = request.method.contains("+watch");
+    let (result_sender, result_receiver) = async_channel::bounded(if watch { 8 } else { 1 });
+    let message = BrpMessage {
+        method: request.method,
+        params: request.params,
+        sender: result_sender,
+    };
+    sender.send(message).await.ok()?;
+
+    if watch {
+        return Some(BrpReply::Stream(request.id, result_receiver));
+    }
+    let result = result_receiver.recv().await.ok()?;
+    Some(BrpReply::Complete(BrpResponse::new(request.id, result)))
+}
+
+fn invalid_request(id: Option<Value>, message: &str) -> BrpReply {
+    BrpReply::Complete(BrpResponse::new(
+        id,
+        Err(BrpError {
+            code: error_codes::INVALID_REQUEST,
+            message: message.to_string(),
+            data: None,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use futures_lite::future;
+    use tower::ServiceExt;
+
+    /// Post a BRP request with the `Authorization` header `authorization` to an endpoint protected by
+    /// `auth`, whose mailbox is not ready yet
+    fn post_brp(auth: BrpAuth, authorization: Option<&str>) -> StatusCode {
+        let router = Router::new()
+            .route("/brp", post(handle_brp_request))
+            .layer(Extension(BrpMailbox::default()))
+            .layer(Extension(auth));
+        let mut request = Request::post("/brp");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        let request = request
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","id":1,"method":"rpc.discover"}"#,
+            ))
+            .unwrap();
+        future::block_on(router.oneshot(request)).unwrap().status()
+    }
+
+    fn bearer() -> BrpAuth {
+        BrpAuth::BearerToken("secret".to_string())
+    }
+
+    #[test]
+    fn missing_or_wrong_tokens_are_rejected() {
+        assert_eq!(post_brp(bearer(), None), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            post_brp(bearer(), Some("Bearer guess")),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            post_brp(bearer(), Some("Bearer secret2")),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(post_brp(bearer(), Some("secret")), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn the_right_token_is_accepted() {
+        // Past authentication, the request waits for the mailbox created at startup.
+        assert_eq!(
+            post_brp(bearer(), Some("Bearer secret")),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            post_brp(BrpAuth::None, None),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
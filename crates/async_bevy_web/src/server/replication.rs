@@ -0,0 +1,432 @@
+use crate::server::{Connection, WsMessage};
+use abw_client::protocol::{
+    merge_diff, EntityComponents, EntityDelta, EntityId, ReplicationMessage,
+};
+use bevy::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+pub use abw_client::protocol::ReplicatedComponent;
+
+/// Marks an entity whose registered components are replicated to `WebSocketPlugin` clients
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Replicated;
+
+/// Replicates the components registered with `app.replicate::<C>()` of `Replicated` entities to the
+/// clients of `WebSocketPlugin`
+///
+/// Every client receives a snapshot of the entities visible to it when it connects, then at most one
+/// `ReplicationMessage::Delta` per frame with the entities that changed: components that were added
+/// are sent whole, components that changed as a JSON merge patch of their previous value, and
+/// components whose serialized value did not change are not sent. Changes are collected in `Last`.
+///
+/// By default every client sees every replicated entity. Insert a `ReplicationFilter` on a
+/// `Connection` entity to restrict what that client sees; entities entering and leaving its view are
+/// sent as spawns and despawns. The browser side is the `abw_client` crate, which requires the
+/// endpoint to use the JSON codec.
+///
+/// # Example
+/// ```ignore
+/// app.add_plugins(WebSocketPlugin::new("/ws"))
+///     .add_plugins(ReplicationPlugin)
+///     .replicate::<AxisPosition>()
+///     .replicate::<ProgramState>();
+///
+/// commands.spawn((Replicated, AxisPosition::default(), Cell(2)));
+/// ```
+pub struct ReplicationPlugin;
+
+impl Plugin for ReplicationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplicationState>()
+            .configure_sets(
+                Last,
+                (ReplicationSystems::Collect, ReplicationSystems::Send).chain(),
+            )
+            .add_systems(Last, send_replication.in_set(ReplicationSystems::Send));
+    }
+}
+
+/// System sets of the replication systems, in `Last`
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReplicationSystems {
+    /// Collects the changes of every replicated component type
+    Collect,
+    /// Sends snapshots and deltas to the clients
+    Send,
+}
+
+/// Lets plugins register replicated component types
+pub trait AppReplicationExt {
+    /// Replicate the component `C` of `Replicated` entities, requires `ReplicationPlugin`
+    fn replicate<C: Component + ReplicatedComponent>(&mut self) -> &mut Self;
+}
+
+impl AppReplicationExt for App {
+    fn replicate<C: Component + ReplicatedComponent>(&mut self) -> &mut Self {
+        self.add_systems(
+            Last,
+            collect_component_changes::<C>.in_set(ReplicationSystems::Collect),
+        )
+    }
+}
+
+impl WsMessage for ReplicationMessage {
+    const NAME: &'static str = ReplicationMessage::KIND;
+}
+
+/// Restricts the replicated entities a client sees
+///
+/// Insert it on the client's `Connection` entity, e.g. when it authenticates. The predicate can read
+/// any component of the entity, but it is only evaluated for the entities whose replicated
+/// components changed, and for every entity when the filter is inserted or replaced. If it depends
+/// on components that are not replicated, insert the filter again when they change.
+///
+/// # Example
+/// ```ignore
+/// commands.entity(connection).insert(ReplicationFilter::new(move |entity| {
+///     entity.get::<Cell>().is_some_and(|cell| cell.0 == operator_cell)
+/// }));
+/// ```
+#[derive(Component, Clone)]
+pub struct ReplicationFilter(Arc<dyn Fn(&EntityRef) -> bool + Send + Sync>);
+
+impl ReplicationFilter {
+    /// Show the client the replicated entities for which `filter` returns `true`
+    pub fn new(filter: impl Fn(&EntityRef) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(filter))
+    }
+}
+
+#[derive(Resource, Default)]
+struct ReplicationState {
+    /// Last replicated value of the components of every replicated entity
+    entities: HashMap<Entity, EntityComponents>,
+    /// Changes collected this frame
+    changes: HashMap<Entity, EntityDelta>,
+}
+
+impl ReplicationState {
+    fn set_component(&mut self, entity: Entity, name: &str, value: Value) {
+        let components = self.entities.entry(entity).or_default();
+        let delta = match components.get(name) {
+            Some(previous) if *previous == value => return,
+            Some(previous) => merge_diff(previous, &value),
+            None => None,
+        };
+        let changes = self.changes.entry(entity).or_default();
+        match delta {
+            Some(patch) => changes.patch.insert(name.to_string(), patch),
+            None => changes.set.insert(name.to_string(), value.clone()),
+        };
+        components.insert(name.to_string(), value);
+    }
+
+    fn remove_component(&mut self, entity: Entity, name: &str) {
+        let Some(components) = self.entities.get_mut(&entity) else {
+            return;
+        };
+        if components.remove(name).is_some() {
+            let changes = self.changes.entry(entity).or_default();
+            changes.set.remove(name);
+            changes.patch.remove(name);
+            changes.removed.push(name.to_string());
+        }
+    }
+}
+
+/// Per client replication state, on the `Connection` entity
+#[derive(Component, Default)]
+struct ReplicationClient {
+    visible: HashSet<Entity>,
+    /// Whether the client has the current state, it gets a snapshot otherwise
+    synced: bool,
+}
+
+fn collect_component_changes<C: Component + ReplicatedComponent>(
+    mut state: ResMut<ReplicationState>,
+    components: Query<(Entity, Ref<C>, Ref<Replicated>)>,
+    mut removed: RemovedComponents<C>,
+) {
+    // Removals first, so that a component removed and inserted again this frame is replicated.
+    for entity in removed.read() {
+        state.remove_component(entity, C::NAME);
+    }
+    for (entity, component, replicated) in &components {
+        if !component.is_changed() && !replicated.is_added() {
+            continue;
+        }
+        match serde_json::to_value(&*component) {
+            Ok(value) => state.set_component(entity, C::NAME, value),
+            Err(err) => log::error!(
+                "failed to serialize replicated `{}` of {entity}: {err}",
+                C::NAME
+            ),
+        }
+    }
+}
+
+type ClientQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Connection,
+        Option<Ref<'static, ReplicationFilter>>,
+        Option<&'static mut ReplicationClient>,
+    ),
+    Without<Replicated>,
+>;
+
+fn send_replication(
+    mut state: ResMut<ReplicationState>,
+    mut unreplicated: RemovedComponents<Replicated>,
+    mut unfiltered: RemovedComponents<ReplicationFilter>,
+    replicated: Query<EntityRef, With<Replicated>>,
+    mut clients: ClientQuery,
+    mut commands: Commands,
+) {
+    // Entities that were despawned or lost `Replicated` leave the view of every client below.
+    let mut left = Vec::new();
+    for entity in unreplicated.read() {
+        if !replicated.contains(entity) && state.entities.remove(&entity).is_some() {
+            state.changes.remove(&entity);
+            left.push(entity);
+        }
+    }
+    let unfiltered: HashSet<Entity> = unfiltered.read().collect();
+
+    let is_visible = |filter: Option<&ReplicationFilter>, entity: Entity| {
+        filter.is_none_or(|filter| {
+            replicated
+                .get(entity)
+                .is_ok_and(|entity| (filter.0)(&entity))
+        })
+    };
+
+    for (client_entity, connection, filter, client) in &mut clients {
+        let Some(mut client) = client.filter(|client| client.synced) else {
+            let visible: HashSet<Entity> = state
+                .entities
+                .keys()
+                .copied()
+                .filter(|entity| is_visible(filter.as_deref(), *entity))
+                .collect();
+            let entities = visible
+                .iter()
+                .map(|entity| (entity.to_bits(), state.entities[entity].clone()))
+                .collect();
+            let synced = send(connection, ReplicationMessage::Snapshot { entities });
+            commands
+                .entity(client_entity)
+                .try_insert(ReplicationClient { visible, synced });
+            continue;
+        };
+
+        let mut despawned: Vec<EntityId> = left
+            .iter()
+            .filter(|entity| client.visible.remove(*entity))
+            .map(|entity| entity.to_bits())
+            .collect();
+
+        // Visibility can only change for the entities that changed, unless the filter did.
+        let refilter = filter.as_ref().is_some_and(|filter| filter.is_changed())
+            || unfiltered.contains(&client_entity);
+        let candidates: Box<dyn Iterator<Item = &Entity>> = if refilter {
+            Box::new(state.entities.keys())
+        } else {
+            Box::new(state.changes.keys())
+        };
+
+        let mut changed: BTreeMap<EntityId, EntityDelta> = BTreeMap::new();
+        for &entity in candidates {
+            let was_visible = client.visible.contains(&entity);
+            if !is_visible(filter.as_deref(), entity) {
+                if was_visible {
+                    client.visible.remove(&entity);
+                    despawned.push(entity.to_bits());
+                }
+            } else if !was_visible {
+                client.visible.insert(entity);
+                let delta = EntityDelta {
+                    set: state.entities[&entity].clone(),
+                    ..default()
+                };
+                changed.insert(entity.to_bits(), delta);
+            } else if let Some(delta) = state.changes.get(&entity) {
+                changed.insert(entity.to_bits(), delta.clone());
+            }
+        }
+        changed.retain(|_, delta| !delta.is_empty());
+
+        if !changed.is_empty() || !despawned.is_empty() {
+            client.synced = send(connection, ReplicationMessage::Delta { changed, despawned });
+        }
+    }
+
+    state.changes.clear();
+}
+
+/// Send a replication message, returns whether the client is still in sync
+fn send(connection: &Connection, message: ReplicationMessage) -> bool {
+    match connection.send(&message) {
+        Ok(()) => true,
+        Err(err) => {
+            log::warn!(
+                "replication to WebSocket client {} failed, it will be resynced: {err}",
+                connection.id()
+            );
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abw_client::protocol::Envelope;
+    use axum::extract::ws::Message as WsFrame;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Position(f64);
+
+    impl ReplicatedComponent for Position {
+        const NAME: &'static str = "Position";
+    }
+
+    /// Not replicated, read by the filters
+    #[derive(Component)]
+    struct Cell(u8);
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(ReplicationPlugin).replicate::<Position>();
+        app
+    }
+
+    fn in_cell(cell: u8) -> ReplicationFilter {
+        ReplicationFilter::new(move |entity| entity.get::<Cell>().is_some_and(|c| c.0 == cell))
+    }
+
+    fn received(frames: &mut mpsc::Receiver<WsFrame>) -> Option<ReplicationMessage> {
+        let WsFrame::Text(text) = frames.try_recv().ok()? else {
+            panic!("expected a JSON text frame");
+        };
+        let envelope: Envelope<ReplicationMessage> = serde_json::from_str(&text).unwrap();
+        assert_eq!(envelope.kind, ReplicationMessage::KIND);
+        Some(envelope.data)
+    }
+
+    fn delta(changed: &[(Entity, EntityDelta)], despawned: &[Entity]) -> ReplicationMessage {
+        ReplicationMessage::Delta {
+            changed: changed
+                .iter()
+                .map(|(entity, delta)| (entity.to_bits(), delta.clone()))
+                .collect(),
+            despawned: despawned.iter().map(|entity| entity.to_bits()).collect(),
+        }
+    }
+
+    fn set(value: Value) -> EntityDelta {
+        EntityDelta {
+            set: BTreeMap::from([(Position::NAME.to_string(), value)]),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn clients_get_a_snapshot_then_deltas() {
+        let mut app = app();
+        let robot = app.world_mut().spawn((Replicated, Position(1.0))).id();
+        let (connection, mut frames) = Connection::for_test(1);
+        app.world_mut().spawn(connection);
+
+        app.update();
+        assert_eq!(
+            received(&mut frames),
+            Some(ReplicationMessage::Snapshot {
+                entities: BTreeMap::from([(
+                    robot.to_bits(),
+                    BTreeMap::from([(Position::NAME.to_string(), json!(1.0))])
+                )]),
+            })
+        );
+
+        app.update();
+        assert_eq!(received(&mut frames), None);
+
+        app.world_mut().get_mut::<Position>(robot).unwrap().0 = 2.0;
+        let spawned = app.world_mut().spawn((Replicated, Position(5.0))).id();
+        app.update();
+        assert_eq!(
+            received(&mut frames),
+            Some(delta(
+                &[(robot, set(json!(2.0))), (spawned, set(json!(5.0)))],
+                &[]
+            ))
+        );
+
+        app.world_mut().despawn(robot);
+        app.update();
+        assert_eq!(received(&mut frames), Some(delta(&[], &[robot])));
+    }
+
+    #[test]
+    fn filters_are_evaluated_for_changed_entities_and_changed_filters() {
+        let mut app = app();
+        let near = app
+            .world_mut()
+            .spawn((Replicated, Position(1.0), Cell(1)))
+            .id();
+        let far = app
+            .world_mut()
+            .spawn((Replicated, Position(2.0), Cell(2)))
+            .id();
+        let (connection, mut frames) = Connection::for_test(1);
+        let client = app.world_mut().spawn((connection, in_cell(1))).id();
+
+        app.update();
+        let Some(ReplicationMessage::Snapshot { entities }) = received(&mut frames) else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(
+            entities.keys().copied().collect::<Vec<_>>(),
+            [near.to_bits()]
+        );
+
+        app.world_mut().get_mut::<Position>(far).unwrap().0 = 3.0;
+        app.update();
+        assert_eq!(received(&mut frames), None);
+
+        // A change of a component that is not replicated is seen when the filter is inserted again.
+        app.world_mut().get_mut::<Cell>(far).unwrap().0 = 1;
+        app.update();
+        assert_eq!(received(&mut frames), None);
+        app.world_mut().entity_mut(client).insert(in_cell(1));
+        app.update();
+        assert_eq!(
+            received(&mut frames),
+            Some(delta(&[(far, set(json!(3.0)))], &[]))
+        );
+
+        // Leaving the filter on a replicated change is a despawn.
+        let mut entity = app.world_mut().entity_mut(near);
+        entity.get_mut::<Cell>().unwrap().0 = 2;
+        entity.get_mut::<Position>().unwrap().0 = 4.0;
+        app.update();
+        assert_eq!(received(&mut frames), Some(delta(&[], &[near])));
+
+        app.world_mut()
+            .entity_mut(client)
+            .remove::<ReplicationFilter>();
+        app.update();
+        assert_eq!(
+            received(&mut frames),
+            Some(delta(&[(near, set(json!(4.0)))], &[]))
+        );
+    }
+}
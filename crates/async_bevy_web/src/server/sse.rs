@@ -0,0 +1,549 @@
+use crate::server::{AppRoutesExt, EcsHandle};
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Router};
+use bevy::ecs::query::QueryFilter;
+use bevy::prelude::*;
+use bevy_tokio_tasks::{CancellationToken, OwnedQueryData};
+use futures_lite::{future, stream};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Streams ECS state to browsers as Server-Sent Events on `GET /events/{topic}`
+///
+/// Every topic is bound to a resource, a message or a query and its payloads are serialized as JSON
+/// with serde:
+/// * resource topics send the resource when it changed
+/// * message topics send a JSON array of the messages written since the previous event
+/// * query topics send a JSON array of the query items when it differs from the previous event
+///
+/// Each topic sends at most `max_hz` events per second, changes in between are coalesced into the
+/// next event; pass `f64::INFINITY` to send an event every frame with changes. Events carry an `id`,
+/// so a reconnecting `EventSource` sends `Last-Event-ID` and receives the events it missed, as long as
+/// they are still in the topic's history. Ids are prefixed with an epoch of the server run, so ids
+/// from before a restart are not mistaken for new ones. New resource and query subscribers start with
+/// the latest state. Events are published in `Last`, in the `SseSystems` set, and streams end when the
+/// app exits.
+///
+/// # Example
+/// ```ignore
+/// app.add_plugins(
+///     SsePlugin::new()
+///         .resource::<BatteryState>("battery", 1.0)
+///         .message::<RobotAlert>("alerts", f64::INFINITY)
+///         .query::<(Entity, &RobotPose)>("poses", 10.0),
+/// );
+/// // new EventSource("/events/poses").onmessage = (e) => draw(JSON.parse(e.data));
+/// ```
+pub struct SsePlugin {
+    topics: Vec<TopicRegistration>,
+    history: usize,
+}
+
+struct TopicRegistration {
+    name: String,
+    min_interval: Duration,
+    kind: TopicKind,
+    register: fn(&mut App, Arc<SseTopic>),
+}
+
+impl Default for SsePlugin {
+    fn default() -> Self {
+        Self {
+            topics: Vec::new(),
+            history: 256,
+        }
+    }
+}
+
+impl SsePlugin {
+    /// Create a plugin with no topics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how many events of a message topic are kept for `Last-Event-ID` resume (default 256)
+    pub fn with_history(mut self, history: usize) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Publish the resource `R` on `topic` whenever it changes
+    ///
+    /// # Arguments
+    /// * `topic` - Name used in the route, `/events/{topic}`
+    /// * `max_hz` - Maximum number of events per second, panics unless positive
+    pub fn resource<R: Resource + Serialize>(self, topic: impl Into<String>, max_hz: f64) -> Self {
+        self.topic(topic, max_hz, TopicKind::State, |app, topic| {
+            app.add_systems(
+                Last,
+                (move |resource: Option<Res<R>>, mut dirty: Local<bool>| {
+                    let Some(resource) = resource else {
+                        return;
+                    };
+                    *dirty |= resource.is_changed();
+                    if *dirty && topic.is_ready() {
+                        topic.publish_json(&*resource);
+                        *dirty = false;
+                    }
+                })
+                .in_set(SseSystems),
+            );
+        })
+    }
+
+    /// Publish the messages `E` written in the ECS on `topic`
+    ///
+    /// # Arguments
+    /// * `topic` - Name used in the route, `/events/{topic}`
+    /// * `max_hz` - Maximum number of events per second, messages written in between are batched;
+    ///   panics unless positive
+    pub fn message<E: Message + Clone + Serialize>(
+        self,
+        topic: impl Into<String>,
+        max_hz: f64,
+    ) -> Self {
+        self.topic(topic, max_hz, TopicKind::Messages, |app, topic| {
+            app.add_message::<E>().add_systems(
+                Last,
+                (move |mut reader: MessageReader<E>, mut pending: Local<Vec<E>>| {
+                    pending.extend(reader.read().cloned());
+                    if !pending.is_empty() && topic.is_ready() {
+                        topic.publish_json(&*pending);
+                        pending.clear();
+                    }
+                })
+                .in_set(SseSystems),
+            );
+        })
+    }
+
+    /// Publish the items of the query `D` on `topic`
+    ///
+    /// # Arguments
+    /// * `topic` - Name used in the route, `/events/{topic}`
+    /// * `max_hz` - Maximum number of events per second, also how often the query is evaluated;
+    ///   panics unless positive
+    pub fn query<D>(self, topic: impl Into<String>, max_hz: f64) -> Self
+    where
+        D: OwnedQueryData + 'static,
+        D::Owned: Serialize,
+    {
+        self.query_filtered::<D, ()>(topic, max_hz)
+    }
+
+    /// Publish the items of the query `D` filtered by `F` on `topic`
+    ///
+    /// # Arguments
+    /// * `topic` - Name used in the route, `/events/{topic}`
+    /// * `max_hz` - Maximum number of events per second, also how often the query is evaluated;
+    ///   panics unless positive
+    pub fn query_filtered<D, F>(self, topic: impl Into<String>, max_hz: f64) -> Self
+    where
+        D: OwnedQueryData + 'static,
+        D::Owned: Serialize,
+        F: QueryFilter + 'static,
+    {
+        self.topic(topic, max_hz, TopicKind::State, |app, topic| {
+            app.add_systems(
+                Last,
+                (move |query: Query<D, F>, mut previous: Local<Option<String>>| {
+                    if !topic.begin_evaluation() {
+                        return;
+                    }
+                    let items: Vec<D::Owned> = query.iter().map(D::to_owned_item).collect();
+                    let Some(data) = to_json(&items) else {
+                        return;
+                    };
+                    if previous.as_deref() != Some(data.as_str()) {
+                        topic.publish(data.clone());
+                        *previous = Some(data);
+                    }
+                })
+                .in_set(SseSystems),
+            );
+        })
+    }
+
+    fn topic(
+        mut self,
+        name: impl Into<String>,
+        max_hz: f64,
+        kind: TopicKind,
+        register: fn(&mut App, Arc<SseTopic>),
+    ) -> Self {
+        let name = name.into();
+        assert!(
+            max_hz > 0.0,
+            "SSE topic `{name}` needs a positive max_hz, got {max_hz}"
+        );
+        let min_interval = if max_hz.is_finite() {
+            Duration::from_secs_f64(1.0 / max_hz)
+        } else {
+            Duration::ZERO
+        };
+        self.topics.push(TopicRegistration {
+            name,
+            min_interval,
+            kind,
+            register,
+        });
+        self
+    }
+}
+
+impl Plugin for SsePlugin {
+    fn build(&self, app: &mut App) {
+        let epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let mut topics = HashMap::new();
+        for registration in &self.topics {
+            if topics.contains_key(&registration.name) {
+                panic!(
+                    "SSE topic `{}` is bound more than once, give each binding its own topic",
+                    registration.name
+                );
+            }
+            let topic = Arc::new(SseTopic::new(registration, self.history, epoch));
+            (registration.register)(app, topic.clone());
+            topics.insert(registration.name.clone(), topic);
+        }
+
+        app.add_routes(
+            Router::new()
+                .route("/events/{topic}", get(stream_topic))
+                .layer(Extension(SseTopics(Arc::new(topics)))),
+        );
+    }
+}
+
+/// System set of the systems publishing the topics of `SsePlugin`, in `Last`
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SseSystems;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TopicKind {
+    /// Every event is a full state, only the latest one is kept
+    State,
+    /// Every event is a batch of messages, the last `history` ones are kept
+    Messages,
+}
+
+#[derive(Clone)]
+struct SseTopics(Arc<HashMap<String, Arc<SseTopic>>>);
+
+#[derive(Clone)]
+struct SseEvent {
+    id: u64,
+    data: Arc<str>,
+}
+
+struct SseTopic {
+    name: String,
+    /// Prefix of the event ids, distinct for every run of the server
+    epoch: u64,
+    min_interval: Duration,
+    kind: TopicKind,
+    capacity: usize,
+    sender: broadcast::Sender<SseEvent>,
+    state: Mutex<TopicState>,
+}
+
+struct TopicState {
+    next_id: u64,
+    last_published: Option<Instant>,
+    last_evaluated: Option<Instant>,
+    history: VecDeque<SseEvent>,
+}
+
+impl SseTopic {
+    fn new(registration: &TopicRegistration, history: usize, epoch: u64) -> Self {
+        let capacity = match registration.kind {
+            TopicKind::State => 1,
+            TopicKind::Messages => history.max(1),
+        };
+        Self {
+            name: registration.name.clone(),
+            epoch,
+            min_interval: registration.min_interval,
+            kind: registration.kind,
+            capacity,
+            sender: broadcast::channel(capacity.max(16)).0,
+            state: Mutex::new(TopicState {
+                next_id: 1,
+                last_published: None,
+                last_evaluated: None,
+                history: VecDeque::with_capacity(capacity),
+            }),
+        }
+    }
+
+    /// Whether the throttling interval elapsed since the last event
+    fn is_ready(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .last_published
+            .is_none_or(|last| last.elapsed() >= self.min_interval)
+    }
+
+    /// Whether the throttling interval elapsed since the topic's query was last evaluated, recording
+    /// this evaluation if so
+    fn begin_evaluation(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if state
+            .last_evaluated
+            .is_some_and(|last| now.duration_since(last) < self.min_interval)
+        {
+            return false;
+        }
+        state.last_evaluated = Some(now);
+        true
+    }
+
+    /// The `id` field of the event numbered `id`
+    fn event_id(&self, id: u64) -> String {
+        format!("{}-{id}", self.epoch)
+    }
+
+    /// The number of the event a `Last-Event-ID` refers to, `None` if it is from another run
+    fn parse_event_id(&self, last_event_id: &str) -> Option<u64> {
+        let (epoch, id) = last_event_id.trim().split_once('-')?;
+        if epoch.parse::<u64>().ok()? != self.epoch {
+            return None;
+        }
+        id.parse().ok()
+    }
+
+    fn publish_json<T: Serialize + ?Sized>(&self, value: &T) {
+        if let Some(data) = to_json(value) {
+            self.publish(data);
+        }
+    }
+
+    fn publish(&self, data: String) {
+        let mut state = self.state.lock().unwrap();
+        let event = SseEvent {
+            id: state.next_id,
+            data: data.into(),
+        };
+        state.next_id += 1;
+        state.last_published = Some(Instant::now());
+        if state.history.len() == self.capacity {
+            state.history.pop_front();
+        }
+        state.history.push_back(event.clone());
+        // Sent while holding the lock, so that subscribers never miss an event between their
+        // history snapshot and their receiver. Fails only when there are no subscribers.
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribe to new events and get the retained events to send first
+    fn subscribe(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (VecDeque<SseEvent>, broadcast::Receiver<SseEvent>) {
+        let state = self.state.lock().unwrap();
+        (self.backlog(&state, last_event_id), self.sender.subscribe())
+    }
+
+    /// Retained events after `last_event_id`
+    ///
+    /// Unknown ids, e.g. too old to be in the history, are treated like a new subscriber: state
+    /// topics start with the latest state and message topics with the next message.
+    fn backlog(&self, state: &TopicState, last_event_id: Option<u64>) -> VecDeque<SseEvent> {
+        let known = last_event_id.filter(|id| {
+            *id < state.next_id
+                && state
+                    .history
+                    .front()
+                    .is_some_and(|first| *id + 1 >= first.id)
+        });
+        match (known, self.kind) {
+            (Some(id), _) => state
+                .history
+                .iter()
+                .filter(|event| event.id > id)
+                .cloned()
+                .collect(),
+            (None, TopicKind::State) => state.history.back().cloned().into_iter().collect(),
+            (None, TopicKind::Messages) => VecDeque::new(),
+        }
+    }
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> Option<String> {
+    serde_json::to_string(value)
+        .inspect_err(|err| log::error!("failed to serialize SSE event: {err}"))
+        .ok()
+}
+
+struct TopicStream {
+    topic: Arc<SseTopic>,
+    backlog: VecDeque<SseEvent>,
+    receiver: broadcast::Receiver<SseEvent>,
+    last_sent: Option<u64>,
+    shutdown: CancellationToken,
+}
+
+impl TopicStream {
+    async fn next(&mut self) -> Option<SseEvent> {
+        loop {
+            if let Some(event) = self.backlog.pop_front() {
+                return Some(event);
+            }
+            let received = future::or(async { Some(self.receiver.recv().await) }, async {
+                self.shutdown.cancelled().await;
+                None
+            })
+            .await?;
+            match received {
+                // Skip events already sent from the backlog.
+                Ok(event) if self.last_sent.is_some_and(|id| event.id <= id) => continue,
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    let state = self.topic.state.lock().unwrap();
+                    self.backlog = self.topic.backlog(&state, self.last_sent);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+async fn stream_topic(
+    Path(topic): Path<String>,
+    Extension(topics): Extension<SseTopics>,
+    ecs: EcsHandle,
+    headers: HeaderMap,
+) -> Response {
+    let Some(topic) = topics.0.get(&topic).cloned() else {
+        return (StatusCode::NOT_FOUND, format!("unknown topic `{topic}`")).into_response();
+    };
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| topic.parse_event_id(value));
+    log::debug!(
+        "SSE subscriber on `{}` (Last-Event-ID {last_event_id:?})",
+        topic.name
+    );
+
+    let (backlog, receiver) = topic.subscribe(last_event_id);
+    let state = TopicStream {
+        topic,
+        backlog,
+        receiver,
+        last_sent: None,
+        shutdown: ecs.shutdown_token(),
+    };
+    let events = stream::unfold(state, |mut state| async move {
+        let event = state.next().await?;
+        state.last_sent = Some(event.id);
+        let sse = Event::default()
+            .id(state.topic.event_id(event.id))
+            .data(&*event.data);
+        Some((Ok::<_, Infallible>(sse), state))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPOCH: u64 = 42;
+
+    fn topic(kind: TopicKind, min_interval: Duration, history: usize) -> SseTopic {
+        let registration = TopicRegistration {
+            name: "test".to_string(),
+            min_interval,
+            kind,
+            register: |_, _| {},
+        };
+        SseTopic::new(&registration, history, EPOCH)
+    }
+
+    fn backlog_ids(topic: &SseTopic, last_event_id: &str) -> Vec<u64> {
+        let (backlog, _) = topic.subscribe(topic.parse_event_id(last_event_id));
+        backlog.iter().map(|event| event.id).collect()
+    }
+
+    #[derive(Resource, Serialize)]
+    struct Battery(f32);
+
+    #[test]
+    #[should_panic(expected = "SSE topic `battery` is bound more than once")]
+    fn duplicate_topics_are_rejected() {
+        App::new().add_plugins(
+            SsePlugin::new()
+                .resource::<Battery>("battery", 1.0)
+                .resource::<Battery>("battery", 10.0),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "SSE topic `battery` needs a positive max_hz")]
+    fn max_hz_must_be_positive() {
+        SsePlugin::new().resource::<Battery>("battery", f64::NAN);
+    }
+
+    #[test]
+    fn query_evaluation_is_throttled() {
+        let throttled = topic(TopicKind::State, Duration::from_secs(3600), 1);
+        assert!(throttled.begin_evaluation());
+        assert!(!throttled.begin_evaluation());
+
+        let unthrottled = topic(TopicKind::State, Duration::ZERO, 1);
+        assert!(unthrottled.begin_evaluation());
+        assert!(unthrottled.begin_evaluation());
+    }
+
+    #[test]
+    fn publishing_is_throttled() {
+        let topic = topic(TopicKind::Messages, Duration::from_secs(3600), 4);
+        assert!(topic.is_ready());
+        topic.publish("[1]".to_string());
+        assert!(!topic.is_ready());
+    }
+
+    #[test]
+    fn message_topics_resume_after_last_event_id() {
+        let topic = topic(TopicKind::Messages, Duration::ZERO, 4);
+        for n in 1..=6 {
+            topic.publish(format!("[{n}]"));
+        }
+
+        assert_eq!(topic.event_id(4), "42-4");
+        assert_eq!(backlog_ids(&topic, "42-4"), vec![5, 6]);
+        assert_eq!(backlog_ids(&topic, "42-2"), vec![3, 4, 5, 6]);
+        assert_eq!(backlog_ids(&topic, "42-6"), Vec::<u64>::new());
+        // Too old to be in the history, or from another run of the server.
+        assert_eq!(backlog_ids(&topic, "42-1"), Vec::<u64>::new());
+        assert_eq!(backlog_ids(&topic, "7-4"), Vec::<u64>::new());
+        assert_eq!(backlog_ids(&topic, "4"), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn state_topics_start_with_the_latest_state() {
+        let topic = topic(TopicKind::State, Duration::ZERO, 4);
+        topic.publish("{\"charge\":0.5}".to_string());
+        topic.publish("{\"charge\":0.4}".to_string());
+
+        assert_eq!(backlog_ids(&topic, "42-1"), vec![2]);
+        assert_eq!(backlog_ids(&topic, "42-2"), Vec::<u64>::new());
+        assert_eq!(backlog_ids(&topic, "7-2"), vec![2]);
+        let (backlog, _) = topic.subscribe(None);
+        assert_eq!(&*backlog[0].data, "{\"charge\":0.4}");
+    }
+}
@@ -0,0 +1,735 @@
+use crate::server::{AppRoutesExt, EcsHandle};
+use axum::body::Bytes;
+use axum::extract::ws::{Message as WsFrame, WebSocket, WebSocketUpgrade};
+use axum::response::Response;
+use axum::routing::get;
+use axum::{Extension, Router};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_tokio_tasks::CancellationToken;
+use futures_lite::future;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// A typed message exchanged over the `WebSocketPlugin` endpoint
+///
+/// Every frame is an envelope `{ "kind": NAME, "data": message }` encoded with the endpoint's
+/// `WsCodec`, so several message types can share one socket. Register the types the server receives
+/// with `app.add_ws_message::<M>()`; types that are only sent need no registration.
+///
+/// # Example
+/// ```ignore
+/// #[derive(Serialize, Deserialize, Clone, Debug)]
+/// pub struct UserChatMessage {
+///     pub message: String,
+/// }
+///
+/// impl WsMessage for UserChatMessage {
+///     const NAME: &'static str = "chat:UserChatMessage";
+/// }
+/// ```
+pub trait WsMessage: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Unique name identifying the message type on the wire
+    const NAME: &'static str;
+}
+
+/// Serialization format of the `WebSocketPlugin` frames
+///
+/// JSON is sent as text frames, the binary formats as binary frames. Incoming frames are decoded
+/// with the same codec whatever their frame type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WsCodec {
+    /// JSON text frames, easy to use from browsers
+    #[default]
+    Json,
+    /// `bincode` 1.x binary frames, requires the `bincode` feature
+    #[cfg(feature = "bincode")]
+    Bincode,
+    /// MessagePack binary frames with named fields, requires the `msgpack` feature
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl WsCodec {
+    fn encode<T: Serialize>(self, value: &T) -> Result<WsFrame, WsError> {
+        match self {
+            WsCodec::Json => serde_json::to_string(value)
+                .map(|text| WsFrame::Text(text.into()))
+                .map_err(|err| WsError::Encode(err.to_string())),
+            #[cfg(feature = "bincode")]
+            WsCodec::Bincode => bincode::serialize(value)
+                .map(|bytes| WsFrame::Binary(bytes.into()))
+                .map_err(|err| WsError::Encode(err.to_string())),
+            #[cfg(feature = "msgpack")]
+            WsCodec::MessagePack => rmp_serde::to_vec_named(value)
+                .map(|bytes| WsFrame::Binary(bytes.into()))
+                .map_err(|err| WsError::Encode(err.to_string())),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, WsError> {
+        match self {
+            WsCodec::Json => {
+                serde_json::from_slice(bytes).map_err(|err| WsError::Decode(err.to_string()))
+            }
+            #[cfg(feature = "bincode")]
+            WsCodec::Bincode => {
+                bincode::deserialize(bytes).map_err(|err| WsError::Decode(err.to_string()))
+            }
+            #[cfg(feature = "msgpack")]
+            WsCodec::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|err| WsError::Decode(err.to_string()))
+            }
+        }
+    }
+
+    /// Encode `message` in its envelope
+    fn encode_message<M: WsMessage>(self, message: &M) -> Result<WsFrame, WsError> {
+        self.encode(&Envelope {
+            kind: M::NAME,
+            data: message,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    kind: &'a str,
+    data: &'a T,
+}
+
+/// The first field of an envelope, decoded on its own to find the message type
+#[derive(Deserialize)]
+struct EnvelopeKind {
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct OwnedEnvelope<T> {
+    #[allow(dead_code)]
+    kind: String,
+    data: T,
+}
+
+/// Error returned when sending or receiving WebSocket messages
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WsError {
+    /// The message could not be serialized
+    Encode(String),
+    /// An incoming frame could not be deserialized
+    Decode(String),
+    /// The entity has no `Connection`, e.g. because the client disconnected
+    NoSuchConnection(Entity),
+    /// The connection's outbound queue is full because the client does not keep up. The message was dropped.
+    Full,
+    /// The connection is closing
+    Closed,
+}
+
+impl fmt::Display for WsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WsError::Encode(err) => write!(f, "failed to encode WebSocket message: {err}"),
+            WsError::Decode(err) => write!(f, "failed to decode WebSocket message: {err}"),
+            WsError::NoSuchConnection(entity) => {
+                write!(f, "{entity} is not a WebSocket connection")
+            }
+            WsError::Full => f.write_str("WebSocket outbound queue is full"),
+            WsError::Closed => f.write_str("WebSocket connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for WsError {}
+
+/// Serves typed WebSockets on the ABW web server
+///
+/// Each accepted socket is spawned as an entity with a `Connection` component, which is despawned
+/// when the socket closes, and `WsConnectionEvent`s are written for both. Frames carrying a message
+/// registered with `add_ws_message` are written as `WsReceived<M>` messages in `PreUpdate`, in the
+/// `WsSystems` set, in the order they were received. Systems reply with `Connection::send` or the
+/// `WebSockets` system parameter. Despawning the entity (or removing `Connection`) closes the socket.
+///
+/// # Example
+/// ```ignore
+/// app.add_plugins(WebSocketPlugin::new("/ws"))
+///     .add_ws_message::<UserChatMessage>()
+///     .add_systems(Update, |mut received: MessageReader<WsReceived<UserChatMessage>>, sockets: WebSockets| {
+///         for chat in received.read() {
+///             let _ = sockets.broadcast(&NewChatMessage { name: chat.from.to_string(), message: chat.message.message.clone() });
+///         }
+///     });
+/// ```
+pub struct WebSocketPlugin {
+    path: String,
+    codec: WsCodec,
+    inbound_capacity: usize,
+    outbound_capacity: usize,
+}
+
+impl WebSocketPlugin {
+    /// Create a JSON WebSocket endpoint
+    ///
+    /// # Arguments
+    /// * `path` - Route of the endpoint, e.g. `/ws`
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            codec: WsCodec::Json,
+            inbound_capacity: 1024,
+            outbound_capacity: 256,
+        }
+    }
+
+    /// Set the codec of every frame (default `WsCodec::Json`)
+    pub fn with_codec(mut self, codec: WsCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Set how many incoming frames, of all connections, can be queued before sockets stop being read (default 1024)
+    pub fn with_inbound_capacity(mut self, capacity: usize) -> Self {
+        self.inbound_capacity = capacity;
+        self
+    }
+
+    /// Set how many outgoing messages are queued per connection before sends fail with `WsError::Full` (default 256)
+    pub fn with_outbound_capacity(mut self, capacity: usize) -> Self {
+        self.outbound_capacity = capacity;
+        self
+    }
+}
+
+impl Plugin for WebSocketPlugin {
+    fn build(&self, app: &mut App) {
+        let (events_tx, events_rx) = mpsc::channel(self.inbound_capacity.max(1));
+        let endpoint = WsEndpoint {
+            events_tx,
+            codec: self.codec,
+            outbound_capacity: self.outbound_capacity.max(1),
+            next_id: Arc::new(AtomicU64::new(1)),
+        };
+
+        app.add_message::<WsConnectionEvent>()
+            .init_resource::<WsMessageRegistry>()
+            .insert_resource(WsServer {
+                codec: self.codec,
+                events_rx,
+                connections: HashMap::new(),
+            })
+            .add_systems(PreUpdate, receive_ws_events.in_set(WsSystems))
+            .add_routes(
+                Router::new()
+                    .route(&self.path, get(upgrade_websocket))
+                    .layer(Extension(endpoint)),
+            );
+    }
+}
+
+/// System set in `PreUpdate` containing the system which spawns connections and writes received messages
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WsSystems;
+
+/// Lets plugins register the message types received over the `WebSocketPlugin` endpoint
+pub trait AppWebSocketExt {
+    /// Write every received `M` as a `WsReceived<M>` message
+    fn add_ws_message<M: WsMessage>(&mut self) -> &mut Self;
+}
+
+impl AppWebSocketExt for App {
+    fn add_ws_message<M: WsMessage>(&mut self) -> &mut Self {
+        self.add_message::<WsReceived<M>>();
+        let previous = self
+            .world_mut()
+            .get_resource_or_init::<WsMessageRegistry>()
+            .decoders
+            .insert(M::NAME, write_received_message::<M>);
+        if previous.is_some() {
+            log::warn!(
+                "WebSocket message `{}` is registered more than once",
+                M::NAME
+            );
+        }
+        self
+    }
+}
+
+/// A message `M` received from the client of the `Connection` entity `from`
+#[derive(Message, Debug, Clone)]
+pub struct WsReceived<M: WsMessage> {
+    /// The connection entity
+    pub from: Entity,
+    /// The decoded message
+    pub message: M,
+}
+
+/// Written when a client connects or disconnects
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsConnectionEvent {
+    /// A `Connection` entity was spawned for a new socket
+    Connected(Entity),
+    /// The socket closed and its entity was despawned
+    Disconnected(Entity),
+}
+
+/// Component of the entity representing one WebSocket client
+#[derive(Component, Debug)]
+pub struct Connection {
+    id: u64,
+    codec: WsCodec,
+    outbound_tx: mpsc::Sender<WsFrame>,
+}
+
+impl Connection {
+    /// Id of the connection, unique for the lifetime of the app
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Queue `message` for this client
+    pub fn send<M: WsMessage>(&self, message: &M) -> Result<(), WsError> {
+        self.send_frame(self.codec.encode_message(message)?)
+    }
+
+    fn send_frame(&self, frame: WsFrame) -> Result<(), WsError> {
+        self.outbound_tx.try_send(frame).map_err(|err| match err {
+            mpsc::error::TrySendError::Full(_) => WsError::Full,
+            mpsc::error::TrySendError::Closed(_) => WsError::Closed,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Connection {
+    /// A JSON connection whose outbound frames are read from the returned receiver
+    pub(crate) fn for_test(id: u64) -> (Self, mpsc::Receiver<WsFrame>) {
+        let (outbound_tx, outbound_rx) = mpsc::channel(16);
+        let connection = Self {
+            id,
+            codec: WsCodec::Json,
+            outbound_tx,
+        };
+        (connection, outbound_rx)
+    }
+}
+
+/// System parameter sending messages to the `WebSocketPlugin` clients
+#[derive(SystemParam)]
+pub struct WebSockets<'w, 's> {
+    connections: Query<'w, 's, (Entity, &'static Connection)>,
+    server: Res<'w, WsServer>,
+}
+
+impl WebSockets<'_, '_> {
+    /// Queue `message` for the client of the connection entity `to`
+    pub fn send<M: WsMessage>(&self, to: Entity, message: &M) -> Result<(), WsError> {
+        self.connections
+            .get(to)
+            .map_err(|_| WsError::NoSuchConnection(to))?
+            .1
+            .send(message)
+    }
+
+    /// Queue `message` for every connected client
+    ///
+    /// The message is encoded once. Clients whose queue is full miss it, which is logged; only encoding
+    /// errors are returned.
+    pub fn broadcast<M: WsMessage>(&self, message: &M) -> Result<(), WsError> {
+        let frame = self.server.codec.encode_message(message)?;
+        for (entity, connection) in &self.connections {
+            if let Err(WsError::Full) = connection.send_frame(frame.clone()) {
+                log::warn!("dropped `{}` for slow WebSocket client {entity}", M::NAME);
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterate over the connection entities
+    pub fn connections(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.connections.iter().map(|(entity, _)| entity)
+    }
+}
+
+type DecodeFn = fn(&mut World, Entity, WsCodec, &[u8]) -> Result<(), WsError>;
+
+#[derive(Resource, Default)]
+struct WsMessageRegistry {
+    decoders: HashMap<&'static str, DecodeFn>,
+}
+
+fn write_received_message<M: WsMessage>(
+    world: &mut World,
+    from: Entity,
+    codec: WsCodec,
+    bytes: &[u8],
+) -> Result<(), WsError> {
+    let envelope: OwnedEnvelope<M> = codec.decode(bytes)?;
+    world.write_message(WsReceived {
+        from,
+        message: envelope.data,
+    });
+    Ok(())
+}
+
+#[derive(Resource)]
+struct WsServer {
+    codec: WsCodec,
+    events_rx: mpsc::Receiver<WsEvent>,
+    /// Connection entities by connection id
+    connections: HashMap<u64, Entity>,
+}
+
+enum WsEvent {
+    Connected(Connection),
+    Received(u64, Bytes),
+    Disconnected(u64),
+}
+
+fn receive_ws_events(world: &mut World) {
+    world.resource_scope(|world, mut server: Mut<WsServer>| {
+        while let Ok(event) = server.events_rx.try_recv() {
+            match event {
+                WsEvent::Connected(connection) => {
+                    let id = connection.id;
+                    let entity = world.spawn(connection).id();
+                    server.connections.insert(id, entity);
+                    world.write_message(WsConnectionEvent::Connected(entity));
+                }
+                WsEvent::Received(id, bytes) => {
+                    let Some(&entity) = server.connections.get(&id) else {
+                        continue;
+                    };
+                    if let Err(err) = dispatch(world, server.codec, entity, &bytes) {
+                        log::warn!("dropped WebSocket frame from {entity}: {err}");
+                    }
+                }
+                WsEvent::Disconnected(id) => {
+                    let Some(entity) = server.connections.remove(&id) else {
+                        continue;
+                    };
+                    // The entity is already gone when the server closed the connection by despawning it.
+                    if let Ok(entity_mut) = world.get_entity_mut(entity) {
+                        entity_mut.despawn();
+                    }
+                    world.write_message(WsConnectionEvent::Disconnected(entity));
+                }
+            }
+        }
+    });
+}
+
+fn dispatch(world: &mut World, codec: WsCodec, from: Entity, bytes: &[u8]) -> Result<(), WsError> {
+    let kind = codec.decode::<EnvelopeKind>(bytes)?.kind;
+    let decode = world
+        .resource::<WsMessageRegistry>()
+        .decoders
+        .get(kind.as_str())
+        .copied()
+        .ok_or_else(|| WsError::Decode(format!("unregistered message kind `{kind}`")))?;
+    decode(world, from, codec, bytes)
+}
+
+#[derive(Clone)]
+struct WsEndpoint {
+    events_tx: mpsc::Sender<WsEvent>,
+    codec: WsCodec,
+    outbound_capacity: usize,
+    next_id: Arc<AtomicU64>,
+}
+
+async fn upgrade_websocket(
+    upgrade: WebSocketUpgrade,
+    Extension(endpoint): Extension<WsEndpoint>,
+    ecs: EcsHandle,
+) -> Response {
+    let shutdown = ecs.shutdown_token();
+    upgrade.on_upgrade(move |socket| run_connection(socket, endpoint, shutdown))
+}
+
+enum Step {
+    Inbound(Option<Result<WsFrame, axum::Error>>),
+    Outbound(Option<WsFrame>),
+    Shutdown,
+}
+
+async fn run_connection(mut socket: WebSocket, endpoint: WsEndpoint, shutdown: CancellationToken) {
+    let id = endpoint.next_id.fetch_add(1, Ordering::Relaxed);
+    let (outbound_tx, mut outbound_rx) = mpsc::channel(endpoint.outbound_capacity);
+    let connection = Connection {
+        id,
+        codec: endpoint.codec,
+        outbound_tx,
+    };
+    if endpoint
+        .events_tx
+        .send(WsEvent::Connected(connection))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    loop {
+        let step = future::or(
+            future::or(async { Step::Inbound(socket.recv().await) }, async {
+                Step::Outbound(outbound_rx.recv().await)
+            }),
+            async {
+                shutdown.cancelled().await;
+                Step::Shutdown
+            },
+        )
+        .await;
+
+        let bytes = match step {
+            Step::Inbound(Some(Ok(WsFrame::Text(text)))) => Bytes::from(text),
+            Step::Inbound(Some(Ok(WsFrame::Binary(bytes)))) => bytes,
+            // Pings are answered by axum.
+            Step::Inbound(Some(Ok(WsFrame::Ping(_) | WsFrame::Pong(_)))) => continue,
+            Step::Inbound(_) => break,
+            Step::Outbound(Some(frame)) => {
+                if socket.send(frame).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+            // The `Connection` was despawned, or the app is exiting.
+            Step::Outbound(None) | Step::Shutdown => {
+                let _ = socket.send(WsFrame::Close(None)).await;
+                break;
+            }
+        };
+        if endpoint
+            .events_tx
+            .send(WsEvent::Received(id, bytes))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    let _ = endpoint.events_tx.send(WsEvent::Disconnected(id)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Chat {
+        message: String,
+    }
+
+    impl WsMessage for Chat {
+        const NAME: &'static str = "test:Chat";
+    }
+
+    fn chat(message: &str) -> Chat {
+        Chat {
+            message: message.to_string(),
+        }
+    }
+
+    fn bytes(frame: WsFrame) -> Bytes {
+        match frame {
+            WsFrame::Text(text) => Bytes::from(text),
+            WsFrame::Binary(bytes) => bytes,
+            frame => panic!("expected a data frame, got {frame:?}"),
+        }
+    }
+
+    fn connection(id: u64, capacity: usize) -> (Connection, mpsc::Receiver<WsFrame>) {
+        let (outbound_tx, outbound_rx) = mpsc::channel(capacity);
+        let connection = Connection {
+            id,
+            codec: WsCodec::Json,
+            outbound_tx,
+        };
+        (connection, outbound_rx)
+    }
+
+    /// An app serving `Chat` whose socket events are sent through the returned sender
+    fn app() -> (App, mpsc::Sender<WsEvent>) {
+        let mut app = App::new();
+        app.add_plugins(WebSocketPlugin::new("/ws"))
+            .add_ws_message::<Chat>();
+        let (events_tx, events_rx) = mpsc::channel(16);
+        app.world_mut().resource_mut::<WsServer>().events_rx = events_rx;
+        (app, events_tx)
+    }
+
+    fn connection_events(app: &App) -> Vec<WsConnectionEvent> {
+        app.world()
+            .resource::<Messages<WsConnectionEvent>>()
+            .iter_current_update_messages()
+            .copied()
+            .collect()
+    }
+
+    fn received(app: &App) -> Vec<(Entity, Chat)> {
+        app.world()
+            .resource::<Messages<WsReceived<Chat>>>()
+            .iter_current_update_messages()
+            .map(|received| (received.from, received.message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn envelopes_round_trip_with_every_codec() {
+        let json = WsCodec::Json.encode_message(&chat("hello")).unwrap();
+        assert_eq!(
+            json,
+            WsFrame::Text(r#"{"kind":"test:Chat","data":{"message":"hello"}}"#.into())
+        );
+
+        let codecs = [
+            WsCodec::Json,
+            #[cfg(feature = "bincode")]
+            WsCodec::Bincode,
+            #[cfg(feature = "msgpack")]
+            WsCodec::MessagePack,
+        ];
+        for codec in codecs {
+            let frame = bytes(codec.encode_message(&chat("hello")).unwrap());
+            let kind = codec.decode::<EnvelopeKind>(&frame).unwrap().kind;
+            assert_eq!(kind, Chat::NAME, "{codec:?}");
+            let envelope = codec.decode::<OwnedEnvelope<Chat>>(&frame).unwrap();
+            assert_eq!(envelope.data, chat("hello"), "{codec:?}");
+        }
+    }
+
+    #[test]
+    fn connections_are_spawned_and_despawned() {
+        let (mut app, events) = app();
+        let (connection, _frames) = connection(1, 1);
+        events.try_send(WsEvent::Connected(connection)).unwrap();
+        app.update();
+
+        let [WsConnectionEvent::Connected(entity)] = connection_events(&app)[..] else {
+            panic!("expected one connection");
+        };
+        assert_eq!(app.world().get::<Connection>(entity).unwrap().id(), 1);
+
+        // Events of unknown connections are ignored.
+        events.try_send(WsEvent::Disconnected(2)).unwrap();
+        events.try_send(WsEvent::Disconnected(1)).unwrap();
+        app.update();
+        assert_eq!(
+            connection_events(&app),
+            [WsConnectionEvent::Disconnected(entity)]
+        );
+        assert!(app.world().get_entity(entity).is_err());
+    }
+
+    #[test]
+    fn registered_messages_are_written_in_order() {
+        let (mut app, events) = app();
+        let (connection, _frames) = connection(1, 1);
+        events.try_send(WsEvent::Connected(connection)).unwrap();
+        for message in ["first", "second"] {
+            let frame = bytes(WsCodec::Json.encode_message(&chat(message)).unwrap());
+            events.try_send(WsEvent::Received(1, frame)).unwrap();
+        }
+        app.update();
+
+        let [WsConnectionEvent::Connected(entity)] = connection_events(&app)[..] else {
+            panic!("expected one connection");
+        };
+        assert_eq!(
+            received(&app),
+            [(entity, chat("first")), (entity, chat("second"))]
+        );
+    }
+
+    #[test]
+    fn unregistered_and_malformed_frames_are_dropped() {
+        let (mut app, events) = app();
+        let (connection, _frames) = connection(1, 1);
+        events.try_send(WsEvent::Connected(connection)).unwrap();
+        let frames = [
+            r#"{"kind":"test:Unknown","data":{"message":"hello"}}"#,
+            r#"{"kind":"test:Chat","data":{"text":"hello"}}"#,
+            "not json",
+        ];
+        for frame in frames {
+            events
+                .try_send(WsEvent::Received(1, Bytes::from_static(frame.as_bytes())))
+                .unwrap();
+        }
+        let frame = bytes(WsCodec::Json.encode_message(&chat("after")).unwrap());
+        events.try_send(WsEvent::Received(1, frame)).unwrap();
+        app.update();
+
+        let [WsConnectionEvent::Connected(entity)] = connection_events(&app)[..] else {
+            panic!("expected one connection");
+        };
+        assert_eq!(received(&app), [(entity, chat("after"))]);
+        assert!(app.world().get::<Connection>(entity).is_some());
+    }
+
+    #[test]
+    fn sends_fail_on_full_and_closed_connections() {
+        let (full, _full_frames) = connection(1, 1);
+        assert_eq!(full.send(&chat("first")), Ok(()));
+        assert_eq!(full.send(&chat("second")), Err(WsError::Full));
+
+        let (closed, closed_frames) = connection(2, 1);
+        drop(closed_frames);
+        assert_eq!(closed.send(&chat("hello")), Err(WsError::Closed));
+    }
+
+    #[test]
+    fn web_sockets_send_and_broadcast() {
+        let (mut app, _events) = app();
+        let (full, mut full_frames) = connection(1, 1);
+        full.send(&chat("queued")).unwrap();
+        let full = app.world_mut().spawn(full).id();
+        let (open, mut open_frames) = connection(2, 4);
+        let open = app.world_mut().spawn(open).id();
+        let not_a_connection = app.world_mut().spawn_empty().id();
+
+        let results = app
+            .world_mut()
+            .run_system_once(move |sockets: WebSockets| {
+                (
+                    sockets.send(not_a_connection, &chat("hello")),
+                    sockets.send(full, &chat("hello")),
+                    sockets.broadcast(&chat("everyone")),
+                )
+            })
+            .unwrap();
+        assert_eq!(
+            results,
+            (
+                Err(WsError::NoSuchConnection(not_a_connection)),
+                Err(WsError::Full),
+                Ok(())
+            )
+        );
+
+        // The full connection missed the broadcast, the other one got it.
+        let decode = |frame| {
+            WsCodec::Json
+                .decode::<OwnedEnvelope<Chat>>(&bytes(frame))
+                .unwrap()
+                .data
+        };
+        assert_eq!(decode(full_frames.try_recv().unwrap()), chat("queued"));
+        assert!(full_frames.try_recv().is_err());
+        assert_eq!(decode(open_frames.try_recv().unwrap()), chat("everyone"));
+
+        drop(open_frames);
+        let closed = app
+            .world_mut()
+            .run_system_once(move |sockets: WebSockets| sockets.send(open, &chat("hello")))
+            .unwrap();
+        assert_eq!(closed, Err(WsError::Closed));
+    }
+}
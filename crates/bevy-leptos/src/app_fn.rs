@@ -0,0 +1,89 @@
+use crate::{leptos_server_config, leptos_task_context, LeptosServerConfig, LEPTOS_TASK_CONTEXT};
+use bevy_tokio_tasks::{CancellationToken, TaskContext};
+use leptos::config::LeptosOptions;
+use std::fmt::Display;
+
+/// A parameter of a `#[leptos_app]` function, resolved from the Leptos app running it
+///
+/// Implemented for [`TaskContext`] (the Bevy world), [`CancellationToken`] (cancelled when the app is
+/// stopped), [`LeptosServerConfig`], `Option<LeptosServerConfig>` and [`LeptosOptions`].
+///
+/// # Example
+/// ```ignore
+/// #[leptos_app]
+/// pub async fn start_leptos_app(
+///     world: TaskContext,
+///     config: LeptosServerConfig,
+/// ) -> std::io::Result<()> {
+///     // ...
+/// }
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be a parameter of a `#[leptos_app]` function",
+    label = "not resolved by the Leptos app",
+    note = "implement `LeptosAppParam` for it to be injected"
+)]
+pub trait LeptosAppParam: Sized {
+    /// Get the value, or why the app cannot run, which marks it failed
+    fn from_leptos_app() -> Result<Self, String>;
+}
+
+impl LeptosAppParam for TaskContext {
+    fn from_leptos_app() -> Result<Self, String> {
+        leptos_task_context().ok_or_else(not_in_leptos_app)
+    }
+}
+
+impl LeptosAppParam for CancellationToken {
+    fn from_leptos_app() -> Result<Self, String> {
+        LEPTOS_TASK_CONTEXT
+            .try_with(|scope| scope.stop.clone())
+            .map_err(|_| not_in_leptos_app())
+    }
+}
+
+impl LeptosAppParam for Option<LeptosServerConfig> {
+    fn from_leptos_app() -> Result<Self, String> {
+        LEPTOS_TASK_CONTEXT
+            .try_with(|_| leptos_server_config())
+            .map_err(|_| not_in_leptos_app())
+    }
+}
+
+impl LeptosAppParam for LeptosServerConfig {
+    fn from_leptos_app() -> Result<Self, String> {
+        Option::<LeptosServerConfig>::from_leptos_app()?.ok_or_else(|| {
+            "the Leptos app has no LeptosServerConfig, give it one with LeptosAppPlugin::with_config"
+                .to_string()
+        })
+    }
+}
+
+impl LeptosAppParam for LeptosOptions {
+    fn from_leptos_app() -> Result<Self, String> {
+        Ok(LeptosServerConfig::from_leptos_app()?.leptos_options())
+    }
+}
+
+fn not_in_leptos_app() -> String {
+    "not running in a Leptos app started by LeptosAppPlugin".to_string()
+}
+
+/// What a `#[leptos_app]` function returns: `()`, or `Result<(), E>` whose error marks the app failed in
+/// [`WebServerStatus`](crate::WebServerStatus)
+pub trait LeptosAppOutput {
+    /// The error to report, if any
+    fn into_failure(self) -> Option<String>;
+}
+
+impl LeptosAppOutput for () {
+    fn into_failure(self) -> Option<String> {
+        None
+    }
+}
+
+impl<E: Display> LeptosAppOutput for Result<(), E> {
+    fn into_failure(self) -> Option<String> {
+        self.err().map(|error| error.to_string())
+    }
+}
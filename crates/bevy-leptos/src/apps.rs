@@ -0,0 +1,758 @@
+use crate::status::{StatusReporter, StatusReports};
+use crate::{
+    live_reload, LeptosApp, LeptosAppScope, RestartPolicy, WebServerState, LEPTOS_TASK_CONTEXT,
+};
+use bevy::prelude::*;
+use bevy_tokio_tasks::{
+    panic_message, CancellationToken, TaskContext, TaskId, TaskRegistry, TokioTasksRuntime,
+};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// How long a stopped app has to return before its task is aborted
+pub const STOP_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// The Leptos apps added with [`LeptosAppPlugin`](crate::LeptosAppPlugin), by name
+///
+/// Every app runs in its own background task, named `leptos_app:<name>` in the [`TaskRegistry`], and
+/// is started and stopped on its own with [`LeptosAppCommands`]. All apps are started in
+/// `PostStartup`, except those deferred with
+/// [`LeptosAppPlugin::start_when`](crate::LeptosAppPlugin::start_when). The task supervises the app: it tracks its state in
+/// [`WebServerStatus`](crate::WebServerStatus) and restarts it according to its [`RestartPolicy`].
+#[derive(Resource)]
+pub struct LeptosApps {
+    apps: BTreeMap<String, LeptosAppEntry>,
+    stop_grace_period: Duration,
+}
+
+impl Default for LeptosApps {
+    fn default() -> Self {
+        Self {
+            apps: BTreeMap::new(),
+            stop_grace_period: STOP_GRACE_PERIOD,
+        }
+    }
+}
+
+struct LeptosAppEntry {
+    app: LeptosApp,
+    /// Not started in `PostStartup` but once a run condition holds
+    deferred: bool,
+    run: Option<LeptosAppRun>,
+}
+
+/// The current run of an app
+struct LeptosAppRun {
+    task: TaskId,
+    /// Cancelled to stop the app, a child of the runtime's shutdown token
+    stop: CancellationToken,
+    /// Cancelled once the run finished, so the next one can bind the same address
+    done: CancellationToken,
+    /// Cancelled once the live reload server of the run finished, so the next one can bind the same
+    /// reload port; `None` if the run serves no live reload
+    reload_done: Option<CancellationToken>,
+}
+
+impl LeptosApps {
+    /// Iterate over the apps, ordered by name
+    pub fn iter(&self) -> impl Iterator<Item = &LeptosApp> + '_ {
+        self.apps.values().map(|entry| &entry.app)
+    }
+
+    /// Iterate over the names of the apps, ordered by name
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.apps.keys().map(String::as_str)
+    }
+
+    /// Get the app named `name`
+    pub fn get(&self, name: &str) -> Option<&LeptosApp> {
+        self.apps.get(name).map(|entry| &entry.app)
+    }
+
+    /// Get the task of the last run of the app named `name`, `None` if it was never started
+    pub fn task(&self, name: &str) -> Option<TaskId> {
+        self.apps.get(name)?.run.as_ref().map(|run| run.task)
+    }
+
+    /// Whether the task of the app named `name` is running, which includes waiting to restart it
+    pub fn is_running(&self, name: &str, registry: &TaskRegistry) -> bool {
+        self.task(name)
+            .and_then(|task| registry.get(task))
+            .is_some_and(|task| task.is_running())
+    }
+
+    /// Add an app, panics if an app with the same name was already added
+    pub(crate) fn add(&mut self, app: LeptosApp, deferred: bool) {
+        if self.apps.contains_key(&app.name) {
+            panic!(
+                "a Leptos app named `{}` was already added, give each LeptosAppPlugin its own name",
+                app.name
+            );
+        }
+        self.apps.insert(
+            app.name.clone(),
+            LeptosAppEntry {
+                app,
+                deferred,
+                run: None,
+            },
+        );
+    }
+}
+
+/// Starts and stops the apps of [`LeptosApps`] from systems
+///
+/// # Example
+/// ```ignore
+/// fn enter_maintenance(mut commands: Commands) {
+///     commands.stop_leptos_app("operator");
+///     commands.start_leptos_app("maintenance");
+/// }
+/// ```
+pub trait LeptosAppCommands {
+    /// Start the app named `name`, unless it is running
+    fn start_leptos_app(&mut self, name: impl Into<String>);
+
+    /// Stop the app named `name`
+    ///
+    /// The future returned by [`shutdown_signal`](crate::shutdown_signal) completes, so the app's server
+    /// finishes in-flight requests and returns. An app still running after [`STOP_GRACE_PERIOD`] is
+    /// aborted and marked failed.
+    fn stop_leptos_app(&mut self, name: impl Into<String>);
+
+    /// Stop the app named `name` and start it again once it finished, or start it if it is not running
+    fn restart_leptos_app(&mut self, name: impl Into<String>);
+
+    /// Serve the app named `name` on `addr`, restarting it if it is running
+    ///
+    /// Changes the site address of its [`LeptosServerConfig`](crate::LeptosServerConfig), so the app
+    /// must have one and bind its listener from it, e.g. with [`serve_leptos_app`](crate::serve_leptos_app).
+    fn rebind_leptos_app(&mut self, name: impl Into<String>, addr: SocketAddr);
+}
+
+impl LeptosAppCommands for Commands<'_, '_> {
+    fn start_leptos_app(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.queue(move |world: &mut World| start_app(world, &name));
+    }
+
+    fn stop_leptos_app(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.queue(move |world: &mut World| stop_app(world, &name));
+    }
+
+    fn restart_leptos_app(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.queue(move |world: &mut World| {
+            stop_app(world, &name);
+            start_app(world, &name);
+        });
+    }
+
+    fn rebind_leptos_app(&mut self, name: impl Into<String>, addr: SocketAddr) {
+        let name = name.into();
+        self.queue(move |world: &mut World| rebind_app(world, &name, addr));
+    }
+}
+
+/// Starts, stops or rebinds an app of [`LeptosApps`], the message counterpart of [`LeptosAppCommands`]
+///
+/// Applied in `PostUpdate`.
+///
+/// # Example
+/// ```ignore
+/// fn on_emergency_stop(mut web_server: MessageWriter<WebServerCommand>) {
+///     web_server.write(WebServerCommand::Stop(DEFAULT_LEPTOS_APP.to_string()));
+/// }
+/// ```
+#[derive(Message, Clone, Debug, PartialEq, Eq)]
+pub enum WebServerCommand {
+    /// Start the app, unless it is running
+    Start(String),
+    /// Stop the app
+    Stop(String),
+    /// Stop the app and start it again
+    Restart(String),
+    /// Serve the app on another address, see [`LeptosAppCommands::rebind_leptos_app`]
+    Rebind {
+        /// Name of the app
+        app: String,
+        /// The new site address
+        addr: SocketAddr,
+    },
+}
+
+pub(crate) fn apply_web_server_commands(
+    mut web_server_commands: MessageReader<WebServerCommand>,
+    mut commands: Commands,
+) {
+    for command in web_server_commands.read() {
+        match command.clone() {
+            WebServerCommand::Start(app) => commands.start_leptos_app(app),
+            WebServerCommand::Stop(app) => commands.stop_leptos_app(app),
+            WebServerCommand::Restart(app) => commands.restart_leptos_app(app),
+            WebServerCommand::Rebind { app, addr } => commands.rebind_leptos_app(app, addr),
+        }
+    }
+}
+
+/// Start every app of [`LeptosApps`] which is not running nor deferred
+pub fn start_leptos_apps(world: &mut World) {
+    let names: Vec<String> = world
+        .resource::<LeptosApps>()
+        .apps
+        .iter()
+        .filter(|(_, entry)| !entry.deferred)
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in names {
+        start_app(world, &name);
+    }
+}
+
+pub(crate) fn start_app(world: &mut World, name: &str) {
+    let registry = world.resource::<TaskRegistry>().clone();
+    world.resource_scope(|world, mut apps: Mut<LeptosApps>| {
+        let stopping = apps
+            .apps
+            .get(name)
+            .and_then(|entry| entry.run.as_ref())
+            .is_some_and(|run| run.stop.is_cancelled());
+        if apps.is_running(name, &registry) && !stopping {
+            return;
+        }
+        let Some(entry) = apps.apps.get_mut(name) else {
+            log::warn!("cannot start unknown Leptos app `{name}`");
+            return;
+        };
+        let previous = entry.run.as_ref().map(|run| run.done.clone());
+        let previous_reload = entry.run.as_ref().and_then(|run| run.reload_done.clone());
+        let runtime = world.resource::<TokioTasksRuntime>();
+        let stop = runtime.shutdown_token().child_token();
+
+        let done = CancellationToken::new();
+
+        let reload_done = entry
+            .app
+            .config
+            .clone()
+            .filter(|config| config.serves_live_reload())
+            .map(|config| {
+                let stop = stop.clone();
+                let reload_done = CancellationToken::new();
+                let task_done = reload_done.clone();
+                runtime
+                    .task()
+                    .name(format!("leptos_live_reload:{name}"))
+                    .spawn(move |_| async move {
+                        // Also cancelled when the task is aborted.
+                        let _done = task_done.drop_guard();
+                        if let Some(previous) = previous_reload {
+                            previous.cancelled().await;
+                        }
+                        live_reload::serve_live_reload(config, stop).await;
+                    });
+                reload_done
+            });
+
+        let reporter = StatusReporter {
+            app: name.to_string(),
+            reports_tx: world.resource::<StatusReports>().reports_tx.clone(),
+        };
+        let app = entry.app.clone();
+        let supervisor_stop = stop.clone();
+        let supervisor_done = done.clone();
+        let task = runtime
+            .task()
+            .name(format!("leptos_app:{name}"))
+            .spawn(move |ctx| async move {
+                // Also cancelled when the task is aborted.
+                let _done = supervisor_done.drop_guard();
+                if let Some(previous) = previous {
+                    previous.cancelled().await;
+                }
+                supervise(app, ctx, supervisor_stop, reporter).await;
+            })
+            .id();
+        entry.run = Some(LeptosAppRun {
+            task,
+            stop,
+            done,
+            reload_done,
+        });
+    });
+}
+
+pub(crate) fn stop_app(world: &mut World, name: &str) {
+    let apps = world.resource::<LeptosApps>();
+    let grace_period = apps.stop_grace_period;
+    let run = match apps.apps.get(name) {
+        Some(LeptosAppEntry { run: Some(run), .. }) => run,
+        Some(_) => return,
+        None => {
+            log::warn!("cannot stop unknown Leptos app `{name}`");
+            return;
+        }
+    };
+    if run.stop.is_cancelled() || run.done.is_cancelled() {
+        return;
+    }
+    run.stop.cancel();
+
+    // An app which does not await `shutdown_signal` would otherwise keep the next run waiting forever.
+    let (task, done) = (run.task, run.done.clone());
+    let registry = world.resource::<TaskRegistry>().clone();
+    let reporter = StatusReporter {
+        app: name.to_string(),
+        reports_tx: world.resource::<StatusReports>().reports_tx.clone(),
+    };
+    let name = name.to_string();
+    world
+        .resource::<TokioTasksRuntime>()
+        .task()
+        .name(format!("leptos_app_stop:{name}"))
+        .spawn(move |_| async move {
+            if tokio::time::timeout(grace_period, done.cancelled())
+                .await
+                .is_ok()
+            {
+                return;
+            }
+            log::error!("Leptos app `{name}` did not stop within {grace_period:?}, aborting it");
+            // Reported before aborting, so it precedes the `Starting` of a next run.
+            reporter.report(WebServerState::Failed(format!(
+                "did not stop within {grace_period:?} and was aborted"
+            )));
+            registry.cancel(task);
+        });
+}
+
+pub(crate) fn rebind_app(world: &mut World, name: &str, addr: SocketAddr) {
+    let registry = world.resource::<TaskRegistry>().clone();
+    let mut apps = world.resource_mut::<LeptosApps>();
+    let running = apps.is_running(name, &registry);
+    let Some(entry) = apps.apps.get_mut(name) else {
+        log::warn!("cannot rebind unknown Leptos app `{name}`");
+        return;
+    };
+    let Some(config) = entry.app.config.take() else {
+        log::warn!("cannot rebind Leptos app `{name}`, it has no LeptosServerConfig");
+        return;
+    };
+    entry.app.config = Some(config.with_site_addr(addr));
+    if running {
+        stop_app(world, name);
+        start_app(world, name);
+    }
+}
+
+/// Run `app` until it is stopped, restarting it according to its [`RestartPolicy`]
+async fn supervise(
+    app: LeptosApp,
+    ctx: TaskContext,
+    stop: CancellationToken,
+    reporter: StatusReporter,
+) {
+    let mut failures = 0;
+    loop {
+        reporter.report(WebServerState::Starting);
+        let started = Instant::now();
+        let failure = Arc::new(Mutex::new(None));
+        let scope = LeptosAppScope {
+            ctx: ctx.clone(),
+            config: app.config.clone(),
+            stop: stop.clone(),
+            reporter: reporter.clone(),
+            failure: failure.clone(),
+        };
+        // Run in a task of its own to catch panics; dropping the set aborts it with the supervisor.
+        let mut run = JoinSet::new();
+        run.spawn(LEPTOS_TASK_CONTEXT.scope(scope, (app.app_fn)()));
+        let error = match run.join_next().await {
+            Some(Ok(())) if stop.is_cancelled() => break,
+            Some(Ok(())) => failure
+                .lock()
+                .unwrap()
+                .take()
+                .unwrap_or_else(|| "the server returned without being stopped".to_string()),
+            Some(Err(err)) if err.is_panic() => {
+                format!("panicked: {}", panic_message(err.into_panic().as_ref()))
+            }
+            Some(Err(_)) | None => "the server task was cancelled".to_string(),
+        };
+        log::error!("Leptos app `{}` failed: {error}", app.name);
+        reporter.report(WebServerState::Failed(error));
+
+        if app
+            .restart
+            .healthy_after()
+            .is_some_and(|healthy| started.elapsed() >= healthy)
+        {
+            failures = 0;
+        }
+        let Some(delay) = app.restart.delay(failures) else {
+            if app.restart != RestartPolicy::Never {
+                log::error!(
+                    "Leptos app `{}` failed {} times in a row, not restarting it",
+                    app.name,
+                    failures + 1
+                );
+            }
+            return;
+        };
+        failures += 1;
+        log::info!("restarting Leptos app `{}` in {delay:?}", app.name);
+        if tokio::time::timeout(delay, stop.cancelled()).await.is_ok() {
+            break;
+        }
+    }
+    reporter.report(WebServerState::Stopped);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        leptos_server_config, report_listening, shutdown_signal, LeptosAppFn, LeptosAppPlugin,
+        LeptosServerConfig, WebServerStatusChanged, DEFAULT_LEPTOS_APP,
+    };
+    use bevy_tokio_tasks::TokioTasksPlugin;
+
+    const SITE_ADDR: SocketAddr =
+        SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 3000);
+
+    /// What the runs of a test app did, in order
+    #[derive(Clone, Default)]
+    struct Runs(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Runs {
+        fn push(&self, event: &'static str) {
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn get(&self) -> Vec<&'static str> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    /// Every state change of the apps, in order
+    #[derive(Resource, Default)]
+    struct Transitions(Vec<WebServerState>);
+
+    fn record_transitions(
+        mut changed: MessageReader<WebServerStatusChanged>,
+        mut transitions: ResMut<Transitions>,
+    ) {
+        transitions
+            .0
+            .extend(changed.read().map(|changed| changed.state.clone()));
+    }
+
+    /// An app listening on its configured address until it is stopped, which takes it a moment
+    fn serving_app(runs: Runs) -> LeptosAppFn {
+        Arc::new(move || {
+            let runs = runs.clone();
+            Box::pin(async move {
+                runs.push("start");
+                report_listening(
+                    leptos_server_config().map_or(SITE_ADDR, |config| config.site_addr()),
+                );
+                shutdown_signal().await;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                runs.push("end");
+            })
+        })
+    }
+
+    fn app(plugin: LeptosAppPlugin) -> App {
+        let mut app = App::new();
+        // Apps which ignore `shutdown_signal` keep the runtime for this long when the test ends.
+        app.add_plugins(TokioTasksPlugin {
+            shutdown_timeout: Duration::from_millis(100),
+            ..TokioTasksPlugin::default()
+        })
+        .add_plugins(plugin)
+        .init_resource::<Transitions>()
+        .add_systems(Update, record_transitions);
+        app
+    }
+
+    /// Update the app until `done` holds
+    fn update_until(app: &mut App, mut done: impl FnMut(&App) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(app) {
+            assert!(
+                Instant::now() < deadline,
+                "timed out, transitions: {:?}",
+                transitions(app)
+            );
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn transitions(app: &App) -> &[WebServerState] {
+        &app.world().resource::<Transitions>().0
+    }
+
+    fn has_transitions(count: usize) -> impl FnMut(&App) -> bool {
+        move |app| transitions(app).len() >= count
+    }
+
+    fn is_running(app: &App) -> bool {
+        let registry = app.world().resource::<TaskRegistry>();
+        app.world()
+            .resource::<LeptosApps>()
+            .is_running(DEFAULT_LEPTOS_APP, registry)
+    }
+
+    fn command(app: &mut App, command: impl FnOnce(&mut Commands)) {
+        command(&mut app.world_mut().commands());
+        app.world_mut().flush();
+    }
+
+    #[test]
+    fn apps_start_and_stop() {
+        let runs = Runs::default();
+        let mut app = app(LeptosAppPlugin::new(serving_app(runs.clone())));
+        update_until(&mut app, has_transitions(2));
+        assert!(is_running(&app));
+
+        // Starting a running app does nothing.
+        command(&mut app, |commands| {
+            commands.start_leptos_app(DEFAULT_LEPTOS_APP)
+        });
+        command(&mut app, |commands| {
+            commands.stop_leptos_app(DEFAULT_LEPTOS_APP)
+        });
+        update_until(&mut app, |app| !is_running(app));
+        app.update();
+        assert_eq!(
+            transitions(&app),
+            [
+                WebServerState::Starting,
+                WebServerState::Listening(SITE_ADDR),
+                WebServerState::Stopped,
+            ]
+        );
+        assert_eq!(runs.get(), ["start", "end"]);
+    }
+
+    #[test]
+    fn restarts_wait_for_the_previous_run() {
+        let runs = Runs::default();
+        let mut app = app(LeptosAppPlugin::new(serving_app(runs.clone())));
+        update_until(&mut app, has_transitions(2));
+
+        command(&mut app, |commands| {
+            commands.restart_leptos_app(DEFAULT_LEPTOS_APP)
+        });
+        update_until(&mut app, has_transitions(5));
+        assert_eq!(
+            transitions(&app),
+            [
+                WebServerState::Starting,
+                WebServerState::Listening(SITE_ADDR),
+                WebServerState::Stopped,
+                WebServerState::Starting,
+                WebServerState::Listening(SITE_ADDR),
+            ]
+        );
+        assert_eq!(runs.get(), ["start", "end", "start"]);
+    }
+
+    #[test]
+    fn rebinding_serves_on_the_new_address() {
+        let runs = Runs::default();
+        let config = LeptosServerConfig::new("test").with_hot_reload(false);
+        let mut app = app(LeptosAppPlugin::new(serving_app(runs.clone())).with_config(config));
+        update_until(&mut app, has_transitions(2));
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 4000));
+        command(&mut app, |commands| {
+            commands.rebind_leptos_app(DEFAULT_LEPTOS_APP, addr)
+        });
+        update_until(&mut app, has_transitions(5));
+        assert_eq!(
+            transitions(&app)[2..],
+            [
+                WebServerState::Stopped,
+                WebServerState::Starting,
+                WebServerState::Listening(addr),
+            ]
+        );
+        assert_eq!(runs.get(), ["start", "end", "start"]);
+        let apps = app.world().resource::<LeptosApps>();
+        let config = apps.get(DEFAULT_LEPTOS_APP).unwrap().config.as_ref();
+        assert_eq!(config.map(LeptosServerConfig::site_addr), Some(addr));
+    }
+
+    #[test]
+    fn apps_not_stopping_in_time_are_aborted() {
+        let stubborn: LeptosAppFn = Arc::new(|| Box::pin(std::future::pending()));
+        let mut app = app(LeptosAppPlugin::new(stubborn));
+        app.world_mut()
+            .resource_mut::<LeptosApps>()
+            .stop_grace_period = Duration::from_millis(20);
+        update_until(&mut app, has_transitions(1));
+
+        command(&mut app, |commands| {
+            commands.stop_leptos_app(DEFAULT_LEPTOS_APP)
+        });
+        update_until(&mut app, |app| !is_running(app));
+        update_until(&mut app, has_transitions(2));
+        assert_eq!(
+            transitions(&app)[1],
+            WebServerState::Failed("did not stop within 20ms and was aborted".to_string())
+        );
+
+        // The aborted run does not hold up the next one.
+        command(&mut app, |commands| {
+            commands.start_leptos_app(DEFAULT_LEPTOS_APP)
+        });
+        update_until(&mut app, has_transitions(3));
+        assert_eq!(transitions(&app)[2], WebServerState::Starting);
+    }
+
+    #[derive(Resource)]
+    struct Ready;
+
+    #[test]
+    fn deferred_apps_start_once_their_condition_holds() {
+        let plugin =
+            LeptosAppPlugin::new(serving_app(Runs::default())).start_when(resource_exists::<Ready>);
+        let mut app = app(plugin);
+        for _ in 0..10 {
+            app.update();
+        }
+        assert!(transitions(&app).is_empty());
+        assert_eq!(
+            app.world()
+                .resource::<LeptosApps>()
+                .task(DEFAULT_LEPTOS_APP),
+            None
+        );
+
+        app.world_mut().insert_resource(Ready);
+        update_until(&mut app, has_transitions(2));
+        assert_eq!(
+            transitions(&app),
+            [
+                WebServerState::Starting,
+                WebServerState::Listening(SITE_ADDR)
+            ]
+        );
+    }
+
+    /// An app which counts its runs and returns after `lifetime` without being stopped
+    fn returning_app(runs: Runs, lifetime: Duration) -> LeptosAppFn {
+        Arc::new(move || {
+            let runs = runs.clone();
+            Box::pin(async move {
+                runs.push("start");
+                tokio::time::sleep(lifetime).await;
+            })
+        })
+    }
+
+    fn returned() -> WebServerState {
+        WebServerState::Failed("the server returned without being stopped".to_string())
+    }
+
+    #[test]
+    fn panicking_apps_are_failed() {
+        let panicking: LeptosAppFn = Arc::new(|| Box::pin(async { panic!("boom") }));
+        let mut app = app(LeptosAppPlugin::new(panicking));
+        update_until(&mut app, |app| {
+            !is_running(app) && transitions(app).len() >= 2
+        });
+        assert_eq!(
+            transitions(&app),
+            [
+                WebServerState::Starting,
+                WebServerState::Failed("panicked: boom".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_apps_restart_until_max_restarts() {
+        let runs = Runs::default();
+        let policy = RestartPolicy::ExponentialBackoff {
+            initial: Duration::from_millis(5),
+            max: Duration::from_secs(1),
+            max_restarts: Some(2),
+        };
+        let plugin = LeptosAppPlugin::new(returning_app(runs.clone(), Duration::ZERO))
+            .with_restart_policy(policy);
+        let mut app = app(plugin);
+        update_until(&mut app, |app| {
+            !is_running(app) && transitions(app).len() >= 6
+        });
+        assert_eq!(
+            transitions(&app),
+            [
+                WebServerState::Starting,
+                returned(),
+                WebServerState::Starting,
+                returned(),
+                WebServerState::Starting,
+                returned(),
+            ]
+        );
+        assert_eq!(runs.get().len(), 3);
+    }
+
+    #[test]
+    fn healthy_runs_reset_the_failure_count() {
+        let runs = Runs::default();
+        // Runs last longer than `max`, so none of the failures are consecutive.
+        let policy = RestartPolicy::ExponentialBackoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(10),
+            max_restarts: Some(1),
+        };
+        let plugin = LeptosAppPlugin::new(returning_app(runs.clone(), Duration::from_millis(20)))
+            .with_restart_policy(policy);
+        let mut app = app(plugin);
+        update_until(&mut app, |_| runs.get().len() >= 4);
+        assert!(is_running(&app));
+
+        command(&mut app, |commands| {
+            commands.stop_leptos_app(DEFAULT_LEPTOS_APP)
+        });
+        update_until(&mut app, |app| !is_running(app));
+        app.update();
+        assert_eq!(transitions(&app).last(), Some(&WebServerState::Stopped));
+    }
+
+    #[test]
+    fn stopping_while_waiting_to_restart_stops() {
+        let runs = Runs::default();
+        let policy =
+            RestartPolicy::exponential_backoff(Duration::from_secs(60), Duration::from_secs(60));
+        let plugin = LeptosAppPlugin::new(returning_app(runs.clone(), Duration::ZERO))
+            .with_restart_policy(policy);
+        let mut app = app(plugin);
+        update_until(&mut app, has_transitions(2));
+        assert!(is_running(&app));
+
+        command(&mut app, |commands| {
+            commands.stop_leptos_app(DEFAULT_LEPTOS_APP)
+        });
+        update_until(&mut app, |app| !is_running(app));
+        app.update();
+        assert_eq!(
+            transitions(&app),
+            [
+                WebServerState::Starting,
+                returned(),
+                WebServerState::Stopped
+            ]
+        );
+        assert_eq!(runs.get().len(), 1);
+    }
+}
@@ -0,0 +1,353 @@
+use leptos::config::{
+    errors::LeptosConfigError, get_config_from_env, get_config_from_str, Env, LeptosOptions,
+};
+use std::borrow::Cow;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Settings of the Leptos server started by `LeptosAppPlugin`
+///
+/// Holds the same settings as cargo-leptos (site address, site root, pkg dir, reload port, env), so a
+/// binary configured with it can be launched directly with `cargo run`. It can be built in code, read
+/// from a file or from the `LEPTOS_*` environment variables set by cargo-leptos. In
+/// [`Env::DEV`] with hot reload enabled, `LeptosAppPlugin` serves Leptos' live reload WebSocket itself
+/// and reloads the pages when the files of the site root change.
+///
+/// # Example
+/// ```ignore
+/// let config = LeptosServerConfig::new(env!("CARGO_CRATE_NAME"))
+///     .with_site_addr(([0, 0, 0, 0], 8080))
+///     .with_site_root("target/site");
+/// app.add_plugins(LeptosAppPlugin::new(start_leptos_app()).with_config(config));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LeptosServerConfig {
+    options: LeptosOptions,
+    hot_reload: bool,
+    serves_live_reload: bool,
+}
+
+impl LeptosServerConfig {
+    /// Create a config with the defaults of cargo-leptos: `127.0.0.1:3000`, `target/site`, `pkg`,
+    /// reload port 3001, dev env
+    ///
+    /// # Arguments
+    /// * `output_name` - Name of the generated WASM, JS and CSS files, usually `env!("CARGO_CRATE_NAME")`
+    pub fn new(output_name: impl Into<String>) -> Self {
+        let options = LeptosOptions::builder()
+            .output_name(output_name.into())
+            .site_root("target/site")
+            .build();
+        Self {
+            options,
+            hot_reload: true,
+            serves_live_reload: false,
+        }
+    }
+
+    /// Read the `[package.metadata.leptos]` or `[[workspace.metadata.leptos]]` section of a
+    /// `Cargo.toml`, or a file holding only the settings, overridden by the `LEPTOS_*` environment
+    /// variables
+    ///
+    /// Like cargo-leptos, the output of a workspace project is named after its `name` unless
+    /// `output-name` is set. A missing file is `LeptosConfigError::ConfigNotFound`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let text = std::fs::read_to_string(path).map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => LeptosConfigError::ConfigNotFound.into(),
+            _ => ConfigFileError::Io(err),
+        })?;
+        let text = with_workspace_output_name(&text);
+        let options = match get_config_from_str(&text) {
+            Err(LeptosConfigError::ConfigSectionNotFound) => {
+                get_config_from_str(&format!("[package.metadata.leptos]\n{text}"))?
+            }
+            options => options?,
+        };
+        Ok(options.into())
+    }
+
+    /// Read the `LEPTOS_*` environment variables set by cargo-leptos, with its defaults for the missing ones
+    pub fn from_env() -> Result<Self, LeptosConfigError> {
+        Ok(get_config_from_env()?.leptos_options.into())
+    }
+
+    /// Set the address the server listens on (default `127.0.0.1:3000`)
+    pub fn with_site_addr(mut self, addr: impl Into<SocketAddr>) -> Self {
+        self.options.site_addr = addr.into();
+        self
+    }
+
+    /// Set the directory the site is served from (default `target/site`)
+    pub fn with_site_root(mut self, site_root: impl Into<String>) -> Self {
+        self.options.site_root = site_root.into().into();
+        self
+    }
+
+    /// Set the directory of the WASM, JS and CSS files, relative to the site root (default `pkg`)
+    pub fn with_site_pkg_dir(mut self, site_pkg_dir: impl Into<String>) -> Self {
+        self.options.site_pkg_dir = site_pkg_dir.into().into();
+        self
+    }
+
+    /// Set the port of the live reload WebSocket (default 3001)
+    pub fn with_reload_port(mut self, port: u32) -> Self {
+        self.options.reload_port = port;
+        self
+    }
+
+    /// Set the environment (default `Env::DEV`)
+    pub fn with_env(mut self, env: Env) -> Self {
+        self.options.env = env;
+        self
+    }
+
+    /// Enable or disable live reload in the dev environment (default enabled)
+    pub fn with_hot_reload(mut self, hot_reload: bool) -> Self {
+        self.hot_reload = hot_reload;
+        self
+    }
+
+    /// Address the server listens on
+    pub fn site_addr(&self) -> SocketAddr {
+        self.options.site_addr
+    }
+
+    /// Directory the site is served from
+    pub fn site_root(&self) -> &str {
+        &self.options.site_root
+    }
+
+    /// Directory of the WASM, JS and CSS files, relative to the site root
+    pub fn site_pkg_dir(&self) -> &str {
+        &self.options.site_pkg_dir
+    }
+
+    /// Port of the live reload WebSocket
+    pub fn reload_port(&self) -> u32 {
+        self.options.reload_port
+    }
+
+    /// The environment
+    pub fn env(&self) -> &Env {
+        &self.options.env
+    }
+
+    /// Whether `LeptosAppPlugin` serves live reload, i.e. hot reload is enabled in the dev environment
+    pub fn hot_reload(&self) -> bool {
+        self.hot_reload && self.options.env == Env::DEV
+    }
+
+    /// Whether `LeptosAppPlugin` serves live reload for the app, set when the plugin is built
+    ///
+    /// False under `cargo leptos watch`, which serves it itself. When set, pages rendered through
+    /// `leptos_routes_with_world` include the script connecting to the reload WebSocket.
+    pub fn serves_live_reload(&self) -> bool {
+        self.serves_live_reload
+    }
+
+    pub(crate) fn with_serves_live_reload(mut self, serves_live_reload: bool) -> Self {
+        self.serves_live_reload = serves_live_reload;
+        self
+    }
+
+    /// The options to pass to the Leptos integration, e.g. to `leptos_routes` and `HydrationScripts`
+    pub fn leptos_options(&self) -> LeptosOptions {
+        self.options.clone()
+    }
+}
+
+impl From<LeptosOptions> for LeptosServerConfig {
+    fn from(options: LeptosOptions) -> Self {
+        Self {
+            options,
+            hot_reload: true,
+            serves_live_reload: false,
+        }
+    }
+}
+
+/// Error returned by [`LeptosServerConfig::from_file`]
+#[derive(Debug)]
+pub enum ConfigFileError {
+    /// The file exists but could not be read
+    Io(std::io::Error),
+    /// The file is missing or holds no valid config
+    Config(LeptosConfigError),
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigFileError::Io(err) => write!(f, "failed to read the Leptos config: {err}"),
+            ConfigFileError::Config(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ConfigFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigFileError::Io(err) => Some(err),
+            ConfigFileError::Config(err) => Some(err),
+        }
+    }
+}
+
+impl From<LeptosConfigError> for ConfigFileError {
+    fn from(err: LeptosConfigError) -> Self {
+        ConfigFileError::Config(err)
+    }
+}
+
+/// Add `output-name = <name>` to a `[[workspace.metadata.leptos]]` section which only has a `name`,
+/// since Leptos requires `output-name`
+fn with_workspace_output_name(text: &str) -> Cow<'_, str> {
+    let lines: Vec<&str> = text.lines().collect();
+    let Some(header) = lines
+        .iter()
+        .position(|line| line.trim() == "[[workspace.metadata.leptos]]")
+    else {
+        return Cow::Borrowed(text);
+    };
+    let section = lines[header + 1..]
+        .iter()
+        .take_while(|line| !line.trim_start().starts_with('['));
+    let value_of = |line: &str, key: &str| {
+        line.split_once('=')
+            .filter(|(name, _)| name.trim() == key)
+            .map(|(_, value)| value.trim().to_string())
+    };
+    let mut name = None;
+    for line in section {
+        if value_of(line, "output-name").is_some() {
+            return Cow::Borrowed(text);
+        }
+        name = name.or_else(|| value_of(line, "name"));
+    }
+    let Some(name) = name else {
+        return Cow::Borrowed(text);
+    };
+
+    let mut with_output_name = lines[..=header].join("\n");
+    with_output_name.push_str(&format!("\noutput-name = {name}\n"));
+    with_output_name.push_str(&lines[header + 1..].join("\n"));
+    Cow::Owned(with_output_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Write `text` to a file of the temporary directory
+    fn write(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("bevy-leptos-{}-{name}", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn package_metadata_is_read() {
+        let path = write(
+            "package.toml",
+            r#"
+[package]
+name = "robot"
+
+[package.metadata.leptos]
+output-name = "robot_site"
+site-addr = "0.0.0.0:4000"
+reload-port = 4001
+
+[dependencies]
+leptos = "0.8"
+"#,
+        );
+        let config = LeptosServerConfig::from_file(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(&*config.leptos_options().output_name, "robot_site");
+        assert_eq!(config.site_addr(), SocketAddr::from(([0, 0, 0, 0], 4000)));
+        assert_eq!(config.reload_port(), 4001);
+    }
+
+    #[test]
+    fn workspace_metadata_is_read() {
+        let path = write(
+            "workspace.toml",
+            r#"
+[workspace]
+members = ["app", "server"]
+
+[[workspace.metadata.leptos]]
+name = "start-axum-workspace"
+bin-package = "server"
+site-root = "target/workspace-site"
+env = "PROD"
+
+[profile.release]
+lto = true
+"#,
+        );
+        let config = LeptosServerConfig::from_file(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            &*config.leptos_options().output_name,
+            "start-axum-workspace"
+        );
+        assert_eq!(config.site_root(), "target/workspace-site");
+        assert_eq!(config.env(), &Env::PROD);
+    }
+
+    #[test]
+    fn files_of_settings_only_are_read() {
+        let path = write(
+            "settings.toml",
+            "output-name = \"robot_site\"\nsite-pkg-dir = \"assets\"\n",
+        );
+        let config = LeptosServerConfig::from_file(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(&*config.leptos_options().output_name, "robot_site");
+        assert_eq!(config.site_pkg_dir(), "assets");
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let missing = std::env::temp_dir().join("bevy-leptos-missing/Cargo.toml");
+        assert!(matches!(
+            LeptosServerConfig::from_file(missing),
+            Err(ConfigFileError::Config(LeptosConfigError::ConfigNotFound))
+        ));
+        assert!(matches!(
+            LeptosServerConfig::from_file(std::env::temp_dir()),
+            Err(ConfigFileError::Io(_))
+        ));
+
+        let path = write(
+            "invalid.toml",
+            "[package.metadata.leptos]\nreload-port = \"x\"\n",
+        );
+        let result = LeptosServerConfig::from_file(&path);
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(
+            result,
+            Err(ConfigFileError::Config(LeptosConfigError::ConfigError(_)))
+        ));
+    }
+
+    #[test]
+    fn hot_reload_is_only_enabled_in_dev() {
+        let config = LeptosServerConfig::new("robot");
+        assert_eq!(config.env(), &Env::DEV);
+        assert!(config.hot_reload());
+        assert!(!config.clone().with_hot_reload(false).hot_reload());
+        assert!(!config.clone().with_env(Env::PROD).hot_reload());
+        assert!(!config
+            .with_env(Env::PROD)
+            .with_hot_reload(true)
+            .hot_reload());
+    }
+}
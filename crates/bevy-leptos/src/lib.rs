@@ -1,49 +1,217 @@
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::pin::Pin;
 use std::future::Future;
 use bevy::prelude::*;
-use bevy_tokio_tasks::TokioTasksRuntime;
+use bevy_tokio_tasks::{CancellationToken, TaskContext};
 
 pub use abw_macros::leptos_app;
+pub use app_fn::*;
+pub use apps::*;
+pub use config::*;
+pub use server_fns::*;
+pub use status::*;
+
+mod app_fn;
+mod apps;
+mod config;
+mod live_reload;
+mod server_fns;
+mod status;
+
+tokio::task_local! {
+    static LEPTOS_TASK_CONTEXT: LeptosAppScope;
+}
+
+/// What the task running a Leptos app can access
+#[derive(Clone)]
+struct LeptosAppScope {
+    ctx: TaskContext,
+    config: Option<LeptosServerConfig>,
+    /// Cancelled when the app is stopped or the Bevy app exits
+    stop: CancellationToken,
+    reporter: status::StatusReporter,
+    /// Set by [`report_failure`], reported once the app returns
+    failure: Arc<Mutex<Option<String>>>,
+}
+
 pub type LeptosAppFn = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
 
-#[derive(Resource, Clone)]
+/// Name of the app of a [`LeptosAppPlugin`] which was not given one with [`LeptosAppPlugin::named`]
+pub const DEFAULT_LEPTOS_APP: &str = "default";
+
+#[derive(Clone)]
 pub struct LeptosApp {
+    /// Name of the app in [`LeptosApps`]
+    pub name: String,
     pub app_fn: LeptosAppFn,
+    /// Server settings, available to the app through [`leptos_server_config`]
+    pub config: Option<LeptosServerConfig>,
+    /// Whether the app is started again when it fails
+    pub restart: RestartPolicy,
 }
 
 impl LeptosApp {
     pub fn new(app_fn: LeptosAppFn) -> Self {
-        Self { app_fn }
+        Self { name: DEFAULT_LEPTOS_APP.to_string(), app_fn, config: None, restart: RestartPolicy::Never }
     }
 }
 
+/// Adds a Leptos app to [`LeptosApps`] and starts it in `PostStartup`, or once the condition given to
+/// [`LeptosAppPlugin::start_when`] holds
+///
+/// The plugin can be added several times to serve several apps, e.g. an operator UI and an admin UI on
+/// different ports, as long as each is given its own name.
+///
+/// # Example
+/// ```ignore
+/// app.add_plugins(LeptosAppPlugin::new(operator_app()).named("operator").with_config(operator_config))
+///     .add_plugins(LeptosAppPlugin::new(admin_app()).named("admin").with_config(admin_config));
+/// ```
 pub struct LeptosAppPlugin {
     leptos_app: LeptosApp,
+    add_start_system: Mutex<Option<AddStartSystem>>,
 }
 
+/// Adds the system starting a deferred app, taken on build since run conditions are not `Clone`
+type AddStartSystem = Box<dyn FnOnce(&mut App, String) + Send + Sync>;
+
 impl LeptosAppPlugin {
     pub fn new(app_fn: LeptosAppFn) -> Self {
         let leptos_app = LeptosApp::new(app_fn);
-        Self { leptos_app }
+        Self { leptos_app, add_start_system: Mutex::new(None) }
+    }
+
+    /// Set the name of the app in [`LeptosApps`] (default [`DEFAULT_LEPTOS_APP`])
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.leptos_app.name = name.into();
+        self
+    }
+
+    /// Set the server settings, read by the app with [`leptos_server_config`]
+    ///
+    /// In the dev environment with hot reload enabled, the plugin also serves Leptos' live reload
+    /// WebSocket on the reload port, unless the binary runs under `cargo leptos watch` which serves it
+    /// already, and pages rendered through `leptos_routes_with_world` include the reload script.
+    pub fn with_config(mut self, config: LeptosServerConfig) -> Self {
+        self.leptos_app.config = Some(config);
+        self
+    }
+
+    /// Set whether the app is started again when it panics or returns without being stopped (default
+    /// [`RestartPolicy::Never`]). Its state is tracked in [`WebServerStatus`] either way.
+    pub fn with_restart_policy(mut self, restart: RestartPolicy) -> Self {
+        self.leptos_app.restart = restart;
+        self
+    }
+
+    /// Defer starting the app until `condition` holds, e.g. `resource_exists::<MachineConfig>`, instead
+    /// of starting it in `PostStartup`
+    ///
+    /// The condition is checked in `PostUpdate` and starts the app once; stopping it afterwards with
+    /// [`LeptosAppCommands`] or [`WebServerCommand`] is not undone.
+    pub fn start_when<M>(mut self, condition: impl SystemCondition<M> + Send + Sync + 'static) -> Self {
+        self.add_start_system = Mutex::new(Some(Box::new(move |app: &mut App, name: String| {
+            app.add_systems(PostUpdate, start_deferred_app(name).run_if(condition));
+        })));
+        self
     }
 }
 
 
 impl Plugin for LeptosAppPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(self.leptos_app.clone())
-            .add_systems(PostStartup, start_leptos_app);
+        if !app.world().contains_resource::<LeptosApps>() {
+            app.init_resource::<LeptosApps>()
+                .init_resource::<WebServerStatus>()
+                .init_resource::<status::StatusReports>()
+                .add_message::<WebServerStatusChanged>()
+                .add_message::<WebServerCommand>()
+                .add_systems(PreUpdate, status::apply_status_reports)
+                .add_systems(PostUpdate, apps::apply_web_server_commands)
+                .add_systems(PostStartup, start_leptos_apps);
+        }
+        let add_start_system = self.add_start_system.lock().unwrap().take();
+        let deferred = add_start_system.is_some();
+        if let Some(add_start_system) = add_start_system {
+            add_start_system(app, self.leptos_app.name.clone());
+        }
+        let mut leptos_app = self.leptos_app.clone();
+        let serves_live_reload = serves_live_reload(&leptos_app);
+        leptos_app.config =
+            leptos_app.config.map(|config| config.with_serves_live_reload(serves_live_reload));
+        app.world_mut().resource_mut::<LeptosApps>().add(leptos_app, deferred);
+    }
+
+    fn is_unique(&self) -> bool {
+        false
     }
 }
 
-pub fn start_leptos_app(
-    runtime: ResMut<TokioTasksRuntime>,
-    leptos_app: Res<LeptosApp>,
-) {
-    let app_fn = leptos_app.app_fn.clone();
-    runtime.spawn_background_task(move |_| async move {
-        app_fn().await;
-    });
-}
\ No newline at end of file
+fn start_deferred_app(name: String) -> impl FnMut(Commands, Local<bool>) {
+    move |mut commands, mut started| {
+        if !*started {
+            *started = true;
+            commands.start_leptos_app(name.clone());
+        }
+    }
+}
+
+/// Whether the plugin serves live reload for `leptos_app`, which `cargo leptos watch` does itself
+fn serves_live_reload(leptos_app: &LeptosApp) -> bool {
+    let watched = std::env::var_os("LEPTOS_WATCH").is_some();
+    !watched && leptos_app.config.as_ref().is_some_and(|config| config.hot_reload())
+}
+
+/// Returns a future which completes when the Bevy app starts shutting down or the Leptos app is stopped
+/// with [`LeptosAppCommands::stop_leptos_app`]. Pass it to `axum::serve(..).with_graceful_shutdown(..)`
+/// inside a `#[leptos_app]` function so in-flight requests are completed before the Tokio runtime is
+/// dropped. Outside of a Leptos app started by [`LeptosAppPlugin`] the returned future never completes.
+pub fn shutdown_signal() -> impl Future<Output = ()> + Send + 'static {
+    let token = LEPTOS_TASK_CONTEXT.try_with(|scope| scope.stop.clone()).ok();
+    async move {
+        match token {
+            Some(token) => token.cancelled_owned().await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// Returns the [`TaskContext`] of the task running the Leptos app, giving a `#[leptos_app]` function
+/// access to the Bevy world. Returns `None` outside of a Leptos app started by [`LeptosAppPlugin`].
+pub fn leptos_task_context() -> Option<TaskContext> {
+    LEPTOS_TASK_CONTEXT.try_with(|scope| scope.ctx.clone()).ok()
+}
+
+/// Returns the [`LeptosServerConfig`] given to [`LeptosAppPlugin::with_config`], for a `#[leptos_app]`
+/// function to bind its listener and build its routes. Returns `None` when the plugin has no config or
+/// outside of a Leptos app started by [`LeptosAppPlugin`].
+pub fn leptos_server_config() -> Option<LeptosServerConfig> {
+    LEPTOS_TASK_CONTEXT.try_with(|scope| scope.config.clone()).ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_fn() -> LeptosAppFn {
+        Arc::new(|| Box::pin(async {}))
+    }
+
+    #[test]
+    fn named_apps_coexist() {
+        let mut app = App::new();
+        app.add_plugins(LeptosAppPlugin::new(app_fn()).named("operator"))
+            .add_plugins(LeptosAppPlugin::new(app_fn()).named("admin"));
+        let names: Vec<&str> = app.world().resource::<LeptosApps>().names().collect();
+        assert_eq!(names, ["admin", "operator"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "a Leptos app named `default` was already added")]
+    fn unnamed_apps_conflict() {
+        App::new()
+            .add_plugins(LeptosAppPlugin::new(app_fn()))
+            .add_plugins(LeptosAppPlugin::new(app_fn()));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::LeptosServerConfig;
+use axum::body::Body;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Router};
+use bevy_tokio_tasks::CancellationToken;
+use leptos::config::ReloadWSProtocol;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Connects to the reload WebSocket, like the script of `<AutoReload/>` which only includes it under
+/// `cargo leptos watch`
+const RELOAD_SCRIPT: &str = r#"
+if (window.location.protocol === "https:") {
+    protocol = "wss://";
+}
+function connect() {
+    let ws = new WebSocket(`${protocol}${window.location.hostname}:${reload_port}/live_reload`);
+    ws.onmessage = (ev) => {
+        let msg = JSON.parse(ev.data);
+        if (msg.all) window.location.reload();
+        if (msg.css) {
+            document.querySelectorAll("link").forEach((link) => {
+                if (link.getAttribute("href").includes(msg.css)) {
+                    link.setAttribute("href", "/" + msg.css + "?version=" + Date.now());
+                }
+            });
+        }
+    };
+    ws.onclose = () => setTimeout(connect, 1000);
+    ws.onerror = () => ws.close();
+}
+connect();
+"#;
+
+/// The `<script>` element connecting a page to the reload WebSocket of `config`
+pub(crate) fn reload_script(config: &LeptosServerConfig) -> Arc<str> {
+    let options = config.leptos_options();
+    let reload_port = options.reload_external_port.unwrap_or(options.reload_port);
+    let protocol = match options.reload_ws_protocol {
+        ReloadWSProtocol::WS => "'ws://'",
+        ReloadWSProtocol::WSS => "'wss://'",
+    };
+    format!("<script>(function (reload_port, protocol) {{ {RELOAD_SCRIPT} }})({reload_port}, {protocol})</script>")
+        .into()
+}
+
+/// Insert `script` at the end of the `<head>` of HTML responses
+pub(crate) async fn inject_reload_script(script: Arc<str>, response: Response) -> Response {
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let html = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(html) => String::from_utf8_lossy(&html).into_owned(),
+        Err(err) => {
+            log::error!("failed to read the page to include the live reload script: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let html = match html.find("</head>") {
+        Some(head_end) => format!("{}{script}{}", &html[..head_end], &html[head_end..]),
+        None => html,
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(html))
+}
+
+/// Serve Leptos' live reload WebSocket on the reload port until `shutdown`
+///
+/// Stands in for the one of `cargo leptos watch` when the binary is launched directly: the files of
+/// the site root are polled, and once a change settled connected pages are told to reload, or only
+/// to reload their stylesheets when nothing but CSS changed.
+pub(crate) async fn serve_live_reload(config: LeptosServerConfig, shutdown: CancellationToken) {
+    let Ok(port) = u16::try_from(config.reload_port()) else {
+        log::error!(
+            "failed to serve live reload: reload port {} is not a valid port",
+            config.reload_port()
+        );
+        return;
+    };
+    let addr = SocketAddr::new(config.site_addr().ip(), port);
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("failed to serve live reload on {addr}: {err}");
+            return;
+        }
+    };
+    log::info!("serving live reload on ws://{addr}/live_reload");
+
+    let (reload_tx, _) = broadcast::channel(16);
+    let router = Router::new()
+        .route("/live_reload", get(upgrade_live_reload))
+        .layer(Extension(reload_tx.clone()));
+    let watch_shutdown = shutdown.clone();
+    tokio::spawn(watch_site_root(
+        PathBuf::from(config.site_root()),
+        reload_tx,
+        watch_shutdown,
+    ));
+
+    if let Err(err) = axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+    {
+        log::error!("live reload server failed: {err}");
+    }
+}
+
+async fn upgrade_live_reload(
+    upgrade: WebSocketUpgrade,
+    Extension(reload_tx): Extension<broadcast::Sender<String>>,
+) -> Response {
+    let reload_rx = reload_tx.subscribe();
+    upgrade.on_upgrade(move |socket| forward_reloads(socket, reload_rx))
+}
+
+async fn forward_reloads(mut socket: WebSocket, mut reload_rx: broadcast::Receiver<String>) {
+    loop {
+        let message = match reload_rx.recv().await {
+            Ok(message) => message,
+            Err(broadcast::error::RecvError::Lagged(_)) => r#"{"all":true}"#.to_string(),
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if socket.send(Message::Text(message.into())).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn watch_site_root(
+    site_root: PathBuf,
+    reload_tx: broadcast::Sender<String>,
+    shutdown: CancellationToken,
+) {
+    let mut files = scan(site_root.clone()).await;
+    let mut changed: Vec<PathBuf> = Vec::new();
+    loop {
+        if tokio::time::timeout(POLL_INTERVAL, shutdown.cancelled())
+            .await
+            .is_ok()
+        {
+            return;
+        }
+        let current = scan(site_root.clone()).await;
+        let settled = current == files;
+        changed.extend(
+            current
+                .iter()
+                .filter(|(path, modified)| files.get(*path) != Some(modified))
+                .map(|(path, _)| path.clone()),
+        );
+        changed.extend(
+            files
+                .keys()
+                .filter(|path| !current.contains_key(*path))
+                .cloned(),
+        );
+        files = current;
+
+        // Wait for the build writing the files to finish.
+        if !settled || changed.is_empty() {
+            continue;
+        }
+        let css_only = changed
+            .iter()
+            .all(|path| path.extension().is_some_and(|extension| extension == "css"));
+        if css_only {
+            for path in changed.drain(..) {
+                let Ok(relative) = path.strip_prefix(&site_root) else {
+                    continue;
+                };
+                let css = relative.to_string_lossy().replace('\\', "/");
+                let _ = reload_tx.send(format!(r#"{{"css":"{css}"}}"#));
+            }
+        } else {
+            changed.clear();
+            let _ = reload_tx.send(r#"{"all":true}"#.to_string());
+        }
+    }
+}
+
+/// Modification time of every file under `dir`, read on the blocking thread pool
+async fn scan(dir: PathBuf) -> HashMap<PathBuf, SystemTime> {
+    tokio::task::spawn_blocking(move || scan_blocking(&dir))
+        .await
+        .unwrap_or_default()
+}
+
+fn scan_blocking(dir: &Path) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else if let Ok(modified) = metadata.modified() {
+                files.insert(entry.path(), modified);
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = "<html><head><title>Robot</title></head><body></body></html>";
+
+    fn response(content_type: &str, body: &'static str) -> Response {
+        Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .header(CONTENT_LENGTH, body.len())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    /// Inject `<script/>` into `response`, returning the body and whether `Content-Length` is still set
+    fn inject(response: Response) -> (String, bool) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let response = inject_reload_script("<script/>".into(), response).await;
+            let has_length = response.headers().contains_key(CONTENT_LENGTH);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (String::from_utf8(body.to_vec()).unwrap(), has_length)
+        })
+    }
+
+    #[test]
+    fn script_is_inserted_at_the_end_of_the_head() {
+        let (body, has_length) = inject(response("text/html; charset=utf-8", PAGE));
+        assert_eq!(
+            body,
+            "<html><head><title>Robot</title><script/></head><body></body></html>"
+        );
+        assert!(!has_length);
+    }
+
+    #[test]
+    fn other_responses_are_unchanged() {
+        assert_eq!(
+            inject(response("application/json", PAGE)),
+            (PAGE.to_string(), true)
+        );
+        let fragment = "<p>no head</p>";
+        assert_eq!(
+            inject(response("text/html", fragment)),
+            (fragment.to_string(), false)
+        );
+    }
+}
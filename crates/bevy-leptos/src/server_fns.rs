@@ -0,0 +1,173 @@
+use crate::{leptos_server_config, leptos_task_context, live_reload};
+use axum::extract::FromRef;
+use axum::Router;
+use bevy_tokio_tasks::TaskContext;
+use leptos::config::LeptosOptions;
+use leptos::prelude::*;
+use leptos_axum::{AxumRouteListing, LeptosRoutes};
+
+/// Returns the handle to the Bevy world in a Leptos server function
+///
+/// The world is provided to server functions and SSR rendering of routes registered with
+/// [`LeptosWorldRoutes::leptos_routes_with_world`]. Use the returned [`TaskContext`] to read or
+/// change the world through the Tokio bridge (`resource`, `query`, `run_system`, ...); the calls
+/// complete on the next frame.
+///
+/// # Example
+/// ```ignore
+/// #[server]
+/// pub async fn machine_state() -> Result<MachineState, ServerFnError> {
+///     let mut world = use_world()?;
+///     world.resource::<MachineState>().await.map_err(ServerFnError::new)
+/// }
+/// ```
+pub fn use_world() -> Result<TaskContext, ServerFnError> {
+    use_context::<TaskContext>().ok_or_else(|| {
+        ServerFnError::new(
+            "the Bevy world is not available, register routes with `leptos_routes_with_world`",
+        )
+    })
+}
+
+/// Returns a closure providing the Bevy world to the Leptos context, for `use_world`
+///
+/// Call it inside a `#[leptos_app]` function and pass the closure to the `*_with_context`
+/// functions of `leptos_axum`, e.g. `handle_server_fns_with_context`. Outside of a Leptos app started
+/// by `LeptosAppPlugin` the closure provides nothing.
+pub fn provide_world_context() -> impl Fn() + Clone + Send + Sync + 'static {
+    let ctx = leptos_task_context();
+    move || {
+        if let Some(ctx) = &ctx {
+            provide_context(ctx.clone());
+        }
+    }
+}
+
+/// Registers Leptos routes and server functions with access to the Bevy world
+pub trait LeptosWorldRoutes<S> {
+    /// Like `leptos_routes`, and provides the Bevy world to every route and server function
+    ///
+    /// Registers every `#[server]` function linked into the binary, so they can call `use_world`.
+    /// When `LeptosAppPlugin` serves live reload for the app, the pages also get the script connecting
+    /// to it. Must be called inside a `#[leptos_app]` function.
+    fn leptos_routes_with_world<IV>(
+        self,
+        options: &S,
+        paths: Vec<AxumRouteListing>,
+        app_fn: impl Fn() -> IV + Clone + Send + Sync + 'static,
+    ) -> Self
+    where
+        IV: IntoView + 'static;
+}
+
+impl<S> LeptosWorldRoutes<S> for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    LeptosOptions: FromRef<S>,
+{
+    fn leptos_routes_with_world<IV>(
+        self,
+        options: &S,
+        paths: Vec<AxumRouteListing>,
+        app_fn: impl Fn() -> IV + Clone + Send + Sync + 'static,
+    ) -> Self
+    where
+        IV: IntoView + 'static,
+    {
+        let router =
+            self.leptos_routes_with_context(options, paths, provide_world_context(), app_fn);
+        match leptos_server_config().filter(|config| config.serves_live_reload()) {
+            Some(config) => {
+                let script = live_reload::reload_script(&config);
+                router.layer(axum::middleware::map_response(move |response| {
+                    live_reload::inject_reload_script(script.clone(), response)
+                }))
+            }
+            None => router,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LeptosAppFn, LeptosAppPlugin};
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::get;
+    use bevy::prelude::*;
+    use bevy_tokio_tasks::TokioTasksPlugin;
+    use std::sync::{mpsc, Arc};
+    use std::time::{Duration, Instant};
+    use tower::ServiceExt;
+
+    #[derive(Resource)]
+    struct RobotName(&'static str);
+
+    /// Like a server function, read the world through `use_world` with the context of `provide`
+    async fn robot_name(provide: impl Fn()) -> Result<String, ServerFnError> {
+        let owner = Owner::new();
+        let mut world = owner.with(|| {
+            provide();
+            use_world()
+        })?;
+        Ok(world
+            .run_on_main_thread(|ctx| ctx.world.resource::<RobotName>().0.to_string())
+            .await)
+    }
+
+    #[test]
+    fn use_world_fails_outside_of_a_request() {
+        let err = use_world().unwrap_err();
+        assert!(
+            err.to_string().contains("leptos_routes_with_world"),
+            "{err}"
+        );
+
+        // Outside of a Leptos app there is no world to provide.
+        let owner = Owner::new();
+        owner.with(|| {
+            provide_world_context()();
+            assert!(use_world().is_err());
+        });
+    }
+
+    #[test]
+    fn handlers_read_the_world() {
+        let (response_tx, response_rx) = mpsc::channel();
+        let leptos_app: LeptosAppFn = Arc::new(move || {
+            let response_tx = response_tx.clone();
+            Box::pin(async move {
+                let provide = provide_world_context();
+                let router = Router::new().route(
+                    "/robot",
+                    get(move || {
+                        let provide = provide.clone();
+                        async move { robot_name(provide).await.unwrap() }
+                    }),
+                );
+                let request = Request::get("/robot").body(Body::empty()).unwrap();
+                let response = router.oneshot(request).await.unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                response_tx.send(body).unwrap();
+            })
+        });
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default())
+            .add_plugins(LeptosAppPlugin::new(leptos_app))
+            .insert_resource(RobotName("R2"));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let body = loop {
+            if let Ok(body) = response_rx.try_recv() {
+                break body;
+            }
+            assert!(Instant::now() < deadline, "timed out");
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(&body[..], b"R2");
+    }
+}
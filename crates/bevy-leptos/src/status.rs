@@ -0,0 +1,240 @@
+use crate::{leptos_server_config, shutdown_signal, LEPTOS_TASK_CONTEXT};
+use axum::Router;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// State of the web server of a Leptos app
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WebServerState {
+    /// The app was started and did not report its address yet
+    Starting,
+    /// The app reported, with [`report_listening`] or [`serve_leptos_app`], that it accepts connections
+    Listening(SocketAddr),
+    /// The app panicked, failed with [`report_failure`] or returned without being stopped
+    Failed(String),
+    /// The app was stopped, or the Bevy app is exiting
+    Stopped,
+}
+
+/// The state of the web server of every started app of [`LeptosApps`](crate::LeptosApps)
+///
+/// Updated in `PreUpdate`, which writes a [`WebServerStatusChanged`] message for every transition.
+#[derive(Resource, Default, Debug)]
+pub struct WebServerStatus {
+    apps: BTreeMap<String, WebServerState>,
+}
+
+impl WebServerStatus {
+    /// Get the state of the app named `app`, `None` if it was never started
+    pub fn get(&self, app: &str) -> Option<&WebServerState> {
+        self.apps.get(app)
+    }
+
+    /// Get the address the app named `app` listens on, if it is listening
+    pub fn listening_addr(&self, app: &str) -> Option<SocketAddr> {
+        match self.apps.get(app)? {
+            WebServerState::Listening(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    /// Iterate over the apps and their state, ordered by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &WebServerState)> + '_ {
+        self.apps.iter().map(|(app, state)| (app.as_str(), state))
+    }
+}
+
+/// Written when the web server of a Leptos app changes state
+#[derive(Message, Clone, Debug, PartialEq, Eq)]
+pub struct WebServerStatusChanged {
+    /// Name of the app
+    pub app: String,
+    /// The new state
+    pub state: WebServerState,
+}
+
+/// Whether a Leptos app is started again when it fails
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Leave the app failed
+    #[default]
+    Never,
+    /// Start the app again after a delay doubling with every consecutive failure
+    ///
+    /// Failures stop being consecutive when a run lasted longer than `max`.
+    ExponentialBackoff {
+        /// Delay before the first restart
+        initial: Duration,
+        /// Upper bound of the delay
+        max: Duration,
+        /// How many consecutive failures are restarted, `None` for no limit
+        max_restarts: Option<u32>,
+    },
+}
+
+impl RestartPolicy {
+    /// Restart without limit, waiting `initial` then doubling up to `max`
+    pub fn exponential_backoff(initial: Duration, max: Duration) -> Self {
+        RestartPolicy::ExponentialBackoff {
+            initial,
+            max,
+            max_restarts: None,
+        }
+    }
+
+    /// Delay before restarting after `failures` consecutive failures, `None` to not restart
+    pub(crate) fn delay(&self, failures: u32) -> Option<Duration> {
+        match self {
+            RestartPolicy::Never => None,
+            RestartPolicy::ExponentialBackoff {
+                initial,
+                max,
+                max_restarts,
+            } => {
+                if max_restarts.is_some_and(|max_restarts| failures >= max_restarts) {
+                    return None;
+                }
+                let factor = 2u32.saturating_pow(failures);
+                Some(initial.saturating_mul(factor).min(*max))
+            }
+        }
+    }
+
+    /// How long a run must last for its failure not to count as consecutive
+    pub(crate) fn healthy_after(&self) -> Option<Duration> {
+        match self {
+            RestartPolicy::Never => None,
+            RestartPolicy::ExponentialBackoff { max, .. } => Some(*max),
+        }
+    }
+}
+
+/// Mark the Leptos app this is called from as listening on `addr`
+///
+/// Call it inside a `#[leptos_app]` function once its listener is bound; [`serve_leptos_app`] does it
+/// already. Does nothing outside of a Leptos app started by [`LeptosAppPlugin`](crate::LeptosAppPlugin).
+pub fn report_listening(addr: SocketAddr) {
+    let _ = LEPTOS_TASK_CONTEXT
+        .try_with(|scope| scope.reporter.report(WebServerState::Listening(addr)));
+}
+
+/// Mark the Leptos app this is called from as failed with `error` once it returns
+///
+/// A `#[leptos_app]` function returning `Result<(), E>` does it with its error. Ignored when the app
+/// returns because it was stopped, and outside of a Leptos app started by
+/// [`LeptosAppPlugin`](crate::LeptosAppPlugin).
+pub fn report_failure(error: impl Into<String>) {
+    let error = error.into();
+    let _ = LEPTOS_TASK_CONTEXT.try_with(|scope| *scope.failure.lock().unwrap() = Some(error));
+}
+
+/// Serve `router` on the site address of the app's [`LeptosServerConfig`](crate::LeptosServerConfig)
+/// until [`shutdown_signal`] completes
+///
+/// Reports the app as listening once the address is bound. Fails when the app has no config, the
+/// address cannot be bound or the server fails.
+///
+/// # Example
+/// ```ignore
+/// #[leptos_app]
+/// pub async fn start_leptos_app() {
+///     let app = Router::new()
+///         .leptos_routes_with_world(&leptos_options, routes, MyApp)
+///         .with_state(leptos_options);
+///     serve_leptos_app(app).await.unwrap();
+/// }
+/// ```
+pub async fn serve_leptos_app(router: Router) -> std::io::Result<()> {
+    let config = leptos_server_config().ok_or_else(|| {
+        std::io::Error::other("the Leptos app has no LeptosServerConfig to take the address from")
+    })?;
+    let listener = TcpListener::bind(config.site_addr()).await?;
+    report_listening(listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+}
+
+/// Sends the state changes of one app to [`WebServerStatus`]
+#[derive(Clone)]
+pub(crate) struct StatusReporter {
+    pub(crate) app: String,
+    pub(crate) reports_tx: mpsc::UnboundedSender<(String, WebServerState)>,
+}
+
+impl StatusReporter {
+    pub(crate) fn report(&self, state: WebServerState) {
+        // The receiver only goes away with the Bevy app.
+        let _ = self.reports_tx.send((self.app.clone(), state));
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct StatusReports {
+    pub(crate) reports_tx: mpsc::UnboundedSender<(String, WebServerState)>,
+    reports_rx: mpsc::UnboundedReceiver<(String, WebServerState)>,
+}
+
+impl Default for StatusReports {
+    fn default() -> Self {
+        let (reports_tx, reports_rx) = mpsc::unbounded_channel();
+        Self {
+            reports_tx,
+            reports_rx,
+        }
+    }
+}
+
+pub(crate) fn apply_status_reports(
+    mut reports: ResMut<StatusReports>,
+    mut status: ResMut<WebServerStatus>,
+    mut changed: MessageWriter<WebServerStatusChanged>,
+) {
+    while let Ok((app, state)) = reports.reports_rx.try_recv() {
+        if status.apps.get(&app) == Some(&state) {
+            continue;
+        }
+        status.apps.insert(app.clone(), state.clone());
+        changed.write(WebServerStatusChanged { app, state });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn backoff_doubles_up_to_the_max() {
+        let policy = RestartPolicy::exponential_backoff(SECOND, 10 * SECOND);
+        let delays: Vec<_> = (0..6).map(|failures| policy.delay(failures)).collect();
+        assert_eq!(
+            delays,
+            [1, 2, 4, 8, 10, 10].map(|secs| Some(Duration::from_secs(secs)))
+        );
+        assert_eq!(policy.delay(u32::MAX), Some(10 * SECOND));
+    }
+
+    #[test]
+    fn backoff_stops_after_max_restarts() {
+        let policy = RestartPolicy::ExponentialBackoff {
+            initial: SECOND,
+            max: 10 * SECOND,
+            max_restarts: Some(2),
+        };
+        assert_eq!(policy.delay(0), Some(SECOND));
+        assert_eq!(policy.delay(1), Some(2 * SECOND));
+        assert_eq!(policy.delay(2), None);
+    }
+
+    #[test]
+    fn never_does_not_restart() {
+        assert_eq!(RestartPolicy::Never.delay(0), None);
+        assert_eq!(RestartPolicy::Never.healthy_after(), None);
+    }
+}
@@ -0,0 +1,9 @@
+use bevy_leptos::leptos_app;
+
+#[leptos_app]
+async fn start_leptos_app<T: Default>() {}
+
+#[leptos_app]
+async fn start_other_app(param: impl Default) {}
+
+fn main() {}
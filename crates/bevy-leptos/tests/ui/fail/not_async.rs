@@ -0,0 +1,6 @@
+use bevy_leptos::leptos_app;
+
+#[leptos_app]
+fn start_leptos_app() {}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use bevy_leptos::leptos_app;
+
+struct Server;
+
+impl Server {
+    #[leptos_app]
+    async fn start_leptos_app(&self) {}
+}
+
+fn main() {}
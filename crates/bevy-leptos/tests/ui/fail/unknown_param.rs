@@ -0,0 +1,6 @@
+use bevy_leptos::leptos_app;
+
+#[leptos_app]
+async fn start_leptos_app(_port: u16) {}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use bevy_leptos::{leptos_app, LeptosAppFn};
+
+#[leptos_app]
+async fn start_leptos_app() {}
+
+fn main() {
+    let _: LeptosAppFn = start_leptos_app();
+}
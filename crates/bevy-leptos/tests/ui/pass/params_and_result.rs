@@ -0,0 +1,33 @@
+#![deny(missing_docs)]
+//! Parameters are resolved and visibility, attributes and docs are kept
+
+use bevy_leptos::{leptos_app, LeptosAppFn, LeptosServerConfig};
+use bevy_tokio_tasks::{CancellationToken, TaskContext};
+
+mod apps {
+    use super::*;
+
+    /// The operator UI
+    #[leptos_app]
+    #[allow(unused_variables)]
+    pub async fn operator_app(
+        world: TaskContext,
+        stop: CancellationToken,
+        mut config: Option<LeptosServerConfig>,
+    ) -> Result<(), std::io::Error> {
+        config.take();
+        stop.cancelled().await;
+        Ok(())
+    }
+
+    /// The admin UI
+    #[leptos_app]
+    pub(crate) async fn admin_app(config: LeptosServerConfig) -> Result<(), String> {
+        Err(format!("cannot serve on {}", config.site_addr()))
+    }
+}
+
+fn main() {
+    let _: LeptosAppFn = apps::operator_app();
+    let _: LeptosAppFn = apps::admin_app();
+}
@@ -0,0 +1,406 @@
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, Weak};
+
+use bevy::prelude::*;
+use tokio::sync::broadcast;
+
+use crate::{EcsError, TaskContext, TokioTasksSystems};
+
+/// Publishes the lifecycle of the component `C` (added, changed, removed) to async subscribers
+/// through a [`tokio::sync::broadcast`] channel.
+///
+/// Changes are detected once per frame, in the [`Last`] schedule. The plugin also keeps a copy of
+/// every entity's current `C`, which is used to hand new subscribers, and subscribers which fell
+/// behind, a full snapshot.
+///
+/// ```ignore
+/// app.add_plugins(ComponentWatchPlugin::<Battery>::default().with_capacity(4096));
+///
+/// runtime.spawn_background_task(|mut ctx| async move {
+///     let mut batteries = ctx.watch_components::<Battery>().await?;
+///     while let Some(update) = batteries.recv().await {
+///         match update {
+///             ComponentUpdate::Resync(snapshot) => dashboard.replace_all(snapshot),
+///             ComponentUpdate::Delta(entity, delta) => dashboard.apply(entity, delta),
+///         }
+///     }
+///     Ok::<_, EcsError>(())
+/// });
+/// ```
+pub struct ComponentWatchPlugin<C> {
+    capacity: usize,
+    _marker: PhantomData<fn() -> C>,
+}
+
+impl<C> Default for ComponentWatchPlugin<C> {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> ComponentWatchPlugin<C> {
+    /// Sets how many deltas a subscriber may fall behind before it is resynchronized with a snapshot.
+    /// Defaults to 1024.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+}
+
+impl<C: Component + Clone> Plugin for ComponentWatchPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ComponentWatch::<C>::new(self.capacity));
+        app.add_systems(
+            Last,
+            publish_component_changes::<C>.in_set(TokioTasksSystems::PublishChanges),
+        );
+    }
+}
+
+/// A change of the component `C` on one entity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComponentDelta<C> {
+    /// The component was added to the entity, or the entity was spawned with it.
+    Added(C),
+    /// The component's value changed.
+    Changed(C),
+    /// The component was removed from the entity, or the entity was despawned.
+    Removed,
+}
+
+/// An update received by a [`ComponentSubscription`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComponentUpdate<C> {
+    /// A change on one entity.
+    Delta(Entity, ComponentDelta<C>),
+    /// The complete set of entities with the component, replacing everything received so far.
+    /// Sent first to every new subscriber, and again whenever the subscriber fell behind and missed
+    /// deltas.
+    Resync(Vec<(Entity, C)>),
+}
+
+struct ComponentWatchShared<C> {
+    /// The current value of `C` for every entity. Locked by the publisher while it sends a frame's
+    /// deltas, so a snapshot taken together with a new receiver is consistent with it.
+    current: Mutex<HashMap<Entity, C>>,
+    tx: broadcast::Sender<(Entity, ComponentDelta<C>)>,
+}
+
+impl<C: Clone> ComponentWatchShared<C> {
+    fn snapshot_and_subscribe(
+        &self,
+        filter: &ComponentFilter<C>,
+    ) -> (Vec<(Entity, C)>, DeltaReceiver<C>) {
+        let current = self.current.lock().unwrap();
+        let snapshot = current
+            .iter()
+            .filter(|(entity, value)| filter(**entity, value))
+            .map(|(entity, value)| (*entity, value.clone()))
+            .collect();
+        (snapshot, self.tx.subscribe())
+    }
+}
+
+type DeltaReceiver<C> = broadcast::Receiver<(Entity, ComponentDelta<C>)>;
+
+type ComponentFilter<C> = Box<dyn Fn(Entity, &C) -> bool + Send + Sync>;
+
+/// The Bevy [`Resource`] holding the publishing side of the component `C`'s lifecycle stream, inserted
+/// by [`ComponentWatchPlugin`]. It is cheap to clone, so web handlers can keep a copy and subscribe
+/// for every new connection.
+#[derive(Resource)]
+pub struct ComponentWatch<C> {
+    shared: Arc<ComponentWatchShared<C>>,
+}
+
+impl<C> Clone for ComponentWatch<C> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<C: Component + Clone> ComponentWatch<C> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            shared: Arc::new(ComponentWatchShared {
+                current: Mutex::new(HashMap::new()),
+                tx: broadcast::Sender::new(capacity.max(1)),
+            }),
+        }
+    }
+
+    /// Subscribes to every entity with the component.
+    pub fn subscribe(&self) -> ComponentSubscription<C> {
+        self.subscribe_filtered(|_, _| true)
+    }
+
+    /// Subscribes to the entities accepted by `filter`, which receives the entity and its current
+    /// value of the component. The subscription tracks the entities it delivered: an
+    /// entity whose value becomes accepted is delivered as [`ComponentDelta::Added`], and one whose
+    /// value stops being accepted, or which loses the component, as [`ComponentDelta::Removed`].
+    ///
+    /// ```ignore
+    /// let low_batteries = watch.subscribe_filtered(|_, b| b.0 < 0.2);
+    /// ```
+    pub fn subscribe_filtered(
+        &self,
+        filter: impl Fn(Entity, &C) -> bool + Send + Sync + 'static,
+    ) -> ComponentSubscription<C> {
+        let filter: ComponentFilter<C> = Box::new(filter);
+        let (snapshot, rx) = self.shared.snapshot_and_subscribe(&filter);
+        ComponentSubscription {
+            shared: Arc::downgrade(&self.shared),
+            rx,
+            filter,
+            sent: snapshot.iter().map(|(entity, _)| *entity).collect(),
+            resync: Some(snapshot),
+        }
+    }
+
+    /// Returns a clone of the component's value on every entity, as of the last publish.
+    pub fn snapshot(&self) -> Vec<(Entity, C)> {
+        let current = self.shared.current.lock().unwrap();
+        current
+            .iter()
+            .map(|(entity, value)| (*entity, value.clone()))
+            .collect()
+    }
+
+    /// Returns the number of live subscriptions.
+    pub fn subscriber_count(&self) -> usize {
+        self.shared.tx.receiver_count()
+    }
+}
+
+/// A subscription to the lifecycle of the component `C`, created by [`ComponentWatch::subscribe`] or
+/// [`TaskContext::watch_components`].
+pub struct ComponentSubscription<C> {
+    shared: Weak<ComponentWatchShared<C>>,
+    rx: DeltaReceiver<C>,
+    filter: ComponentFilter<C>,
+    /// The entities the subscriber holds, i.e. which were delivered and not removed since
+    sent: HashSet<Entity>,
+    resync: Option<Vec<(Entity, C)>>,
+}
+
+impl<C: Clone> ComponentSubscription<C> {
+    /// Waits for the next update. The first update is always a [`ComponentUpdate::Resync`] with the
+    /// current state. If the subscriber falls more than the channel capacity behind, the missed
+    /// deltas are replaced by another resync. Returns `None` once the Bevy app has shut down.
+    pub async fn recv(&mut self) -> Option<ComponentUpdate<C>> {
+        if let Some(snapshot) = self.resync.take() {
+            return Some(ComponentUpdate::Resync(snapshot));
+        }
+        loop {
+            match self.rx.recv().await {
+                Ok((entity, delta)) => {
+                    if let Some(delta) = self.filter_delta(entity, delta) {
+                        return Some(ComponentUpdate::Delta(entity, delta));
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    let shared = self.shared.upgrade()?;
+                    let (snapshot, rx) = shared.snapshot_and_subscribe(&self.filter);
+                    self.rx = rx;
+                    self.sent = snapshot.iter().map(|(entity, _)| *entity).collect();
+                    return Some(ComponentUpdate::Resync(snapshot));
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The delta to deliver for `delta` on `entity`, given the entities the subscriber holds
+    fn filter_delta(
+        &mut self,
+        entity: Entity,
+        delta: ComponentDelta<C>,
+    ) -> Option<ComponentDelta<C>> {
+        let value = match delta {
+            ComponentDelta::Added(value) | ComponentDelta::Changed(value) => value,
+            ComponentDelta::Removed => {
+                return self.sent.remove(&entity).then_some(ComponentDelta::Removed);
+            }
+        };
+        if !(self.filter)(entity, &value) {
+            return self.sent.remove(&entity).then_some(ComponentDelta::Removed);
+        }
+        if self.sent.insert(entity) {
+            Some(ComponentDelta::Added(value))
+        } else {
+            Some(ComponentDelta::Changed(value))
+        }
+    }
+}
+
+/// The Bevy system which publishes the additions, changes and removals of the component `C` to its
+/// [`ComponentWatch`].
+pub fn publish_component_changes<C: Component + Clone>(
+    changed: Query<(Entity, Ref<C>), Changed<C>>,
+    mut removed: RemovedComponents<C>,
+    watch: Res<ComponentWatch<C>>,
+) {
+    let shared = &watch.shared;
+    let mut current = shared.current.lock().unwrap();
+    // Removals first, so that a component removed and inserted again in the same frame ends up
+    // present. Entities which gained and lost the component within one frame were never published.
+    for entity in removed.read() {
+        if current.remove(&entity).is_some() {
+            let _ = shared.tx.send((entity, ComponentDelta::Removed));
+        }
+    }
+    for (entity, value) in &changed {
+        let delta = if value.is_added() || !current.contains_key(&entity) {
+            ComponentDelta::Added(value.clone())
+        } else {
+            ComponentDelta::Changed(value.clone())
+        };
+        current.insert(entity, value.clone());
+        let _ = shared.tx.send((entity, delta));
+    }
+}
+
+impl TaskContext {
+    /// Subscribes to the lifecycle of the component `C`. Requires [`ComponentWatchPlugin<C>`] to be
+    /// added, otherwise [`EcsError::MissingResource`] is returned.
+    pub async fn watch_components<C: Component + Clone>(
+        &mut self,
+    ) -> Result<ComponentSubscription<C>, EcsError> {
+        Ok(self.resource::<ComponentWatch<C>>().await?.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Battery(f32);
+
+    #[test]
+    fn entities_leaving_the_value_filter_are_removed() {
+        let watch = ComponentWatch::<Battery>::new(16);
+        let mut low = watch.subscribe_filtered(|_, battery| battery.0 < 0.2);
+        let entity = Entity::from_raw_u32(1).unwrap();
+        let deltas = [
+            ComponentDelta::Added(Battery(0.5)),
+            ComponentDelta::Changed(Battery(0.1)),
+            ComponentDelta::Changed(Battery(0.05)),
+            ComponentDelta::Changed(Battery(0.9)),
+            ComponentDelta::Changed(Battery(0.8)),
+            ComponentDelta::Removed,
+        ];
+        let delivered: Vec<_> = deltas
+            .into_iter()
+            .map(|delta| low.filter_delta(entity, delta))
+            .collect();
+
+        assert_eq!(
+            delivered,
+            vec![
+                None,
+                Some(ComponentDelta::Added(Battery(0.1))),
+                Some(ComponentDelta::Changed(Battery(0.05))),
+                Some(ComponentDelta::Removed),
+                None,
+                None,
+            ]
+        );
+    }
+
+    fn app(capacity: usize) -> App {
+        let mut app = App::new();
+        app.add_plugins(ComponentWatchPlugin::<Battery>::default().with_capacity(capacity));
+        app
+    }
+
+    /// Receive the next update, which must already be published
+    fn next(subscription: &mut ComponentSubscription<Battery>) -> ComponentUpdate<Battery> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(subscription.recv()).unwrap()
+    }
+
+    #[test]
+    fn subscribers_receive_the_lifecycle_of_the_component() {
+        let mut app = app(16);
+        let mut batteries = app
+            .world()
+            .resource::<ComponentWatch<Battery>>()
+            .subscribe();
+        assert_eq!(next(&mut batteries), ComponentUpdate::Resync(vec![]));
+
+        let robot = app.world_mut().spawn(Battery(0.5)).id();
+        app.update();
+        assert_eq!(
+            next(&mut batteries),
+            ComponentUpdate::Delta(robot, ComponentDelta::Added(Battery(0.5)))
+        );
+
+        app.world_mut().get_mut::<Battery>(robot).unwrap().0 = 0.4;
+        app.update();
+        assert_eq!(
+            next(&mut batteries),
+            ComponentUpdate::Delta(robot, ComponentDelta::Changed(Battery(0.4)))
+        );
+
+        let drone = app.world_mut().spawn(Battery(1.0)).id();
+        app.update();
+        assert_eq!(
+            next(&mut batteries),
+            ComponentUpdate::Delta(drone, ComponentDelta::Added(Battery(1.0)))
+        );
+
+        app.world_mut().entity_mut(robot).remove::<Battery>();
+        app.world_mut().despawn(drone);
+        app.update();
+        assert_eq!(
+            [next(&mut batteries), next(&mut batteries)],
+            [
+                ComponentUpdate::Delta(robot, ComponentDelta::Removed),
+                ComponentUpdate::Delta(drone, ComponentDelta::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn lagging_subscribers_are_resynced() {
+        let mut app = app(2);
+        let mut batteries = app
+            .world()
+            .resource::<ComponentWatch<Battery>>()
+            .subscribe();
+        assert_eq!(next(&mut batteries), ComponentUpdate::Resync(vec![]));
+
+        let robots: Vec<Entity> = (0..3)
+            .map(|i| app.world_mut().spawn(Battery(i as f32)).id())
+            .collect();
+        app.update();
+        let ComponentUpdate::Resync(mut snapshot) = next(&mut batteries) else {
+            panic!("expected a resync");
+        };
+        let mut expected: Vec<_> = robots
+            .iter()
+            .enumerate()
+            .map(|(i, robot)| (*robot, Battery(i as f32)))
+            .collect();
+        snapshot.sort_by_key(|(entity, _)| *entity);
+        expected.sort_by_key(|(entity, _)| *entity);
+        assert_eq!(snapshot, expected);
+
+        // The entities of the snapshot are known, so the deltas continue from there.
+        app.world_mut().get_mut::<Battery>(robots[1]).unwrap().0 = 0.5;
+        app.update();
+        assert_eq!(
+            next(&mut batteries),
+            ComponentUpdate::Delta(robots[1], ComponentDelta::Changed(Battery(0.5)))
+        );
+    }
+}
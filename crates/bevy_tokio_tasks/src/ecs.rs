@@ -0,0 +1,484 @@
+use bevy::ecs::query::{QueryFilter, ReadOnlyQueryData};
+use bevy::ecs::system::{RegisteredSystemError, SystemInput};
+use bevy::ecs::world::CommandQueue;
+use bevy::prelude::*;
+
+use crate::{MainThreadClosed, TaskContext};
+
+/// The error returned by the async ECS accessors on [`TaskContext`], like
+/// [`resource`](TaskContext::resource) or [`insert`](TaskContext::insert).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EcsError {
+    /// The Bevy app has shut down before the operation could run.
+    MainThreadClosed,
+    /// The requested resource does not exist. Holds the resource's type name.
+    MissingResource(&'static str),
+    /// The entity does not exist.
+    NoSuchEntity(Entity),
+    /// The message type was not registered with `App::add_message`. Holds the message's type name.
+    UnregisteredMessage(&'static str),
+    /// A system run through [`run_system`](TaskContext::run_system) was skipped because its parameters
+    /// failed validation, e.g. a `Single` query matched no entity. Holds the validation message.
+    SystemSkipped(String),
+    /// A system run through [`run_system`](TaskContext::run_system) returned an error or could not be
+    /// run. Holds the error message.
+    SystemFailed(String),
+}
+
+impl<I: SystemInput, O> From<RegisteredSystemError<I, O>> for EcsError {
+    fn from(err: RegisteredSystemError<I, O>) -> Self {
+        match err {
+            RegisteredSystemError::Skipped(err) => EcsError::SystemSkipped(err.to_string()),
+            err => EcsError::SystemFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<MainThreadClosed> for EcsError {
+    fn from(_: MainThreadClosed) -> Self {
+        EcsError::MainThreadClosed
+    }
+}
+
+impl std::fmt::Display for EcsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EcsError::MainThreadClosed => MainThreadClosed.fmt(f),
+            EcsError::MissingResource(name) => write!(f, "resource {name} does not exist"),
+            EcsError::NoSuchEntity(entity) => write!(f, "entity {entity} does not exist"),
+            EcsError::UnregisteredMessage(name) => write!(f, "message {name} is not registered"),
+            EcsError::SystemSkipped(err) => write!(f, "system was skipped: {err}"),
+            EcsError::SystemFailed(err) => write!(f, "system failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for EcsError {}
+
+/// Read-only query data whose items can be copied out of the [`World`] and sent back to a background
+/// task by [`TaskContext::query`]. Implemented for [`Entity`], `&T` where `T` is a cloneable
+/// component, [`Has<T>`], [`Option`] of any of those, and tuples of up to eight of them.
+pub trait OwnedQueryData: ReadOnlyQueryData {
+    /// The owned form of one query item.
+    type Owned: Send + 'static;
+
+    /// Converts a borrowed query item into its owned form.
+    fn to_owned_item<'w, 's>(item: Self::Item<'w, 's>) -> Self::Owned;
+}
+
+impl OwnedQueryData for Entity {
+    type Owned = Entity;
+
+    fn to_owned_item<'w, 's>(item: Self::Item<'w, 's>) -> Self::Owned {
+        item
+    }
+}
+
+impl<T: Component + Clone> OwnedQueryData for &T {
+    type Owned = T;
+
+    fn to_owned_item<'w, 's>(item: Self::Item<'w, 's>) -> Self::Owned {
+        item.clone()
+    }
+}
+
+impl<T: Component> OwnedQueryData for Has<T> {
+    type Owned = bool;
+
+    fn to_owned_item<'w, 's>(item: Self::Item<'w, 's>) -> Self::Owned {
+        item
+    }
+}
+
+impl<D: OwnedQueryData> OwnedQueryData for Option<D> {
+    type Owned = Option<D::Owned>;
+
+    fn to_owned_item<'w, 's>(item: Self::Item<'w, 's>) -> Self::Owned {
+        item.map(D::to_owned_item)
+    }
+}
+
+macro_rules! impl_owned_query_data_tuple {
+    ($($name:ident),*) => {
+        impl<$($name: OwnedQueryData),*> OwnedQueryData for ($($name,)*) {
+            type Owned = ($($name::Owned,)*);
+
+            #[allow(non_snake_case)]
+            fn to_owned_item<'w, 's>(item: Self::Item<'w, 's>) -> Self::Owned {
+                let ($($name,)*) = item;
+                ($($name::to_owned_item($name),)*)
+            }
+        }
+    };
+}
+
+impl_owned_query_data_tuple!(A);
+impl_owned_query_data_tuple!(A, B);
+impl_owned_query_data_tuple!(A, B, C);
+impl_owned_query_data_tuple!(A, B, C, D);
+impl_owned_query_data_tuple!(A, B, C, D, E);
+impl_owned_query_data_tuple!(A, B, C, D, E, F);
+impl_owned_query_data_tuple!(A, B, C, D, E, F, G);
+impl_owned_query_data_tuple!(A, B, C, D, E, F, G, H);
+
+/// Typed access to the ECS from background tasks. Every accessor is a thin wrapper around
+/// [`try_run_on_main_thread`](TaskContext::try_run_on_main_thread), so it runs during the next
+/// [`tick_runtime_update`](crate::tick_runtime_update) and resolves once it has been applied.
+impl TaskContext {
+    /// Returns a clone of the resource `R`.
+    pub async fn resource<R: Resource + Clone>(&mut self) -> Result<R, EcsError> {
+        self.get_resource::<R>()
+            .await?
+            .ok_or(EcsError::MissingResource(std::any::type_name::<R>()))
+    }
+
+    /// Returns a clone of the resource `R`, or `None` if it does not exist.
+    pub async fn get_resource<R: Resource + Clone>(&mut self) -> Result<Option<R>, EcsError> {
+        Ok(self
+            .try_run_on_main_thread(|ctx| ctx.world.get_resource::<R>().cloned())
+            .await?)
+    }
+
+    /// Inserts or replaces the resource `R`.
+    pub async fn insert_resource<R: Resource>(&mut self, resource: R) -> Result<(), EcsError> {
+        Ok(self
+            .try_run_on_main_thread(move |ctx| ctx.world.insert_resource(resource))
+            .await?)
+    }
+
+    /// Runs the query `D` and returns owned copies of every item.
+    ///
+    /// ```ignore
+    /// let robots: Vec<(Entity, Position)> = ctx.query::<(Entity, &Position)>().await?;
+    /// ```
+    pub async fn query<D>(&mut self) -> Result<Vec<D::Owned>, EcsError>
+    where
+        D: OwnedQueryData + 'static,
+    {
+        self.query_filtered::<D, ()>().await
+    }
+
+    /// Runs the query `D` with the filter `F` and returns owned copies of every item.
+    ///
+    /// A new query state is built for every call, so change detection filters like `Changed<T>` or
+    /// `Added<T>` match every entity; use a system with [`run_system`](Self::run_system) to track changes.
+    ///
+    /// ```ignore
+    /// let moving: Vec<Entity> = ctx.query_filtered::<Entity, With<Velocity>>().await?;
+    /// ```
+    pub async fn query_filtered<D, F>(&mut self) -> Result<Vec<D::Owned>, EcsError>
+    where
+        D: OwnedQueryData + 'static,
+        F: QueryFilter + 'static,
+    {
+        Ok(self
+            .try_run_on_main_thread(|ctx| {
+                let mut query = ctx.world.query_filtered::<D, F>();
+                query.iter(ctx.world).map(D::to_owned_item).collect()
+            })
+            .await?)
+    }
+
+    /// Spawns a new entity with the given bundle and returns it.
+    pub async fn spawn<B: Bundle>(&mut self, bundle: B) -> Result<Entity, EcsError> {
+        Ok(self
+            .try_run_on_main_thread(move |ctx| ctx.world.spawn(bundle).id())
+            .await?)
+    }
+
+    /// Inserts a component or bundle into an existing entity.
+    pub async fn insert<B: Bundle>(&mut self, entity: Entity, bundle: B) -> Result<(), EcsError> {
+        self.try_run_on_main_thread(move |ctx| match ctx.world.get_entity_mut(entity) {
+            Ok(mut entity_mut) => {
+                entity_mut.insert(bundle);
+                Ok(())
+            }
+            Err(_) => Err(EcsError::NoSuchEntity(entity)),
+        })
+        .await?
+    }
+
+    /// Despawns an entity.
+    pub async fn despawn(&mut self, entity: Entity) -> Result<(), EcsError> {
+        self.try_run_on_main_thread(move |ctx| {
+            if ctx.world.despawn(entity) {
+                Ok(())
+            } else {
+                Err(EcsError::NoSuchEntity(entity))
+            }
+        })
+        .await?
+    }
+
+    /// Writes a [`Message`] so that `MessageReader<M>`s see it.
+    pub async fn write_message<M: Message>(&mut self, message: M) -> Result<(), EcsError> {
+        self.try_run_on_main_thread(move |ctx| {
+            ctx.world
+                .write_message(message)
+                .map(|_| ())
+                .ok_or(EcsError::UnregisteredMessage(std::any::type_name::<M>()))
+        })
+        .await?
+    }
+
+    /// Runs a closure with [`Commands`] on the main thread and applies the queued commands right
+    /// away, which batches several structural changes into a single main thread round trip.
+    ///
+    /// ```ignore
+    /// let robot = ctx
+    ///     .commands(|commands| {
+    ///         let robot = commands.spawn(Robot).id();
+    ///         commands.entity(robot).insert(Position::default());
+    ///         robot
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn commands<F, Output>(&mut self, f: F) -> Result<Output, EcsError>
+    where
+        F: FnOnce(&mut Commands) -> Output + Send + 'static,
+        Output: Send + 'static,
+    {
+        Ok(self
+            .try_run_on_main_thread(move |ctx| {
+                let mut queue = CommandQueue::default();
+                let output = f(&mut Commands::new(&mut queue, ctx.world));
+                queue.apply(ctx.world);
+                output
+            })
+            .await?)
+    }
+
+    /// Runs an ordinary Bevy system on the main thread and returns its output, so that background
+    /// tasks can reuse the same `Res`, `Query` and `Commands` logic as the app's systems. The input is
+    /// passed as the system's [`In`] parameter, use `()` for systems without one.
+    ///
+    /// The system is registered as a one-shot system the first time it is run, and its state (change
+    /// ticks, cached queries) is kept for later calls. Because the cache is keyed by the system's type,
+    /// the system must be a function or a non-capturing closure; pass any data it needs as input.
+    ///
+    /// ```ignore
+    /// fn robot_positions(robots: Query<(&Name, &Position)>) -> Vec<(String, Position)> {
+    ///     robots.iter().map(|(name, pos)| (name.to_string(), *pos)).collect()
+    /// }
+    ///
+    /// fn move_robot(In((robot, target)): In<(Entity, Position)>, mut commands: Commands) {
+    ///     commands.entity(robot).insert(target);
+    /// }
+    ///
+    /// let positions = ctx.run_system(robot_positions, ()).await?;
+    /// ctx.run_system(move_robot, (robot, target)).await?;
+    /// ```
+    pub async fn run_system<I, O, M, S>(
+        &mut self,
+        system: S,
+        input: I::Inner<'static>,
+    ) -> Result<O, EcsError>
+    where
+        I: SystemInput + 'static,
+        I::Inner<'static>: Send,
+        O: Send + 'static,
+        S: IntoSystem<I, O, M> + Send + 'static,
+    {
+        self.try_run_on_main_thread(move |ctx| {
+            ctx.world
+                .run_system_cached_with(system, input)
+                .map_err(EcsError::from)
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TokioTasksPlugin, TokioTasksRuntime};
+    use std::future::Future;
+    use std::time::Duration;
+
+    #[derive(Resource, Clone, Debug, PartialEq)]
+    struct Gains(f32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(Component)]
+    struct Robot;
+
+    #[derive(Resource, Clone, Debug, PartialEq)]
+    struct Missing;
+
+    #[derive(Message, Debug, PartialEq)]
+    struct Ping(u32);
+
+    #[derive(Message)]
+    struct Unregistered;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default());
+        app
+    }
+
+    /// Run `task` in the background, updating the app until it completes
+    fn run<Task, Output>(
+        app: &mut App,
+        task: impl FnOnce(TaskContext) -> Task + Send + 'static,
+    ) -> Output
+    where
+        Task: Future<Output = Output> + Send + 'static,
+        Output: Send + 'static,
+    {
+        let handle = app
+            .world()
+            .resource::<TokioTasksRuntime>()
+            .spawn_background_task(task);
+        while !handle.is_finished() {
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let runtime = app.world().resource::<TokioTasksRuntime>();
+        runtime.runtime().block_on(handle).unwrap()
+    }
+
+    #[test]
+    fn resources_round_trip() {
+        let mut app = app();
+        let (gains, missing, error) = run(&mut app, |mut ctx| async move {
+            ctx.insert_resource(Gains(1.5)).await.unwrap();
+            (
+                ctx.resource::<Gains>().await,
+                ctx.get_resource::<Missing>().await,
+                ctx.resource::<Missing>().await,
+            )
+        });
+        assert_eq!(gains, Ok(Gains(1.5)));
+        assert_eq!(missing, Ok(None));
+        assert_eq!(
+            error,
+            Err(EcsError::MissingResource(std::any::type_name::<Missing>()))
+        );
+        assert_eq!(app.world().resource::<Gains>(), &Gains(1.5));
+    }
+
+    #[test]
+    fn entities_round_trip() {
+        let mut app = app();
+        let robot = run(&mut app, |mut ctx| async move {
+            let robot = ctx.spawn((Robot, Position(1))).await.unwrap();
+            ctx.insert(robot, Velocity(2)).await.unwrap();
+            ctx.spawn(Position(5)).await.unwrap();
+            robot
+        });
+        assert_eq!(app.world().get::<Velocity>(robot), Some(&Velocity(2)));
+
+        let (all, moving) = run(&mut app, |mut ctx| async move {
+            let mut all = ctx
+                .query::<(&Position, Option<&Velocity>, Has<Robot>)>()
+                .await
+                .unwrap();
+            all.sort_by_key(|(position, ..)| position.0);
+            let moving = ctx
+                .query_filtered::<Entity, With<Velocity>>()
+                .await
+                .unwrap();
+            (all, moving)
+        });
+        assert_eq!(
+            all,
+            [
+                (Position(1), Some(Velocity(2)), true),
+                (Position(5), None, false),
+            ]
+        );
+        assert_eq!(moving, [robot]);
+
+        let (despawned, again, insert) = run(&mut app, move |mut ctx| async move {
+            (
+                ctx.despawn(robot).await,
+                ctx.despawn(robot).await,
+                ctx.insert(robot, Velocity(3)).await,
+            )
+        });
+        assert_eq!(despawned, Ok(()));
+        assert_eq!(again, Err(EcsError::NoSuchEntity(robot)));
+        assert_eq!(insert, Err(EcsError::NoSuchEntity(robot)));
+        assert!(app.world().get_entity(robot).is_err());
+    }
+
+    #[test]
+    fn messages_are_written() {
+        let mut app = app();
+        app.add_message::<Ping>();
+        let (written, unregistered) = run(&mut app, |mut ctx| async move {
+            (
+                ctx.write_message(Ping(7)).await,
+                ctx.write_message(Unregistered).await,
+            )
+        });
+        assert_eq!(written, Ok(()));
+        assert_eq!(
+            unregistered,
+            Err(EcsError::UnregisteredMessage(std::any::type_name::<
+                Unregistered,
+            >()))
+        );
+        let messages = app.world().resource::<Messages<Ping>>();
+        let mut cursor = messages.get_cursor();
+        let read: Vec<_> = cursor.read(messages).collect();
+        assert_eq!(read, [&Ping(7)]);
+    }
+
+    #[test]
+    fn commands_are_applied_at_once() {
+        let mut app = app();
+        let robot = run(&mut app, |mut ctx| async move {
+            ctx.commands(|commands| {
+                let robot = commands.spawn(Robot).id();
+                commands.entity(robot).insert(Position(3));
+                robot
+            })
+            .await
+            .unwrap()
+        });
+        assert_eq!(app.world().get::<Position>(robot), Some(&Position(3)));
+    }
+
+    fn accumulate(In(step): In<u32>, mut total: Local<u32>) -> u32 {
+        *total += step;
+        *total
+    }
+
+    fn robot_position(robot: Single<&Position, With<Robot>>) -> Position {
+        robot.clone()
+    }
+
+    #[test]
+    fn cached_systems_keep_their_state_between_runs() {
+        let mut app = app();
+        let totals = run(&mut app, |mut ctx| async move {
+            (
+                ctx.run_system(accumulate, 1).await,
+                ctx.run_system(accumulate, 2).await,
+            )
+        });
+        assert_eq!(totals, (Ok(1), Ok(3)));
+    }
+
+    #[test]
+    fn skipped_systems_are_reported() {
+        let mut app = app();
+        let skipped = run(&mut app, |mut ctx| async move {
+            ctx.run_system(robot_position, ()).await
+        });
+        assert!(matches!(skipped, Err(EcsError::SystemSkipped(_))));
+
+        app.world_mut().spawn((Robot, Position(4)));
+        let position = run(&mut app, |mut ctx| async move {
+            ctx.run_system(robot_position, ()).await
+        });
+        assert_eq!(position, Ok(Position(4)));
+    }
+}
@@ -1,10 +1,29 @@
 use std::future::Future;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
+use bevy::platform::time::Instant;
 use bevy::prelude::*;
 
 use tokio::{runtime::Runtime, task::JoinHandle};
+pub use tokio_util::sync::CancellationToken;
+
+mod component_watch;
+mod ecs;
+mod outcome;
+mod queue;
+mod registry;
+mod shutdown;
+mod watch;
+pub use component_watch::*;
+pub use ecs::*;
+pub use outcome::*;
+pub use queue::{MainThreadQueueConfig, MainThreadQueueStats, TryQueueError};
+pub use registry::*;
+pub use shutdown::signal_shutdown_on_app_exit;
+pub use watch::*;
 
 /// An internal struct keeping track of how many ticks have elapsed since the start of the program.
 #[derive(Resource)]
@@ -31,6 +50,41 @@ pub struct TokioTasksPlugin {
     /// functionality enabled if building for non-wasm32 architectures. On wasm32 the current-thread
     /// scheduler is used instead.
     pub make_runtime: Box<dyn Fn() -> Runtime + Send + Sync + 'static>,
+    /// The schedules in which the [`tick_runtime_update`] system runs, inside the
+    /// [`TokioTasksSystems::TickRuntime`] system set. Main thread callbacks are applied and the update
+    /// tick is advanced every time the system runs, so adding several schedules (or a schedule which
+    /// runs several times per frame, like [`FixedPreUpdate`]) makes [`TaskContext::sleep_updates`]
+    /// count those runs. Must not be empty, since tasks waiting on the main thread would never be
+    /// woken. Defaults to [`Update`].
+    ///
+    /// ```ignore
+    /// TokioTasksPlugin {
+    ///     tick_schedules: vec![FixedPreUpdate.intern()],
+    ///     ..TokioTasksPlugin::default()
+    /// }
+    /// ```
+    pub tick_schedules: Vec<InternedScheduleLabel>,
+    /// Bounds the queue of main thread callbacks and limits how many of them run per tick. Defaults
+    /// to an unbounded queue which is fully drained every tick. Queue metrics are published in the
+    /// [`MainThreadQueueStats`] resource.
+    pub main_thread_queue: MainThreadQueueConfig,
+    /// What happens when a background task fails or panics. Inserted as the [`TaskFailurePolicy`]
+    /// resource, and can be overridden per task through [`TaskBuilder::on_failure`]. Defaults to
+    /// [`TaskFailurePolicy::Emit`].
+    pub failure_policy: TaskFailurePolicy,
+    /// How long background tasks are given to finish after the shutdown token handed out through
+    /// [`TaskContext::shutdown_token`] is cancelled, before the runtime is dropped and the remaining
+    /// tasks are aborted. Defaults to 5 seconds.
+    pub shutdown_timeout: Duration,
+    /// When true, SIGINT (Ctrl+C) and SIGTERM are turned into an [`AppExit`] so the app can shut down
+    /// gracefully. A second signal exits the process immediately with the conventional code of that
+    /// signal (130 for SIGINT, 143 for SIGTERM). Has no effect on wasm32. Defaults to false, leaving
+    /// signal handling to the app.
+    pub handle_signals: bool,
+    /// How many tasks which stopped running the [`TaskRegistry`] keeps for inspection. Beyond that
+    /// the oldest ones are evicted once their outcome was handled, so apps spawning many short lived
+    /// tasks use bounded memory. Defaults to 1024.
+    pub finished_task_capacity: usize,
 }
 
 impl Default for TokioTasksPlugin {
@@ -49,28 +103,87 @@ impl Default for TokioTasksPlugin {
                     .build()
                     .expect("Failed to create Tokio runtime for background tasks")
             }),
+            tick_schedules: vec![Update.intern()],
+            main_thread_queue: MainThreadQueueConfig::default(),
+            failure_policy: TaskFailurePolicy::default(),
+            shutdown_timeout: Duration::from_secs(5),
+            handle_signals: false,
+            finished_task_capacity: 1024,
         }
     }
 }
 
 impl Plugin for TokioTasksPlugin {
     fn build(&self, app: &mut App) {
+        assert!(
+            !self.tick_schedules.is_empty(),
+            "TokioTasksPlugin::tick_schedules must contain at least one schedule"
+        );
         let ticks = Arc::new(AtomicUsize::new(0));
         let (update_watch_tx, update_watch_rx) = tokio::sync::watch::channel(());
         let runtime = (self.make_runtime)();
+        let (registry, outcomes) = TaskRegistry::new(self.finished_task_capacity);
         app.insert_resource(UpdateTicks {
             ticks: ticks.clone(),
             update_watch_tx,
         });
-        app.insert_resource(TokioTasksRuntime::new(ticks, runtime, update_watch_rx));
-        app.add_systems(Update, tick_runtime_update);
+        app.insert_resource(registry.clone());
+        app.insert_resource(outcomes);
+        app.insert_resource(self.failure_policy.clone());
+        let runtime = TokioTasksRuntime::new(
+            ticks,
+            runtime,
+            update_watch_rx,
+            registry,
+            self.main_thread_queue.clone(),
+            self.shutdown_timeout,
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.handle_signals {
+            runtime
+                .task()
+                .name("shutdown_signals")
+                .spawn(shutdown::exit_on_signal);
+        }
+        app.insert_resource(runtime);
+        app.init_resource::<MainThreadQueueStats>();
+        app.add_message::<TaskFinished>();
+        app.add_message::<TaskFailed>();
+        for schedule in &self.tick_schedules {
+            app.add_systems(
+                *schedule,
+                tick_runtime_update.in_set(TokioTasksSystems::TickRuntime),
+            );
+        }
+        app.add_systems(PreUpdate, handle_task_outcomes);
+        app.add_systems(
+            Last,
+            (abort_despawned_entity_tasks, signal_shutdown_on_app_exit),
+        );
     }
 }
 
+/// The system sets the [`TokioTasksPlugin`] adds its systems to. Use them to order your own systems
+/// relative to the main thread callbacks, for example so that sensor data written by background
+/// tasks is visible to a `FixedUpdate` system in the same tick:
+///
+/// ```ignore
+/// app.configure_sets(FixedPreUpdate, TokioTasksSystems::TickRuntime.before(MySensorSet));
+/// ```
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokioTasksSystems {
+    /// Contains [`tick_runtime_update`] in every schedule listed in
+    /// [`tick_schedules`](TokioTasksPlugin::tick_schedules).
+    TickRuntime,
+    /// Contains the systems in [`Last`] which publish ECS changes to async subscribers, like the one
+    /// added by [`ResourceWatchPlugin`] and [`ComponentWatchPlugin`].
+    PublishChanges,
+}
+
 /// The Bevy exclusive system which executes the main thread callbacks that background
 /// tasks requested using [`run_on_main_thread`](TaskContext::run_on_main_thread). You
-/// can control which [`CoreStage`] this system executes in by specifying a custom
-/// [`tick_stage`](TokioTasksPlugin::tick_stage) value.
+/// can control which schedules this system executes in by specifying custom
+/// [`tick_schedules`](TokioTasksPlugin::tick_schedules).
 pub fn tick_runtime_update(world: &mut World) {
     let current_tick = {
         let tick_counter = match world.get_resource::<UpdateTicks>() {
@@ -101,8 +214,25 @@ struct TokioTasksRuntimeInner {
     runtime: Runtime,
     ticks: Arc<AtomicUsize>,
     update_watch_rx: tokio::sync::watch::Receiver<()>,
-    update_run_tx: tokio::sync::mpsc::UnboundedSender<MainThreadCallback>,
-    update_run_rx: tokio::sync::mpsc::UnboundedReceiver<MainThreadCallback>,
+    update_run_tx: queue::MainThreadSender,
+    update_run_rx: queue::MainThreadReceiver,
+    registry: TaskRegistry,
+    shutdown: CancellationToken,
+    shutdown_timeout: Duration,
+}
+
+impl Drop for TokioTasksRuntimeInner {
+    /// Gives background tasks a chance to finish before the runtime is dropped. Main thread callbacks
+    /// can no longer run at this point, so any pending or future ones fail with [`MainThreadClosed`].
+    fn drop(&mut self) {
+        self.update_run_rx.close();
+        shutdown::drain(
+            &self.runtime,
+            &self.registry,
+            &self.shutdown,
+            self.shutdown_timeout,
+        );
+    }
 }
 
 impl TokioTasksRuntime {
@@ -110,8 +240,11 @@ impl TokioTasksRuntime {
         ticks: Arc<AtomicUsize>,
         runtime: Runtime,
         update_watch_rx: tokio::sync::watch::Receiver<()>,
+        registry: TaskRegistry,
+        main_thread_queue: MainThreadQueueConfig,
+        shutdown_timeout: Duration,
     ) -> Self {
-        let (update_run_tx, update_run_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (update_run_tx, update_run_rx) = queue::channel(main_thread_queue);
 
         Self(Box::new(TokioTasksRuntimeInner {
             runtime,
@@ -119,19 +252,37 @@ impl TokioTasksRuntime {
             update_watch_rx,
             update_run_tx,
             update_run_rx,
+            registry,
+            shutdown: CancellationToken::new(),
+            shutdown_timeout,
         }))
     }
 
+    /// Returns the token which is cancelled when the app shuts down. The same token is available to
+    /// background tasks through [`TaskContext::shutdown_token`]. Cancelling it manually tells every
+    /// background task to wind down without exiting the app.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.0.shutdown.clone()
+    }
+
     /// Returns the Tokio [`Runtime`] on which background tasks are executed. You can specify
     /// how this is created by providing a custom [`make_runtime`](TokioTasksPlugin::make_runtime).
     pub fn runtime(&self) -> &Runtime {
         &self.0.runtime
     }
 
+    /// Returns the [`TaskRegistry`] in which every task spawned through this runtime is tracked.
+    pub fn registry(&self) -> &TaskRegistry {
+        &self.0.registry
+    }
+
     /// Spawn a task which will run on the background Tokio [`Runtime`] managed by this [`TokioTasksRuntime`]. The
     /// background task is provided a [`TaskContext`] which allows it to do things like
     /// [sleep for a given number of main thread updates](TaskContext::sleep_updates) or
     /// [invoke callbacks on the main Bevy thread](TaskContext::run_on_main_thread).
+    ///
+    /// The task is recorded in the [`TaskRegistry`] without a name. Use [`task`](Self::task) to name
+    /// the task or bind it to an entity.
     pub fn spawn_background_task<Task, Output, Spawnable>(
         &self,
         spawnable_task: Spawnable,
@@ -141,14 +292,74 @@ impl TokioTasksRuntime {
         Output: Send + 'static,
         Spawnable: FnOnce(TaskContext) -> Task + Send + 'static,
     {
+        self.spawn_tracked(TaskOptions::default(), spawnable_task, |_| {
+            TaskStatus::Finished
+        })
+        .1
+    }
+
+    /// Returns a [`TaskBuilder`] which spawns a background task with a name and/or a bound entity.
+    ///
+    /// ```ignore
+    /// runtime
+    ///     .task()
+    ///     .name("sensor_reader")
+    ///     .bind_to(robot)
+    ///     .spawn(|mut ctx| async move { /* ... */ });
+    /// ```
+    pub fn task(&self) -> TaskBuilder<'_> {
+        TaskBuilder::new(self)
+    }
+
+    fn task_context(&self) -> TaskContext {
         let inner = &self.0;
-        let context = TaskContext {
+        TaskContext {
             update_watch_rx: inner.update_watch_rx.clone(),
             ticks: inner.ticks.clone(),
             update_run_tx: inner.update_run_tx.clone(),
-        };
-        let future = spawnable_task(context);
-        inner.runtime.spawn(future)
+            shutdown: inner.shutdown.clone(),
+        }
+    }
+
+    pub(crate) fn spawn_tracked<Task, Output, Spawnable>(
+        &self,
+        options: TaskOptions,
+        spawnable_task: Spawnable,
+        classify: impl FnOnce(&Output) -> TaskStatus + Send + 'static,
+    ) -> (TaskId, JoinHandle<Output>)
+    where
+        Task: Future<Output = Output> + Send + 'static,
+        Output: Send + 'static,
+        Spawnable: FnOnce(TaskContext) -> Task + Send + 'static,
+    {
+        let inner = &self.0;
+        let spawn_tick = inner.ticks.load(Ordering::SeqCst);
+        let task = spawnable_task(self.task_context());
+        inner.registry.register(options, spawn_tick, |id| {
+            inner
+                .runtime
+                .spawn(inner.registry.track(id, task, classify))
+        })
+    }
+
+    pub(crate) fn respawn_tracked<Task, Output, Spawnable>(
+        &self,
+        id: TaskId,
+        spawnable_task: Spawnable,
+        classify: impl FnOnce(&Output) -> TaskStatus + Send + 'static,
+    ) where
+        Task: Future<Output = Output> + Send + 'static,
+        Output: Send + 'static,
+        Spawnable: FnOnce(TaskContext) -> Task + Send + 'static,
+    {
+        let inner = &self.0;
+        let spawn_tick = inner.ticks.load(Ordering::SeqCst);
+        let task = spawnable_task(self.task_context());
+        inner.registry.respawn(id, spawn_tick, |id| {
+            inner
+                .runtime
+                .spawn(inner.registry.track(id, task, classify))
+        });
     }
 
     /// Execute all of the requested runnables on the main thread.
@@ -159,12 +370,19 @@ impl TokioTasksRuntime {
         self.0.runtime.block_on(async {
             tokio::task::yield_now().await;
         });
-        while let Ok(runnable) = self.0.update_run_rx.try_recv() {
+        let started = Instant::now();
+        let mut executed = 0;
+        while let Some(runnable) = self.0.update_run_rx.next(executed, started.elapsed()) {
             let context = MainThreadContext {
                 world,
                 current_tick,
             };
             runnable(context);
+            executed += 1;
+        }
+        let stats = self.0.update_run_rx.finish_tick(executed);
+        if let Some(mut published) = world.get_resource_mut::<MainThreadQueueStats>() {
+            published.clone_from(stats);
         }
     }
 }
@@ -184,8 +402,9 @@ pub struct MainThreadContext<'a> {
 #[derive(Clone, Debug)]
 pub struct TaskContext {
     pub update_watch_rx: tokio::sync::watch::Receiver<()>,
-    pub update_run_tx: tokio::sync::mpsc::UnboundedSender<MainThreadCallback>,
+    update_run_tx: queue::MainThreadSender,
     ticks: Arc<AtomicUsize>,
+    shutdown: CancellationToken,
 }
 
 impl TaskContext {
@@ -196,9 +415,35 @@ impl TaskContext {
         self.ticks.load(Ordering::SeqCst)
     }
 
+    /// Returns the token which is cancelled when the app shuts down, for example after an [`AppExit`]
+    /// or, with [`handle_signals`](TokioTasksPlugin::handle_signals), a SIGINT/SIGTERM. Long lived
+    /// tasks should stop, flush their state and return once it is cancelled; they are aborted when
+    /// the [`shutdown_timeout`](TokioTasksPlugin::shutdown_timeout) elapses.
+    ///
+    /// ```ignore
+    /// axum::serve(listener, app)
+    ///     .with_graceful_shutdown(ctx.shutdown_token().cancelled_owned())
+    ///     .await
+    /// ```
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Returns true once the app has started shutting down.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    /// Waits until the app starts shutting down.
+    pub async fn shutdown_requested(&self) {
+        self.shutdown.cancelled().await
+    }
+
     /// Sleeps the background task until a given number of main thread updates have occurred. If
     /// you instead want to sleep for a given length of wall-clock time, call the normal Tokio sleep
-    /// function.
+    /// function. Keeps waiting for updates after the [shutdown token](Self::shutdown_token) is
+    /// cancelled; only yields and returns early once the main thread is gone for good. See
+    /// [`try_sleep_updates`](Self::try_sleep_updates) to stop sleeping on shutdown.
     pub async fn sleep_updates(&mut self, updates_to_sleep: usize) {
         let target_tick = self
             .ticks
@@ -206,30 +451,262 @@ impl TaskContext {
             .wrapping_add(updates_to_sleep);
         while self.ticks.load(Ordering::SeqCst) < target_tick {
             if self.update_watch_rx.changed().await.is_err() {
+                // No update will ever happen again, don't let loops around this spin.
+                tokio::task::yield_now().await;
                 return;
             }
         }
     }
 
+    /// Like [`sleep_updates`](Self::sleep_updates), but returns [`SleepError::ShuttingDown`] if the
+    /// [shutdown token](Self::shutdown_token) is cancelled, or already was, and
+    /// [`SleepError::MainThreadClosed`] if the main thread stops running updates, before the given
+    /// number of updates have occurred.
+    pub async fn try_sleep_updates(&mut self, updates_to_sleep: usize) -> Result<(), SleepError> {
+        let target_tick = self
+            .ticks
+            .load(Ordering::SeqCst)
+            .wrapping_add(updates_to_sleep);
+        while self.ticks.load(Ordering::SeqCst) < target_tick {
+            tokio::select! {
+                changed = self.update_watch_rx.changed() => {
+                    if changed.is_err() {
+                        return Err(SleepError::MainThreadClosed);
+                    }
+                }
+                _ = self.shutdown.cancelled() => return Err(SleepError::ShuttingDown),
+            }
+        }
+        Ok(())
+    }
+
     /// Invokes a synchronous callback on the main Bevy thread. The callback will have mutable access to the
     /// main Bevy [`World`], allowing it to update any resources or entities that it wants. The callback can
     /// report results back to the background thread by returning an output value, which will then be returned from
-    /// this async function once the callback runs.
+    /// this async function once the callback runs. If the callback queue is bounded and full, this waits
+    /// until there is room for the callback.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the Bevy app shuts down before the callback runs. Long lived tasks should use
+    /// [`try_run_on_main_thread`](Self::try_run_on_main_thread) instead.
     pub async fn run_on_main_thread<Runnable, Output>(&mut self, runnable: Runnable) -> Output
+    where
+        Runnable: FnOnce(MainThreadContext) -> Output + Send + 'static,
+        Output: Send + 'static,
+    {
+        self.try_run_on_main_thread(runnable)
+            .await
+            .expect("Failed to run operation on main thread")
+    }
+
+    /// Like [`run_on_main_thread`](Self::run_on_main_thread), but returns [`MainThreadClosed`] instead of
+    /// panicking if the Bevy app shuts down before the callback runs.
+    ///
+    /// ```ignore
+    /// loop {
+    ///     let reading = read_sensor().await;
+    ///     if ctx.try_run_on_main_thread(move |ctx| store(ctx.world, reading)).await.is_err() {
+    ///         break; // The app is shutting down.
+    ///     }
+    /// }
+    /// ```
+    pub async fn try_run_on_main_thread<Runnable, Output>(
+        &mut self,
+        runnable: Runnable,
+    ) -> Result<Output, MainThreadClosed>
     where
         Runnable: FnOnce(MainThreadContext) -> Output + Send + 'static,
         Output: Send + 'static,
     {
         let (output_tx, output_rx) = tokio::sync::oneshot::channel();
-        if self.update_run_tx.send(Box::new(move |ctx| {
-            if output_tx.send(runnable(ctx)).is_err() {
-                panic!("Failed to sent output from operation run on main thread back to waiting task");
-            }
-        })).is_err() {
-            panic!("Failed to send operation to be run on main thread");
+        self.update_run_tx
+            .send(Box::new(move |ctx| {
+                // The waiting task may have been cancelled in the meantime, in which case nobody is
+                // interested in the output anymore.
+                let _ = output_tx.send(runnable(ctx));
+            }))
+            .await?;
+        output_rx.await.map_err(|_| MainThreadClosed)
+    }
+
+    /// Queues a callback to run on the main Bevy thread without waiting for it to run or for space in
+    /// the queue. If the queue configured through
+    /// [`main_thread_queue`](TokioTasksPlugin::main_thread_queue) is full, the callback is dropped and
+    /// [`TryQueueError::Full`] is returned, which suits high frequency producers like sensor readers
+    /// that would rather skip a sample than fall behind.
+    pub fn try_queue_on_main_thread<Runnable>(
+        &self,
+        runnable: Runnable,
+    ) -> Result<(), TryQueueError>
+    where
+        Runnable: FnOnce(MainThreadContext) + Send + 'static,
+    {
+        self.update_run_tx.try_send(Box::new(runnable))
+    }
+}
+
+/// The error returned by [`TaskContext::try_run_on_main_thread`] when the Bevy app has shut down, so
+/// the main thread will not run any more updates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MainThreadClosed;
+
+impl std::fmt::Display for MainThreadClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the Bevy main thread is no longer running updates")
+    }
+}
+
+impl std::error::Error for MainThreadClosed {}
+
+/// The error returned by [`TaskContext::try_sleep_updates`] when it stops sleeping early.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepError {
+    /// The shutdown token was cancelled, by an [`AppExit`] or by hand. The main thread may still be
+    /// running updates.
+    ShuttingDown,
+    /// The main thread stopped running updates.
+    MainThreadClosed,
+}
+
+impl From<MainThreadClosed> for SleepError {
+    fn from(_: MainThreadClosed) -> Self {
+        SleepError::MainThreadClosed
+    }
+}
+
+impl std::fmt::Display for SleepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SleepError::ShuttingDown => f.write_str("the background tasks are shutting down"),
+            SleepError::MainThreadClosed => MainThreadClosed.fmt(f),
         }
-        output_rx
-            .await
-            .expect("Failed to receive output from operation on main thread")
+    }
+}
+
+impl std::error::Error for SleepError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run updates until `handle` finished, then return its output
+    fn finish<Output>(app: &mut App, handle: JoinHandle<Output>) -> Output {
+        while !handle.is_finished() {
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let runtime = app.world().resource::<TokioTasksRuntime>();
+        runtime.runtime().block_on(handle).unwrap()
+    }
+
+    #[test]
+    fn main_thread_callbacks_fail_once_the_app_is_dropped() {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default());
+        let (results_tx, results_rx) = std::sync::mpsc::channel();
+        app.world()
+            .resource::<TokioTasksRuntime>()
+            .spawn_background_task(|mut ctx| async move {
+                // Queued before or after the queue is closed, the callback never runs.
+                let pending = ctx.try_run_on_main_thread(|_| ()).await;
+                let after = ctx.try_run_on_main_thread(|_| ()).await;
+                results_tx.send((pending, after)).unwrap();
+            });
+
+        // Dropping the runtime waits for the task.
+        drop(app);
+        assert_eq!(
+            results_rx.try_recv().unwrap(),
+            (Err(MainThreadClosed), Err(MainThreadClosed))
+        );
+    }
+
+    #[test]
+    fn sleeping_stops_when_shutting_down() {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default());
+        let runtime = app.world().resource::<TokioTasksRuntime>();
+        let shutdown = runtime.shutdown_token();
+        let handle = runtime.spawn_background_task(|mut ctx| async move {
+            let sleeping = ctx.try_sleep_updates(usize::MAX / 2).await;
+            let cancelled = ctx.try_sleep_updates(1).await;
+            (sleeping, cancelled)
+        });
+
+        app.update();
+        shutdown.cancel();
+        assert_eq!(
+            finish(&mut app, handle),
+            (Err(SleepError::ShuttingDown), Err(SleepError::ShuttingDown))
+        );
+    }
+
+    #[test]
+    fn sleeping_stops_when_updates_stop() {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default());
+        let handle = app
+            .world()
+            .resource::<TokioTasksRuntime>()
+            .spawn_background_task(|mut ctx| async move { ctx.try_sleep_updates(10).await });
+
+        app.update();
+        // The tick counter goes away with the world, stopping the updates.
+        app.world_mut().remove_resource::<UpdateTicks>();
+        assert_eq!(finish(&mut app, handle), Err(SleepError::MainThreadClosed));
+    }
+
+    #[derive(Resource, Default)]
+    struct FixedRuns(usize);
+
+    #[test]
+    fn tasks_are_ticked_by_the_configured_schedules() {
+        let mut app = App::new();
+        app.add_plugins((
+            bevy::time::TimePlugin,
+            TokioTasksPlugin {
+                tick_schedules: vec![FixedPreUpdate.intern()],
+                ..TokioTasksPlugin::default()
+            },
+        ))
+        // Every update runs the fixed schedules three times.
+        .insert_resource(bevy::time::TimeUpdateStrategy::ManualDuration(
+            Time::<Fixed>::default().timestep() * 3,
+        ))
+        .init_resource::<FixedRuns>()
+        .add_systems(
+            FixedPreUpdate,
+            (|mut runs: ResMut<FixedRuns>| runs.0 += 1).before(TokioTasksSystems::TickRuntime),
+        );
+        let handle = app
+            .world()
+            .resource::<TokioTasksRuntime>()
+            .spawn_background_task(|mut ctx| async move {
+                let start = ctx.current_tick();
+                ctx.sleep_updates(6).await;
+                let slept = ctx.current_tick() - start;
+                let (tick, runs) = ctx
+                    .run_on_main_thread(|ctx| {
+                        (ctx.current_tick, ctx.world.resource::<FixedRuns>().0)
+                    })
+                    .await;
+                (slept, tick, runs)
+            });
+
+        let (slept, tick, runs) = finish(&mut app, handle);
+        assert!(slept >= 6, "slept {slept} ticks");
+        assert_eq!(tick, runs);
+
+        let ticks = |app: &App| {
+            app.world()
+                .resource::<UpdateTicks>()
+                .ticks
+                .load(Ordering::SeqCst)
+        };
+        let before = ticks(&app);
+        app.update();
+        assert_eq!(ticks(&app) - before, 3);
+        assert_eq!(ticks(&app), app.world().resource::<FixedRuns>().0);
     }
 }
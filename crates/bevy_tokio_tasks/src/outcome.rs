@@ -0,0 +1,299 @@
+use std::fmt::Display;
+
+use bevy::prelude::*;
+
+use crate::{TaskId, TaskRegistry, TaskStatus, TokioTasksRuntime};
+
+/// Implemented for task outputs which can report a failure. `()` never fails, while a [`Result`] fails
+/// when it holds an `Err`.
+pub trait TaskResult {
+    /// Returns the [`TaskStatus`] a task completing with this output should be recorded with.
+    fn status(&self) -> TaskStatus;
+}
+
+impl TaskResult for () {
+    fn status(&self) -> TaskStatus {
+        TaskStatus::Finished
+    }
+}
+
+impl<T, E: Display> TaskResult for Result<T, E> {
+    fn status(&self) -> TaskStatus {
+        match self {
+            Ok(_) => TaskStatus::Finished,
+            Err(err) => TaskStatus::Failed(err.to_string()),
+        }
+    }
+}
+
+/// Decides what happens when a background task fails or panics. The plugin inserts the value
+/// configured in [`failure_policy`](crate::TokioTasksPlugin::failure_policy) as a resource, and
+/// individual tasks can override it through [`TaskBuilder::on_failure`](crate::TaskBuilder::on_failure).
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TaskFailurePolicy {
+    /// Log the failure and do nothing else.
+    Log,
+    /// Log the failure and write a [`TaskFailed`] message.
+    #[default]
+    Emit,
+    /// Log the failure, write a [`TaskFailed`] message and start the task again, up to
+    /// `max_restarts` times. Only tasks spawned through
+    /// [`spawn_restartable`](crate::TaskBuilder::spawn_restartable) can be restarted, other tasks
+    /// are handled as if the policy was [`Emit`](Self::Emit).
+    Restart {
+        /// The maximum number of times the task is restarted before the failure is only emitted.
+        max_restarts: u32,
+    },
+    /// Log the failure, write a [`TaskFailed`] message and request the app to exit with an error.
+    ExitApp,
+}
+
+/// Written when a background task runs to completion successfully.
+#[derive(Message, Clone, Debug)]
+pub struct TaskFinished {
+    /// The id of the finished task.
+    pub id: TaskId,
+    /// The name of the finished task, if it was given one.
+    pub name: Option<String>,
+}
+
+/// The reason a background task failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskFailure {
+    /// The task completed with an `Err` output. Holds the error's [`Display`] output.
+    Error(String),
+    /// The task panicked. Holds the panic payload message.
+    Panic(String),
+}
+
+impl Display for TaskFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskFailure::Error(err) => write!(f, "returned an error: {err}"),
+            TaskFailure::Panic(message) => write!(f, "panicked: {message}"),
+        }
+    }
+}
+
+/// Written when a background task fails or panics and its [`TaskFailurePolicy`] is not
+/// [`Log`](TaskFailurePolicy::Log).
+#[derive(Message, Clone, Debug)]
+pub struct TaskFailed {
+    /// The id of the failed task.
+    pub id: TaskId,
+    /// The name of the failed task, if it was given one.
+    pub name: Option<String>,
+    /// Why the task failed.
+    pub failure: TaskFailure,
+    /// Whether the task is being restarted because of this failure.
+    pub restarting: bool,
+}
+
+/// A final status reported by a tracked task.
+pub(crate) struct TaskOutcome {
+    pub(crate) id: TaskId,
+    pub(crate) name: Option<String>,
+    pub(crate) status: TaskStatus,
+}
+
+/// The receiving end of the outcomes reported by tracked tasks.
+#[derive(Resource)]
+pub(crate) struct TaskOutcomes {
+    pub(crate) outcome_rx: tokio::sync::mpsc::UnboundedReceiver<TaskOutcome>,
+}
+
+/// The Bevy system which turns the outcomes of completed background tasks into [`TaskFinished`] and
+/// [`TaskFailed`] messages, applying each task's [`TaskFailurePolicy`].
+pub(crate) fn handle_task_outcomes(
+    mut outcomes: ResMut<TaskOutcomes>,
+    registry: Res<TaskRegistry>,
+    runtime: Res<TokioTasksRuntime>,
+    default_policy: Res<TaskFailurePolicy>,
+    mut finished: MessageWriter<TaskFinished>,
+    mut failed: MessageWriter<TaskFailed>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    while let Ok(TaskOutcome { id, name, status }) = outcomes.outcome_rx.try_recv() {
+        let failure = match status {
+            TaskStatus::Finished => {
+                finished.write(TaskFinished { id, name });
+                registry.outcome_handled(id);
+                continue;
+            }
+            TaskStatus::Failed(err) => TaskFailure::Error(err),
+            TaskStatus::Panicked(message) => TaskFailure::Panic(message),
+            TaskStatus::Running | TaskStatus::Cancelled => {
+                registry.outcome_handled(id);
+                continue;
+            }
+        };
+
+        let Some((policy, restart, restarts)) = registry.supervision(id) else {
+            continue;
+        };
+        let policy = policy.unwrap_or_else(|| default_policy.clone());
+        let label = name.as_deref().unwrap_or("unnamed");
+        log::error!("Background task {id} ({label}) {failure}");
+
+        let restart = match (&policy, restart) {
+            (TaskFailurePolicy::Restart { max_restarts }, Some(restart))
+                if restarts < *max_restarts =>
+            {
+                Some(restart)
+            }
+            (TaskFailurePolicy::Restart { max_restarts }, Some(_)) => {
+                log::warn!(
+                    "Background task {id} ({label}) reached its limit of {max_restarts} restarts"
+                );
+                None
+            }
+            _ => None,
+        };
+
+        if policy != TaskFailurePolicy::Log {
+            failed.write(TaskFailed {
+                id,
+                name,
+                failure,
+                restarting: restart.is_some(),
+            });
+        }
+        if let Some(restart) = restart {
+            restart(&runtime, id);
+        }
+        registry.outcome_handled(id);
+        if policy == TaskFailurePolicy::ExitApp {
+            exit.write(AppExit::error());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TaskHandle, TokioTasksPlugin};
+    use std::time::Duration;
+
+    fn app(failure_policy: TaskFailurePolicy) -> App {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin {
+            failure_policy,
+            ..Default::default()
+        });
+        app
+    }
+
+    fn runtime(app: &App) -> &TokioTasksRuntime {
+        app.world().resource::<TokioTasksRuntime>()
+    }
+
+    async fn failing(_: crate::TaskContext) -> Result<(), String> {
+        Err("sensor offline".to_string())
+    }
+
+    /// Update the app once `handle` stopped, returning the failures reported in that update
+    fn update_after<Output>(app: &mut App, handle: &TaskHandle<Output>) -> Vec<TaskFailed> {
+        while !handle.is_finished() {
+            std::thread::yield_now();
+        }
+        app.update();
+        failures(app)
+    }
+
+    fn failures(app: &App) -> Vec<TaskFailed> {
+        app.world()
+            .resource::<Messages<TaskFailed>>()
+            .iter_current_update_messages()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn restart_stops_after_max_restarts() {
+        let mut app = app(TaskFailurePolicy::Restart { max_restarts: 2 });
+        let id = runtime(&app).task().spawn_restartable(failing);
+
+        let mut restarting = Vec::new();
+        for _ in 0..1000 {
+            app.update();
+            restarting.extend(failures(&app).iter().map(|failed| failed.restarting));
+            if restarting.len() == 3 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(restarting, [true, true, false]);
+        let registry = app.world().resource::<TaskRegistry>();
+        assert_eq!(registry.get(id).unwrap().restarts, 2);
+        assert!(!registry.get(id).unwrap().is_running());
+    }
+
+    #[test]
+    fn log_writes_no_failure() {
+        let mut app = app(TaskFailurePolicy::Log);
+        let handle = runtime(&app).task().spawn_fallible(failing);
+
+        assert!(update_after(&mut app, &handle).is_empty());
+        assert_eq!(app.should_exit(), None);
+    }
+
+    #[test]
+    fn exit_app_requests_an_error_exit() {
+        let mut app = app(TaskFailurePolicy::Emit);
+        let handle = runtime(&app)
+            .task()
+            .on_failure(TaskFailurePolicy::ExitApp)
+            .spawn_fallible(failing);
+
+        let failed = update_after(&mut app, &handle);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(
+            failed[0].failure,
+            TaskFailure::Error("sensor offline".to_string())
+        );
+        assert_eq!(app.should_exit(), Some(AppExit::error()));
+    }
+
+    #[test]
+    fn restart_of_non_restartable_task_emits() {
+        let mut app = app(TaskFailurePolicy::Restart { max_restarts: 2 });
+        let handle = runtime(&app).task().spawn_fallible(failing);
+
+        let failed = update_after(&mut app, &handle);
+        assert_eq!(failed.len(), 1);
+        assert!(!failed[0].restarting);
+        let registry = app.world().resource::<TaskRegistry>();
+        assert_eq!(registry.get(handle.id()).unwrap().restarts, 0);
+        assert_eq!(app.should_exit(), None);
+    }
+
+    /// Assert the failure reported once the task of `handle`, named `name`, panicked with `message`
+    fn assert_panicked(app: &mut App, handle: &TaskHandle<()>, name: &str, message: &str) {
+        let failed = update_after(app, handle);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, handle.id());
+        assert_eq!(failed[0].name.as_deref(), Some(name));
+        assert_eq!(failed[0].failure, TaskFailure::Panic(message.to_string()));
+        assert!(!failed[0].restarting);
+    }
+
+    #[test]
+    fn panics_are_reported_with_their_message() {
+        let mut app = app(TaskFailurePolicy::Emit);
+
+        // A `&'static str` payload.
+        let handle = runtime(&app)
+            .task()
+            .name("motor")
+            .spawn(|_| async { panic!("motor stalled") });
+        assert_panicked(&mut app, &handle, "motor", "motor stalled");
+
+        // A `String` payload.
+        let handle = runtime(&app)
+            .task()
+            .name("arm")
+            .spawn(|_| async { panic!("arm {} stalled", 2) });
+        assert_panicked(&mut app, &handle, "arm", "arm 2 stalled");
+    }
+}
@@ -0,0 +1,336 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use tokio::sync::mpsc;
+
+use crate::{MainThreadCallback, MainThreadClosed};
+
+/// Controls how callbacks requested through [`run_on_main_thread`](crate::TaskContext::run_on_main_thread)
+/// are queued and how many of them are executed per tick.
+#[derive(Clone, Debug, Default)]
+pub struct MainThreadQueueConfig {
+    /// The maximum number of callbacks waiting to be executed. When the queue is full,
+    /// [`run_on_main_thread`](crate::TaskContext::run_on_main_thread) waits for a free slot and
+    /// [`try_queue_on_main_thread`](crate::TaskContext::try_queue_on_main_thread) drops the callback.
+    /// `None`, the default, means the queue is unbounded.
+    pub capacity: Option<usize>,
+    /// The maximum number of callbacks executed per run of [`tick_runtime_update`](crate::tick_runtime_update).
+    /// Remaining callbacks are deferred to the next run. `None`, the default, means no limit.
+    pub max_callbacks_per_tick: Option<usize>,
+    /// The maximum wall time spent executing callbacks per run of
+    /// [`tick_runtime_update`](crate::tick_runtime_update). The callback which exceeds the budget still
+    /// runs to completion, the remaining callbacks are deferred to the next run. `None`, the default,
+    /// means no limit.
+    pub max_time_per_tick: Option<Duration>,
+}
+
+impl MainThreadQueueConfig {
+    /// A bounded queue holding at most `capacity` callbacks, without a per tick budget.
+    pub fn bounded(capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..Default::default()
+        }
+    }
+}
+
+/// The Bevy [`Resource`] holding metrics about the main thread callback queue. It is updated every
+/// time [`tick_runtime_update`](crate::tick_runtime_update) runs.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct MainThreadQueueStats {
+    /// The number of callbacks waiting in the queue after the last tick.
+    pub queue_depth: usize,
+    /// The number of callbacks executed during the last tick.
+    pub executed_last_tick: usize,
+    /// The number of callbacks left in the queue by the last tick because its budget was exhausted.
+    pub deferred_last_tick: usize,
+    /// The total number of callbacks executed since the app started.
+    pub total_executed: u64,
+    /// The total number of times callbacks were deferred because a tick's budget was exhausted.
+    pub total_deferred: u64,
+    /// The total number of callbacks dropped, either because the queue was full when calling
+    /// [`try_queue_on_main_thread`](crate::TaskContext::try_queue_on_main_thread) or because the app shut
+    /// down before they could run, including callbacks sent with
+    /// [`run_on_main_thread`](crate::TaskContext::run_on_main_thread) after the shutdown.
+    pub total_dropped: u64,
+}
+
+/// The error returned by [`TaskContext::try_queue_on_main_thread`](crate::TaskContext::try_queue_on_main_thread).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryQueueError {
+    /// The bounded queue is full. The callback was dropped.
+    Full,
+    /// The Bevy app has shut down. The callback was dropped.
+    Closed(MainThreadClosed),
+}
+
+impl std::fmt::Display for TryQueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryQueueError::Full => f.write_str("the main thread callback queue is full"),
+            TryQueueError::Closed(closed) => closed.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TryQueueError {}
+
+/// Counters shared between the queue's senders and the main thread.
+#[derive(Debug, Default)]
+pub(crate) struct QueueCounters {
+    dropped: AtomicU64,
+}
+
+impl QueueCounters {
+    pub(crate) fn add_dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+/// The sending half of the main thread callback queue held by every [`TaskContext`](crate::TaskContext).
+#[derive(Clone, Debug)]
+pub(crate) struct MainThreadSender {
+    tx: SenderKind,
+    counters: Arc<QueueCounters>,
+}
+
+#[derive(Clone, Debug)]
+enum SenderKind {
+    Unbounded(mpsc::UnboundedSender<MainThreadCallback>),
+    Bounded(mpsc::Sender<MainThreadCallback>),
+}
+
+impl MainThreadSender {
+    /// Queues a callback, waiting for a free slot if the queue is bounded and full.
+    pub(crate) async fn send(&self, callback: MainThreadCallback) -> Result<(), MainThreadClosed> {
+        let result = match &self.tx {
+            SenderKind::Unbounded(tx) => tx.send(callback).map_err(|_| MainThreadClosed),
+            SenderKind::Bounded(tx) => tx.send(callback).await.map_err(|_| MainThreadClosed),
+        };
+        if result.is_err() {
+            self.counters.add_dropped(1);
+        }
+        result
+    }
+
+    /// Queues a callback without waiting, dropping it if the queue is full.
+    pub(crate) fn try_send(&self, callback: MainThreadCallback) -> Result<(), TryQueueError> {
+        let result = match &self.tx {
+            SenderKind::Unbounded(tx) => tx
+                .send(callback)
+                .map_err(|_| TryQueueError::Closed(MainThreadClosed)),
+            SenderKind::Bounded(tx) => tx.try_send(callback).map_err(|err| match err {
+                mpsc::error::TrySendError::Full(_) => TryQueueError::Full,
+                mpsc::error::TrySendError::Closed(_) => TryQueueError::Closed(MainThreadClosed),
+            }),
+        };
+        if result.is_err() {
+            self.counters.add_dropped(1);
+        }
+        result
+    }
+}
+
+/// The receiving half of the main thread callback queue, owned by the
+/// [`TokioTasksRuntime`](crate::TokioTasksRuntime).
+pub(crate) struct MainThreadReceiver {
+    rx: ReceiverKind,
+    config: MainThreadQueueConfig,
+    counters: Arc<QueueCounters>,
+    stats: MainThreadQueueStats,
+    budget_exhausted: bool,
+}
+
+enum ReceiverKind {
+    Unbounded(mpsc::UnboundedReceiver<MainThreadCallback>),
+    Bounded(mpsc::Receiver<MainThreadCallback>),
+}
+
+impl ReceiverKind {
+    fn try_recv(&mut self) -> Option<MainThreadCallback> {
+        match self {
+            ReceiverKind::Unbounded(rx) => rx.try_recv().ok(),
+            ReceiverKind::Bounded(rx) => rx.try_recv().ok(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            ReceiverKind::Unbounded(rx) => rx.len(),
+            ReceiverKind::Bounded(rx) => rx.len(),
+        }
+    }
+}
+
+pub(crate) fn channel(config: MainThreadQueueConfig) -> (MainThreadSender, MainThreadReceiver) {
+    let counters = Arc::new(QueueCounters::default());
+    let (tx, rx) = match config.capacity {
+        Some(capacity) => {
+            let (tx, rx) = mpsc::channel(capacity.max(1));
+            (SenderKind::Bounded(tx), ReceiverKind::Bounded(rx))
+        }
+        None => {
+            let (tx, rx) = mpsc::unbounded_channel();
+            (SenderKind::Unbounded(tx), ReceiverKind::Unbounded(rx))
+        }
+    };
+    (
+        MainThreadSender {
+            tx,
+            counters: counters.clone(),
+        },
+        MainThreadReceiver {
+            rx,
+            config,
+            counters,
+            stats: MainThreadQueueStats::default(),
+            budget_exhausted: false,
+        },
+    )
+}
+
+impl MainThreadReceiver {
+    /// Pops the next callback if the tick's budget allows it. `executed` is the number of callbacks
+    /// already executed during this tick, and `elapsed` the time spent executing them.
+    pub(crate) fn next(
+        &mut self,
+        executed: usize,
+        elapsed: Duration,
+    ) -> Option<MainThreadCallback> {
+        let over_count = self
+            .config
+            .max_callbacks_per_tick
+            .is_some_and(|max| executed >= max);
+        let over_time = self
+            .config
+            .max_time_per_tick
+            .is_some_and(|max| elapsed >= max);
+        if over_count || over_time {
+            self.budget_exhausted = true;
+            return None;
+        }
+        self.rx.try_recv()
+    }
+
+    /// Records the metrics of a finished tick and returns the updated stats.
+    pub(crate) fn finish_tick(&mut self, executed: usize) -> &MainThreadQueueStats {
+        let depth = self.rx.len();
+        let deferred = if std::mem::take(&mut self.budget_exhausted) {
+            depth
+        } else {
+            0
+        };
+        let stats = &mut self.stats;
+        stats.queue_depth = depth;
+        stats.executed_last_tick = executed;
+        stats.deferred_last_tick = deferred;
+        stats.total_executed += executed as u64;
+        stats.total_deferred += deferred as u64;
+        stats.total_dropped = self.counters.dropped.load(Ordering::Relaxed);
+        stats
+    }
+
+    /// Closes the queue and drops every pending callback, which makes the tasks waiting on them
+    /// observe [`MainThreadClosed`].
+    pub(crate) fn close(&mut self) {
+        match &mut self.rx {
+            ReceiverKind::Unbounded(rx) => rx.close(),
+            ReceiverKind::Bounded(rx) => rx.close(),
+        }
+        let mut dropped = 0;
+        while self.rx.try_recv().is_some() {
+            dropped += 1;
+        }
+        self.counters.add_dropped(dropped);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::runtime::Runtime;
+
+    fn callback() -> MainThreadCallback {
+        Box::new(|_| {})
+    }
+
+    fn drain(rx: &mut MainThreadReceiver) -> usize {
+        let mut executed = 0;
+        while rx.next(executed, Duration::ZERO).is_some() {
+            executed += 1;
+        }
+        executed
+    }
+
+    #[test]
+    fn callbacks_beyond_the_count_budget_are_deferred() {
+        let (tx, mut rx) = channel(MainThreadQueueConfig {
+            max_callbacks_per_tick: Some(2),
+            ..Default::default()
+        });
+        for _ in 0..5 {
+            tx.try_send(callback()).unwrap();
+        }
+
+        assert_eq!(drain(&mut rx), 2);
+        let stats = rx.finish_tick(2);
+        assert_eq!(stats.queue_depth, 3);
+        assert_eq!(stats.executed_last_tick, 2);
+        assert_eq!(stats.deferred_last_tick, 3);
+
+        assert_eq!(drain(&mut rx), 2);
+        assert_eq!(rx.finish_tick(2).deferred_last_tick, 1);
+
+        assert_eq!(drain(&mut rx), 1);
+        let stats = rx.finish_tick(1);
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.deferred_last_tick, 0);
+        assert_eq!(stats.total_executed, 5);
+        assert_eq!(stats.total_deferred, 4);
+    }
+
+    #[test]
+    fn callbacks_beyond_the_time_budget_are_deferred() {
+        let budget = Duration::from_millis(5);
+        let (tx, mut rx) = channel(MainThreadQueueConfig {
+            max_time_per_tick: Some(budget),
+            ..Default::default()
+        });
+        tx.try_send(callback()).unwrap();
+        tx.try_send(callback()).unwrap();
+
+        assert!(rx.next(0, Duration::ZERO).is_some());
+        assert!(rx.next(1, budget).is_none());
+        assert_eq!(rx.finish_tick(1).deferred_last_tick, 1);
+    }
+
+    #[test]
+    fn full_bounded_queues_drop_queued_callbacks() {
+        let (tx, mut rx) = channel(MainThreadQueueConfig::bounded(2));
+        tx.try_send(callback()).unwrap();
+        tx.try_send(callback()).unwrap();
+
+        assert_eq!(tx.try_send(callback()), Err(TryQueueError::Full));
+        let stats = rx.finish_tick(0);
+        assert_eq!(stats.queue_depth, 2);
+        assert_eq!(stats.total_dropped, 1);
+    }
+
+    #[test]
+    fn closing_drops_pending_and_later_callbacks() {
+        let runtime = Runtime::new().unwrap();
+        let (tx, mut rx) = channel(MainThreadQueueConfig::default());
+        tx.try_send(callback()).unwrap();
+        tx.try_send(callback()).unwrap();
+
+        rx.close();
+        assert_eq!(
+            tx.try_send(callback()),
+            Err(TryQueueError::Closed(MainThreadClosed))
+        );
+        assert_eq!(runtime.block_on(tx.send(callback())), Err(MainThreadClosed));
+        assert_eq!(rx.finish_tick(0).total_dropped, 4);
+    }
+}
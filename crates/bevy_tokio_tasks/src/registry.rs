@@ -0,0 +1,776 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use bevy::ecs::entity::Entities;
+use bevy::prelude::*;
+
+use tokio::task::{AbortHandle, JoinError, JoinHandle};
+
+use crate::outcome::{TaskFailurePolicy, TaskOutcome, TaskOutcomes, TaskResult};
+
+/// A unique identifier assigned to every task spawned onto the [`TokioTasksRuntime`](crate::TokioTasksRuntime).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    /// Returns the raw numeric value of this id. Ids are assigned in spawn order starting at 1.
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for TaskId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "task#{}", self.0)
+    }
+}
+
+/// The lifecycle state of a task tracked by the [`TaskRegistry`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// The task has been spawned and has not completed yet.
+    Running,
+    /// The task's future ran to completion.
+    Finished,
+    /// The task's future completed with an error. Holds the error's [`Display`](std::fmt::Display)
+    /// output. Only tasks spawned through [`TaskBuilder::spawn_fallible`] or
+    /// [`TaskBuilder::spawn_restartable`] can fail this way.
+    Failed(String),
+    /// The task's future panicked. Holds the panic payload message, if it was a string.
+    Panicked(String),
+    /// The task was aborted before it could complete, either explicitly or because the
+    /// entity it was bound to was despawned.
+    Cancelled,
+}
+
+/// A snapshot of the bookkeeping the [`TaskRegistry`] keeps for a single task.
+#[derive(Clone, Debug)]
+pub struct TaskInfo {
+    /// The id assigned to the task when it was spawned.
+    pub id: TaskId,
+    /// The optional human readable name given to the task through [`TaskBuilder::name`].
+    pub name: Option<String>,
+    /// The update tick at which the task was spawned.
+    pub spawn_tick: usize,
+    /// The current status of the task.
+    pub status: TaskStatus,
+    /// How many times the task has been restarted by the [`TaskFailurePolicy::Restart`] policy.
+    pub restarts: u32,
+    /// The entity the task is bound to, if any. Bound tasks are aborted when the entity is despawned.
+    pub entity: Option<Entity>,
+    abort_handle: AbortHandle,
+}
+
+impl TaskInfo {
+    /// Returns true while the task has not finished, failed, panicked or been cancelled.
+    pub fn is_running(&self) -> bool {
+        self.status == TaskStatus::Running
+    }
+
+    /// Returns the handle which can be used to abort the task.
+    pub fn abort_handle(&self) -> &AbortHandle {
+        &self.abort_handle
+    }
+
+    /// Requests cancellation of the task. The status is updated to [`TaskStatus::Cancelled`] once the
+    /// runtime has dropped the task's future.
+    pub fn cancel(&self) {
+        self.abort_handle.abort();
+    }
+}
+
+struct TaskEntry {
+    info: TaskInfo,
+    policy: Option<TaskFailurePolicy>,
+    restart: Option<RestartFn>,
+    /// Outcomes sent for this task which `handle_task_outcomes` has not handled yet. The entry is
+    /// not evicted before they are, as handling them needs its policy and restart callback.
+    pending_outcomes: u32,
+}
+
+impl TaskEntry {
+    fn is_evictable(&self) -> bool {
+        !self.info.is_running() && self.pending_outcomes == 0
+    }
+}
+
+/// Respawns a restartable task under its existing id.
+pub(crate) type RestartFn = Arc<dyn Fn(&crate::TokioTasksRuntime, TaskId) + Send + Sync + 'static>;
+
+struct RegistryState {
+    next_id: u64,
+    tasks: BTreeMap<TaskId, TaskEntry>,
+    /// The running tasks bound to each entity, so despawned entities are found without walking every
+    /// task.
+    by_entity: HashMap<Entity, Vec<TaskId>>,
+    /// Tasks which stopped running and whose outcomes were handled, oldest first. The oldest are
+    /// evicted beyond `finished_capacity`.
+    finished: VecDeque<TaskId>,
+    finished_capacity: usize,
+    /// Final statuses of tasks which stopped before `register` recorded them.
+    early: HashMap<TaskId, TaskStatus>,
+    outcome_tx: tokio::sync::mpsc::UnboundedSender<TaskOutcome>,
+}
+
+impl RegistryState {
+    /// Records the final status of a recorded task and sends its outcome.
+    fn stop(&mut self, id: TaskId, status: TaskStatus) {
+        let Some(entry) = self.tasks.get_mut(&id) else {
+            return;
+        };
+        entry.info.status = status.clone();
+        entry.pending_outcomes += 1;
+        // The receiver only goes away when the app is torn down, in which case nobody is
+        // interested in the outcome anymore.
+        let _ = self.outcome_tx.send(TaskOutcome {
+            id,
+            name: entry.info.name.clone(),
+            status,
+        });
+        if let Some(entity) = entry.info.entity {
+            self.unbind(entity, id);
+        }
+    }
+
+    /// Evicts the oldest stopped tasks beyond the capacity.
+    fn evict(&mut self) {
+        while self.finished.len() > self.finished_capacity {
+            let Some(evicted) = self.finished.pop_front() else {
+                break;
+            };
+            // A restarted task is running again, or has an outcome to handle, and stays. It is queued
+            // again once that outcome was handled.
+            if self
+                .tasks
+                .get(&evicted)
+                .is_some_and(TaskEntry::is_evictable)
+            {
+                self.tasks.remove(&evicted);
+            }
+        }
+    }
+
+    fn bind(&mut self, entity: Entity, id: TaskId) {
+        self.by_entity.entry(entity).or_default().push(id);
+    }
+
+    fn unbind(&mut self, entity: Entity, id: TaskId) {
+        if let Some(ids) = self.by_entity.get_mut(&entity) {
+            ids.retain(|bound| *bound != id);
+            if ids.is_empty() {
+                self.by_entity.remove(&entity);
+            }
+        }
+    }
+}
+
+/// The Bevy [`Resource`] which tracks every task spawned onto the
+/// [`TokioTasksRuntime`](crate::TokioTasksRuntime). Systems can use it to list, inspect and cancel
+/// background tasks. The registry is shared with the runtime, so cloning it is cheap and all clones
+/// observe the same tasks.
+///
+/// Tasks which stopped running are kept so their status can be inspected, up to
+/// [`finished_task_capacity`](crate::TokioTasksPlugin::finished_task_capacity) of them; beyond that
+/// the oldest ones are evicted.
+#[derive(Resource, Clone)]
+pub struct TaskRegistry {
+    state: Arc<Mutex<RegistryState>>,
+    /// Notified whenever a task stops running.
+    stopped: Arc<tokio::sync::Notify>,
+}
+
+impl TaskRegistry {
+    pub(crate) fn new(finished_capacity: usize) -> (Self, TaskOutcomes) {
+        let (outcome_tx, outcome_rx) = tokio::sync::mpsc::unbounded_channel();
+        let registry = Self {
+            state: Arc::new(Mutex::new(RegistryState {
+                next_id: 0,
+                tasks: BTreeMap::new(),
+                by_entity: HashMap::new(),
+                finished: VecDeque::new(),
+                finished_capacity,
+                early: HashMap::new(),
+                outcome_tx,
+            })),
+            stopped: Arc::new(tokio::sync::Notify::new()),
+        };
+        (registry, TaskOutcomes { outcome_rx })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RegistryState> {
+        // A panic while holding the lock cannot leave the map in an inconsistent state, so a poisoned
+        // lock is still safe to use.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns a snapshot of every task known to the registry, ordered by [`TaskId`].
+    pub fn tasks(&self) -> Vec<TaskInfo> {
+        self.lock()
+            .tasks
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect()
+    }
+
+    /// Returns a snapshot of the tasks which are still running, ordered by [`TaskId`].
+    pub fn running(&self) -> Vec<TaskInfo> {
+        self.lock()
+            .tasks
+            .values()
+            .map(|entry| &entry.info)
+            .filter(|info| info.is_running())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns a snapshot of the task with the given id.
+    pub fn get(&self, id: TaskId) -> Option<TaskInfo> {
+        self.lock().tasks.get(&id).map(|entry| entry.info.clone())
+    }
+
+    /// Returns a snapshot of every task spawned with the given name.
+    pub fn find_by_name(&self, name: &str) -> Vec<TaskInfo> {
+        self.lock()
+            .tasks
+            .values()
+            .map(|entry| &entry.info)
+            .filter(|info| info.name.as_deref() == Some(name))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the status of the task with the given id.
+    pub fn status(&self, id: TaskId) -> Option<TaskStatus> {
+        self.lock()
+            .tasks
+            .get(&id)
+            .map(|entry| entry.info.status.clone())
+    }
+
+    /// Aborts the task with the given id. Returns false if no such task is running.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        match self.lock().tasks.get(&id) {
+            Some(entry) if entry.info.is_running() => {
+                entry.info.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Aborts every running task with the given name, returning how many tasks were cancelled.
+    pub fn cancel_by_name(&self, name: &str) -> usize {
+        let state = self.lock();
+        let mut cancelled = 0;
+        for info in state.tasks.values().map(|entry| &entry.info) {
+            if info.is_running() && info.name.as_deref() == Some(name) {
+                info.cancel();
+                cancelled += 1;
+            }
+        }
+        cancelled
+    }
+
+    /// Removes every task which is no longer running from the registry, instead of waiting for them
+    /// to be evicted. Tasks whose outcome was not handled yet are kept until it is.
+    pub fn clear_finished(&self) {
+        let mut state = self.lock();
+        state.tasks.retain(|_, entry| !entry.is_evictable());
+        state.finished.clear();
+    }
+
+    /// Returns the number of tasks known to the registry.
+    pub fn len(&self) -> usize {
+        self.lock().tasks.len()
+    }
+
+    /// Returns true if the registry does not know about any task.
+    pub fn is_empty(&self) -> bool {
+        self.lock().tasks.is_empty()
+    }
+
+    /// Allocates an id for a new task, spawns it through `spawn` and records it.
+    ///
+    /// The lock is released while spawning, as a task completing or dropped right away records its
+    /// status, which is kept in `early` until the task is recorded.
+    pub(crate) fn register<Output>(
+        &self,
+        options: TaskOptions,
+        spawn_tick: usize,
+        spawn: impl FnOnce(TaskId) -> JoinHandle<Output>,
+    ) -> (TaskId, JoinHandle<Output>) {
+        let id = {
+            let mut state = self.lock();
+            state.next_id += 1;
+            TaskId(state.next_id)
+        };
+        let join_handle = spawn(id);
+        let entity = options.entity;
+        let mut state = self.lock();
+        state.tasks.insert(
+            id,
+            TaskEntry {
+                info: TaskInfo {
+                    id,
+                    name: options.name,
+                    spawn_tick,
+                    status: TaskStatus::Running,
+                    entity: options.entity,
+                    restarts: 0,
+                    abort_handle: join_handle.abort_handle(),
+                },
+                policy: options.policy,
+                restart: options.restart,
+                pending_outcomes: 0,
+            },
+        );
+        match state.early.remove(&id) {
+            Some(status) => state.stop(id, status),
+            None => {
+                if let Some(entity) = entity {
+                    state.bind(entity, id);
+                }
+            }
+        }
+        (id, join_handle)
+    }
+
+    /// Spawns a new instance of an existing task through `spawn`, keeping its id and metadata.
+    ///
+    /// The task is marked running before it is spawned, without holding the lock, so a new instance
+    /// stopping right away is recorded.
+    pub(crate) fn respawn<Output>(
+        &self,
+        id: TaskId,
+        spawn_tick: usize,
+        spawn: impl FnOnce(TaskId) -> JoinHandle<Output>,
+    ) {
+        {
+            let mut state = self.lock();
+            let Some(entry) = state.tasks.get_mut(&id) else {
+                return;
+            };
+            entry.info.status = TaskStatus::Running;
+            entry.info.spawn_tick = spawn_tick;
+            entry.info.restarts += 1;
+            if let Some(entity) = entry.info.entity {
+                state.bind(entity, id);
+            }
+        }
+        let join_handle = spawn(id);
+        if let Some(entry) = self.lock().tasks.get_mut(&id) {
+            entry.info.abort_handle = join_handle.abort_handle();
+        }
+    }
+
+    /// Returns the failure policy override and restart callback of a task.
+    pub(crate) fn supervision(
+        &self,
+        id: TaskId,
+    ) -> Option<(Option<TaskFailurePolicy>, Option<RestartFn>, u32)> {
+        self.lock().tasks.get(&id).map(|entry| {
+            (
+                entry.policy.clone(),
+                entry.restart.clone(),
+                entry.info.restarts,
+            )
+        })
+    }
+
+    /// Marks one outcome of a task as handled, making the task evictable once it stopped and has no
+    /// other outcome to handle.
+    pub(crate) fn outcome_handled(&self, id: TaskId) {
+        let mut state = self.lock();
+        let Some(entry) = state.tasks.get_mut(&id) else {
+            return;
+        };
+        entry.pending_outcomes = entry.pending_outcomes.saturating_sub(1);
+        if entry.is_evictable() {
+            state.finished.push_back(id);
+            state.evict();
+        }
+    }
+
+    /// Records the final status of a task.
+    fn set_status(&self, id: TaskId, status: TaskStatus) {
+        let mut state = self.lock();
+        if state.tasks.contains_key(&id) {
+            state.stop(id, status);
+        } else {
+            state.early.insert(id, status);
+        }
+        drop(state);
+        self.stopped.notify_waiters();
+    }
+
+    /// Waits until no task tracked by this registry is running anymore.
+    pub(crate) async fn wait_idle(&self) {
+        loop {
+            let stopped = self.stopped.notified();
+            tokio::pin!(stopped);
+            // Register interest before checking, so a task stopping in between is not missed.
+            stopped.as_mut().enable();
+            let running = self
+                .lock()
+                .tasks
+                .values()
+                .any(|entry| entry.info.is_running());
+            if !running {
+                return;
+            }
+            stopped.await;
+        }
+    }
+
+    /// Wraps a task's future so that its final status is recorded in this registry. The `classify`
+    /// callback decides whether the task's output counts as a success or a failure.
+    pub(crate) fn track<Task>(
+        &self,
+        id: TaskId,
+        task: Task,
+        classify: impl FnOnce(&Task::Output) -> TaskStatus + Send + 'static,
+    ) -> impl Future<Output = Task::Output>
+    where
+        Task: Future,
+    {
+        let guard = StatusGuard {
+            registry: self.clone(),
+            id,
+            completed: false,
+        };
+        async move {
+            let mut guard = guard;
+            match CatchUnwind(Box::pin(task)).await {
+                Ok(output) => {
+                    guard.complete(classify(&output));
+                    output
+                }
+                Err(payload) => {
+                    guard.complete(TaskStatus::Panicked(panic_message(payload.as_ref())));
+                    // Let Tokio observe the panic too, so the JoinHandle still reports it.
+                    std::panic::resume_unwind(payload)
+                }
+            }
+        }
+    }
+}
+
+/// Marks a task as cancelled if its future is dropped before it completed.
+struct StatusGuard {
+    registry: TaskRegistry,
+    id: TaskId,
+    completed: bool,
+}
+
+impl StatusGuard {
+    fn complete(&mut self, status: TaskStatus) {
+        self.completed = true;
+        self.registry.set_status(self.id, status);
+    }
+}
+
+impl Drop for StatusGuard {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.registry.set_status(self.id, TaskStatus::Cancelled);
+        }
+    }
+}
+
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.0.as_mut();
+        match catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+/// Extracts a readable message from a panic payload, as recorded in [`TaskStatus::Panicked`]. Use it
+/// to report panics caught elsewhere the same way.
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// The handle returned by [`TaskBuilder::spawn`]. It can be awaited like the underlying Tokio
+/// [`JoinHandle`], and additionally exposes the [`TaskId`] under which the task was registered.
+#[derive(Debug)]
+pub struct TaskHandle<Output> {
+    id: TaskId,
+    join_handle: JoinHandle<Output>,
+}
+
+impl<Output> TaskHandle<Output> {
+    /// Returns the id under which the task is tracked in the [`TaskRegistry`].
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Aborts the task.
+    pub fn abort(&self) {
+        self.join_handle.abort();
+    }
+
+    /// Returns true if the task has completed, panicked or been cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.join_handle.is_finished()
+    }
+
+    /// Converts this handle into the underlying Tokio [`JoinHandle`].
+    pub fn into_join_handle(self) -> JoinHandle<Output> {
+        self.join_handle
+    }
+}
+
+impl<Output> Future for TaskHandle<Output> {
+    type Output = Result<Output, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.join_handle).poll(cx)
+    }
+}
+
+/// The metadata recorded for a task when it is spawned.
+#[derive(Default)]
+pub(crate) struct TaskOptions {
+    pub(crate) name: Option<String>,
+    pub(crate) entity: Option<Entity>,
+    pub(crate) policy: Option<TaskFailurePolicy>,
+    pub(crate) restart: Option<RestartFn>,
+}
+
+/// A builder for spawning a background task with extra metadata, created by
+/// [`TokioTasksRuntime::task`](crate::TokioTasksRuntime::task).
+pub struct TaskBuilder<'a> {
+    runtime: &'a crate::TokioTasksRuntime,
+    options: TaskOptions,
+}
+
+impl<'a> TaskBuilder<'a> {
+    pub(crate) fn new(runtime: &'a crate::TokioTasksRuntime) -> Self {
+        Self {
+            runtime,
+            options: TaskOptions::default(),
+        }
+    }
+
+    /// Gives the task a human readable name which is shown in the [`TaskRegistry`].
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.options.name = Some(name.into());
+        self
+    }
+
+    /// Binds the task to an entity. The task is aborted automatically when the entity is despawned.
+    pub fn bind_to(mut self, entity: Entity) -> Self {
+        self.options.entity = Some(entity);
+        self
+    }
+
+    /// Overrides the app-wide [`TaskFailurePolicy`] resource for this task.
+    pub fn on_failure(mut self, policy: TaskFailurePolicy) -> Self {
+        self.options.policy = Some(policy);
+        self
+    }
+
+    /// Spawns the task onto the background Tokio runtime. See
+    /// [`spawn_background_task`](crate::TokioTasksRuntime::spawn_background_task).
+    pub fn spawn<Task, Output, Spawnable>(self, spawnable_task: Spawnable) -> TaskHandle<Output>
+    where
+        Task: Future<Output = Output> + Send + 'static,
+        Output: Send + 'static,
+        Spawnable: FnOnce(crate::TaskContext) -> Task + Send + 'static,
+    {
+        let (id, join_handle) = self
+            .runtime
+            .spawn_tracked(self.options, spawnable_task, |_| TaskStatus::Finished);
+        TaskHandle { id, join_handle }
+    }
+
+    /// Spawns a task whose output is a [`Result`]. An `Err` output is recorded as
+    /// [`TaskStatus::Failed`] and handled according to the task's [`TaskFailurePolicy`].
+    pub fn spawn_fallible<Task, Output, Spawnable>(
+        self,
+        spawnable_task: Spawnable,
+    ) -> TaskHandle<Output>
+    where
+        Task: Future<Output = Output> + Send + 'static,
+        Output: TaskResult + Send + 'static,
+        Spawnable: FnOnce(crate::TaskContext) -> Task + Send + 'static,
+    {
+        let (id, join_handle) =
+            self.runtime
+                .spawn_tracked(self.options, spawnable_task, TaskResult::status);
+        TaskHandle { id, join_handle }
+    }
+
+    /// Spawns a task which can be started again by the [`TaskFailurePolicy::Restart`] policy when it
+    /// fails or panics. Because the task may run more than once, the spawn callback must be `Fn` and
+    /// the task's output is discarded.
+    pub fn spawn_restartable<Task, Output, Spawnable>(mut self, spawnable_task: Spawnable) -> TaskId
+    where
+        Task: Future<Output = Output> + Send + 'static,
+        Output: TaskResult + Send + 'static,
+        Spawnable: Fn(crate::TaskContext) -> Task + Send + Sync + 'static,
+    {
+        let spawnable_task = Arc::new(spawnable_task);
+        let respawnable = spawnable_task.clone();
+        self.options.restart = Some(Arc::new(move |runtime, id| {
+            let spawnable_task = respawnable.clone();
+            runtime.respawn_tracked(id, move |ctx| spawnable_task(ctx), TaskResult::status);
+        }));
+        let (id, _) = self.runtime.spawn_tracked(
+            self.options,
+            move |ctx| spawnable_task(ctx),
+            TaskResult::status,
+        );
+        id
+    }
+}
+
+/// The Bevy system which aborts running tasks whose bound entity no longer exists.
+pub fn abort_despawned_entity_tasks(registry: Res<TaskRegistry>, entities: &Entities) {
+    let state = registry.lock();
+    for (entity, ids) in &state.by_entity {
+        if entities.contains(*entity) {
+            continue;
+        }
+        for entry in ids.iter().filter_map(|id| state.tasks.get(id)) {
+            entry.info.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use tokio::runtime::Runtime;
+
+    fn spawn(
+        registry: &TaskRegistry,
+        runtime: &Runtime,
+        entity: Option<Entity>,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) -> (TaskId, JoinHandle<()>) {
+        let options = TaskOptions {
+            entity,
+            ..TaskOptions::default()
+        };
+        registry.register(options, 0, |id| {
+            runtime.spawn(registry.track(id, task, |_| TaskStatus::Finished))
+        })
+    }
+
+    #[test]
+    fn stopped_tasks_are_evicted_beyond_capacity() {
+        let runtime = Runtime::new().unwrap();
+        let (registry, mut outcomes) = TaskRegistry::new(2);
+        let (running, _) = spawn(&registry, &runtime, None, std::future::pending());
+        let finished: Vec<TaskId> = (0..4)
+            .map(|_| {
+                let (id, handle) = spawn(&registry, &runtime, None, async {});
+                runtime.block_on(handle).unwrap();
+                id
+            })
+            .collect();
+        // Nothing is evicted before the outcomes were handled.
+        assert_eq!(registry.len(), 5);
+        while let Ok(outcome) = outcomes.outcome_rx.try_recv() {
+            registry.outcome_handled(outcome.id);
+        }
+
+        let ids: Vec<TaskId> = registry.tasks().iter().map(|info| info.id).collect();
+        assert_eq!(ids, vec![running, finished[2], finished[3]]);
+        assert_eq!(registry.status(finished[3]), Some(TaskStatus::Finished));
+
+        registry.clear_finished();
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn failures_are_handled_before_eviction() {
+        let mut app = App::new();
+        app.add_plugins(crate::TokioTasksPlugin {
+            finished_task_capacity: 0,
+            failure_policy: TaskFailurePolicy::ExitApp,
+            ..Default::default()
+        });
+        let handle = app
+            .world()
+            .resource::<crate::TokioTasksRuntime>()
+            .task()
+            .spawn_fallible(|_| async { Err::<(), _>("sensor offline") });
+        while !handle.is_finished() {
+            std::thread::yield_now();
+        }
+
+        app.update();
+
+        let failed = app.world().resource::<Messages<crate::TaskFailed>>();
+        let failure = failed.iter_current_update_messages().next().unwrap();
+        assert_eq!(
+            failure.failure,
+            crate::TaskFailure::Error("sensor offline".to_string())
+        );
+        assert_eq!(app.should_exit(), Some(AppExit::error()));
+        assert!(app.world().resource::<TaskRegistry>().is_empty());
+    }
+
+    #[test]
+    fn task_completing_while_spawned_is_recorded() {
+        let runtime = Runtime::new().unwrap();
+        let (registry, mut outcomes) = TaskRegistry::new(16);
+        let (id, _) = registry.register(TaskOptions::default(), 0, |id| {
+            let handle = runtime.spawn(registry.track(id, async {}, |_| TaskStatus::Finished));
+            // The task records its status while the registry is still spawning it.
+            while !handle.is_finished() {
+                std::thread::yield_now();
+            }
+            handle
+        });
+
+        assert_eq!(registry.status(id), Some(TaskStatus::Finished));
+        let outcome = outcomes.outcome_rx.try_recv().unwrap();
+        assert_eq!((outcome.id, outcome.status), (id, TaskStatus::Finished));
+    }
+
+    #[test]
+    fn tasks_of_despawned_entities_are_aborted() {
+        let runtime = Runtime::new().unwrap();
+        let (registry, _outcomes) = TaskRegistry::new(16);
+        let mut world = World::new();
+        let despawned = world.spawn_empty().id();
+        let alive = world.spawn_empty().id();
+        let (aborted, aborted_handle) =
+            spawn(&registry, &runtime, Some(despawned), std::future::pending());
+        let (kept, _) = spawn(&registry, &runtime, Some(alive), std::future::pending());
+        world.despawn(despawned);
+        world.insert_resource(registry.clone());
+
+        world.run_system_once(abort_despawned_entity_tasks).unwrap();
+
+        assert!(runtime.block_on(aborted_handle).unwrap_err().is_cancelled());
+        assert_eq!(registry.status(aborted), Some(TaskStatus::Cancelled));
+        assert_eq!(registry.status(kept), Some(TaskStatus::Running));
+        let by_entity: Vec<Entity> = registry.lock().by_entity.keys().copied().collect();
+        assert_eq!(by_entity, vec![alive]);
+    }
+}
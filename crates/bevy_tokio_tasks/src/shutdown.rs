@@ -0,0 +1,220 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
+
+use crate::{TaskContext, TaskRegistry, TokioTasksRuntime};
+
+/// The Bevy system which signals the shutdown token handed to background tasks as soon as an
+/// [`AppExit`] message is written, giving tasks the rest of the frame and the
+/// [`shutdown_timeout`](crate::TokioTasksPlugin::shutdown_timeout) to wind down.
+pub fn signal_shutdown_on_app_exit(
+    mut exits: MessageReader<AppExit>,
+    runtime: Res<TokioTasksRuntime>,
+) {
+    if exits.read().next().is_some() {
+        runtime.shutdown_token().cancel();
+    }
+}
+
+/// Waits for SIGINT or SIGTERM and turns the first one into an [`AppExit`]. A second signal exits
+/// the process immediately, in case the app is stuck while shutting down.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn exit_on_signal(mut ctx: TaskContext) {
+    let shutdown = ctx.shutdown_token();
+    let signal = tokio::select! {
+        _ = shutdown.cancelled() => return,
+        result = wait_for_signal() => match result {
+            Ok(signal) => signal,
+            Err(err) => {
+                log::error!("Failed to listen for shutdown signals: {err}");
+                return;
+            }
+        }
+    };
+
+    log::info!("{} received, exiting app", signal.name());
+    if ctx
+        .try_run_on_main_thread(|ctx| {
+            ctx.world.write_message(AppExit::Success);
+        })
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    // Keep listening so that a second signal can force the exit, but stop once the runtime is
+    // draining so this task does not hold up the shutdown itself.
+    tokio::select! {
+        _ = shutdown.cancelled() => {}
+        Ok(signal) = wait_for_signal() => {
+            log::warn!("Second shutdown signal ({}) received, exiting immediately", signal.name());
+            std::process::exit(signal.exit_code());
+        }
+    }
+}
+
+/// The shutdown signals handled by [`exit_on_signal`]
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShutdownSignal {
+    Interrupt,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Terminate,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ShutdownSignal {
+    fn name(self) -> &'static str {
+        match self {
+            ShutdownSignal::Interrupt => "SIGINT",
+            ShutdownSignal::Terminate => "SIGTERM",
+        }
+    }
+
+    /// The exit code of a process killed by the signal, 128 plus the signal number
+    fn exit_code(self) -> i32 {
+        match self {
+            ShutdownSignal::Interrupt => 130,
+            ShutdownSignal::Terminate => 143,
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> std::io::Result<ShutdownSignal> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result.map(|()| ShutdownSignal::Interrupt),
+        _ = terminate.recv() => Ok(ShutdownSignal::Terminate),
+    }
+}
+
+#[cfg(all(not(unix), not(target_arch = "wasm32")))]
+async fn wait_for_signal() -> std::io::Result<ShutdownSignal> {
+    tokio::signal::ctrl_c()
+        .await
+        .map(|()| ShutdownSignal::Interrupt)
+}
+
+/// Signals shutdown to all background tasks and blocks until they have finished or `timeout` has
+/// elapsed. The deadline is measured on a separate thread so that this works even if the runtime was
+/// built without the Tokio time driver; the thread stops as soon as the tasks have drained.
+pub(crate) fn drain(
+    runtime: &Runtime,
+    registry: &TaskRegistry,
+    shutdown: &CancellationToken,
+    timeout: Duration,
+) {
+    shutdown.cancel();
+    if tokio::runtime::Handle::try_current().is_ok() {
+        // Blocking on the runtime from within an async context would panic.
+        log::warn!("TokioTasksRuntime dropped from within a Tokio runtime, not draining tasks");
+        return;
+    }
+
+    let deadline = CancellationToken::new();
+    let expired = deadline.clone();
+    let (drained_tx, drained_rx) = mpsc::channel::<()>();
+    let timer = std::thread::spawn(move || {
+        // Dropping the sender wakes the thread up before the timeout.
+        if drained_rx.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+            expired.cancel();
+        }
+    });
+    let drained = runtime.block_on(async {
+        tokio::select! {
+            _ = registry.wait_idle() => true,
+            _ = deadline.cancelled() => false,
+        }
+    });
+    drop(drained_tx);
+    let _ = timer.join();
+    if !drained {
+        let running = registry.running();
+        log::warn!(
+            "{} background task(s) still running after the {:?} shutdown timeout, aborting them: {:?}",
+            running.len(),
+            timeout,
+            running
+                .iter()
+                .map(|task| task.name.as_deref().unwrap_or("unnamed"))
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::TaskOptions;
+    use crate::{TaskStatus, TokioTasksPlugin};
+    use std::time::Instant;
+
+    /// Track a task which only finishes once `shutdown` is cancelled, or never if `ignore_shutdown`
+    fn spawn(
+        registry: &TaskRegistry,
+        runtime: &Runtime,
+        shutdown: &CancellationToken,
+        ignore_shutdown: bool,
+    ) {
+        let shutdown = shutdown.clone();
+        let task = async move {
+            if ignore_shutdown {
+                std::future::pending::<()>().await;
+            }
+            shutdown.cancelled().await;
+        };
+        registry.register(TaskOptions::default(), 0, |id| {
+            runtime.spawn(registry.track(id, task, |_| TaskStatus::Finished))
+        });
+    }
+
+    #[test]
+    fn app_exit_cancels_the_shutdown_token() {
+        let mut app = App::new();
+        app.add_plugins(TokioTasksPlugin::default());
+        let shutdown = app.world().resource::<TokioTasksRuntime>().shutdown_token();
+
+        app.update();
+        assert!(!shutdown.is_cancelled());
+
+        app.world_mut().write_message(AppExit::Success);
+        app.update();
+        assert!(shutdown.is_cancelled());
+    }
+
+    #[test]
+    fn drain_returns_once_tasks_finished() {
+        let runtime = Runtime::new().unwrap();
+        let (registry, _outcomes) = TaskRegistry::new(16);
+        let shutdown = CancellationToken::new();
+        spawn(&registry, &runtime, &shutdown, false);
+
+        let started = Instant::now();
+        drain(&runtime, &registry, &shutdown, Duration::from_secs(60));
+
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(registry.running().is_empty());
+    }
+
+    #[test]
+    fn drain_gives_up_after_the_timeout() {
+        let runtime = Runtime::new().unwrap();
+        let (registry, _outcomes) = TaskRegistry::new(16);
+        let shutdown = CancellationToken::new();
+        spawn(&registry, &runtime, &shutdown, true);
+
+        let timeout = Duration::from_millis(50);
+        let started = Instant::now();
+        drain(&runtime, &registry, &shutdown, timeout);
+
+        assert!(started.elapsed() >= timeout);
+        assert_eq!(registry.running().len(), 1);
+    }
+}
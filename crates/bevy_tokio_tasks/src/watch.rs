@@ -0,0 +1,147 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use tokio::sync::watch;
+
+use crate::{EcsError, TaskContext, TokioTasksSystems};
+
+/// Publishes every change of the resource `R` to async subscribers through a
+/// [`tokio::sync::watch`] channel, so that tasks can `await` changes instead of polling with
+/// [`run_on_main_thread`](TaskContext::run_on_main_thread).
+///
+/// Changes are detected with [`DetectChanges::is_changed`] once per frame, in the [`Last`] schedule,
+/// so subscribers see at most one value per frame: the latest one.
+///
+/// ```ignore
+/// app.add_plugins(ResourceWatchPlugin::<SensorData>::default());
+///
+/// runtime.spawn_background_task(|mut ctx| async move {
+///     let mut sensor_data = ctx.watch_resource::<SensorData>().await?;
+///     while sensor_data.changed().await.is_ok() {
+///         let latest = sensor_data.borrow_and_update().clone();
+///     }
+///     Ok::<_, EcsError>(())
+/// });
+/// ```
+pub struct ResourceWatchPlugin<R> {
+    _marker: PhantomData<fn() -> R>,
+}
+
+impl<R> Default for ResourceWatchPlugin<R> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R: Resource + Clone> Plugin for ResourceWatchPlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ResourceWatch::<R>::new());
+        app.add_systems(
+            Last,
+            publish_resource_changes::<R>.in_set(TokioTasksSystems::PublishChanges),
+        );
+    }
+}
+
+/// The Bevy [`Resource`] holding the sending half of the watch channel for the resource `R`, inserted
+/// by [`ResourceWatchPlugin`]. It is cheap to clone, so web handlers can keep a copy and call
+/// [`subscribe`](Self::subscribe) for every new connection.
+#[derive(Resource)]
+pub struct ResourceWatch<R> {
+    tx: Arc<watch::Sender<Option<R>>>,
+}
+
+impl<R> Clone for ResourceWatch<R> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<R: Resource + Clone> ResourceWatch<R> {
+    fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::Sender::new(None)),
+        }
+    }
+
+    /// Returns a new receiver of the resource's value. The value is `None` while the resource does not
+    /// exist. The current value is marked as seen, so `changed` waits for the next change.
+    pub fn subscribe(&self) -> watch::Receiver<Option<R>> {
+        self.tx.subscribe()
+    }
+
+    /// Returns a clone of the last published value of the resource.
+    pub fn latest(&self) -> Option<R> {
+        self.tx.borrow().clone()
+    }
+
+    /// Returns the number of live receivers.
+    pub fn subscriber_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+/// The Bevy system which publishes the resource `R` to its [`ResourceWatch`] whenever it changes,
+/// and `None` when it is removed.
+pub fn publish_resource_changes<R: Resource + Clone>(
+    resource: Option<Res<R>>,
+    watch: Res<ResourceWatch<R>>,
+) {
+    match resource {
+        Some(resource) if resource.is_changed() => {
+            watch.tx.send_replace(Some(resource.clone()));
+        }
+        Some(_) => {}
+        None => {
+            watch.tx.send_if_modified(|value| value.take().is_some());
+        }
+    }
+}
+
+impl TaskContext {
+    /// Subscribes to the changes of the resource `R`. Requires [`ResourceWatchPlugin<R>`] to be added,
+    /// otherwise [`EcsError::MissingResource`] is returned.
+    pub async fn watch_resource<R: Resource + Clone>(
+        &mut self,
+    ) -> Result<watch::Receiver<Option<R>>, EcsError> {
+        Ok(self.resource::<ResourceWatch<R>>().await?.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Clone, Debug, PartialEq)]
+    struct Gains(f32);
+
+    #[test]
+    fn changes_are_published_once() {
+        let mut app = App::new();
+        app.add_plugins(ResourceWatchPlugin::<Gains>::default());
+        app.insert_resource(Gains(1.0));
+        let mut gains = app.world().resource::<ResourceWatch<Gains>>().subscribe();
+
+        app.update();
+        assert!(gains.has_changed().unwrap());
+        assert_eq!(*gains.borrow_and_update(), Some(Gains(1.0)));
+
+        app.update();
+        assert!(!gains.has_changed().unwrap());
+
+        app.world_mut().resource_mut::<Gains>().0 = 2.0;
+        app.update();
+        assert!(gains.has_changed().unwrap());
+        assert_eq!(*gains.borrow_and_update(), Some(Gains(2.0)));
+
+        app.world_mut().remove_resource::<Gains>();
+        app.update();
+        assert!(gains.has_changed().unwrap());
+        assert_eq!(*gains.borrow_and_update(), None);
+    }
+}
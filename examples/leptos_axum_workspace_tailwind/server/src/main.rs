@@ -1,4 +1,4 @@
-use async_bevy_web::prelude::{ABWConfigPlugin, LeptosAppPlugin};
+use async_bevy_web::prelude::{ABWConfigPlugin, LeptosAppPlugin, LeptosServerConfig};
 use bevy::prelude::*;
 
 pub mod appserv;
@@ -6,14 +6,25 @@ pub mod appserv;
 use appserv::start_leptos_app;
 
 fn main () {
+    // Initialized once here, the Leptos app may be started several times
+    simple_logger::init_with_level(log::Level::Debug).expect("couldn't initialize logging");
+
     App::new()
         .add_systems(Startup, print_running)
         // Use lower frame rate for headless web server (reduces CPU usage and OS scheduler interaction)
         .add_plugins(ABWConfigPlugin::fixed(60.0))
-        .add_plugins(LeptosAppPlugin::new(start_leptos_app()))
+        .add_plugins(LeptosAppPlugin::new(start_leptos_app()).with_config(leptos_config()))
         .run();
 }
 
 fn print_running(){
     println!("Running!")
-}
\ No newline at end of file
+}
+
+// Read the [[workspace.metadata.leptos]] section, overridden by the LEPTOS_* variables set by cargo-leptos,
+// so the server also runs with `cargo run` from the workspace root. Ship the Cargo.toml with the
+// executable, or set the variables, when deploying:
+// <https://github.com/leptos-rs/start-axum#executing-a-server-on-a-remote-machine-without-the-toolchain>
+fn leptos_config() -> LeptosServerConfig {
+    LeptosServerConfig::from_file("Cargo.toml").expect("couldn't read the Leptos config of Cargo.toml")
+}
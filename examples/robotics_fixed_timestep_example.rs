@@ -93,13 +93,19 @@ fn spawn_sensor_reader(runtime: Res<TokioTasksRuntime>) {
             let velocity = simulate_velocity_reading(tick_count);
             
             // Send data to Bevy ECS (runs on main thread)
-            ctx.run_on_main_thread(move |ctx| {
+            let sent = ctx.try_run_on_main_thread(move |ctx| {
                 let mut sensor_data = ctx.world.resource_mut::<SensorData>();
                 sensor_data.position = position;
                 sensor_data.velocity = velocity;
                 sensor_data.timestamp = tick_count;
             }).await;
             
+            // The app is shutting down, stop reading sensors
+            if sent.is_err() {
+                println!("[Sensor Task] Main thread closed, stopping");
+                break;
+            }
+            
             // Every 100 ticks (1 second), print status
             if tick_count % 100 == 0 {
                 println!("[Sensor Task] Read {} samples", tick_count);
@@ -117,10 +123,12 @@ fn spawn_actuator_writer(runtime: Res<TokioTasksRuntime>) {
         loop {
             interval.tick().await;
             
-            // Read control output from Bevy ECS
-            let motor_command = ctx.run_on_main_thread(|ctx| {
+            // Read control output from Bevy ECS, stopping once the app shuts down
+            let Ok(motor_command) = ctx.try_run_on_main_thread(|ctx| {
                 ctx.world.resource::<ControlOutput>().motor_command
-            }).await;
+            }).await else {
+                break;
+            };
             
             // Send command to hardware (simulated)
             send_motor_command(motor_command).await;
@@ -255,7 +263,8 @@ ARCHITECTURE SUMMARY:
    - Tokio tasks handle hardware communication
    - Higher frequency than control loop (oversampling)
    - Non-blocking I/O doesn't affect control timing
-   - Data synchronized via run_on_main_thread()
+   - Data synchronized via try_run_on_main_thread()
+   - Tasks exit cleanly when the app shuts down (MainThreadClosed)
 
 3. SEPARATION OF CONCERNS:
    - FixedUpdate: Deterministic control logic
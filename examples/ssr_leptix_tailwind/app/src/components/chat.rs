@@ -1,13 +1,10 @@
-// use bevy_eventwork::NetworkMessage;
-
-// use bevy_eventwork::NetworkMessage;
-// use bevy_eventwork::NetworkPacket;
 use leptos::prelude::*;
 use leptos_use::core::ConnectionReadyState;
 use leptos_use::UseWebSocketReturn;
 use leptos_use::use_websocket;
 use crate::components::ui::button::Button;
 use codee::string::FromToStringCodec;
+use shared::messages::{decode, encode, NewChatMessage, UserChatMessage};
 
 #[component]
 pub fn WsMessages() -> impl IntoView {
@@ -18,10 +15,20 @@ pub fn WsMessages() -> impl IntoView {
     open,
     close,
     ..
-        } = use_websocket::<String, String, FromToStringCodec>("ws://127.0.0.1:8081");
+        } = use_websocket::<String, String, FromToStringCodec>("ws://127.0.0.1:3000/ws");
 
         let send_message = move |_| {
-            send(&"Hello, world!".to_string());
+            let message = UserChatMessage { message: "Hello, world!".to_string() };
+            if let Ok(frame) = encode(UserChatMessage::KIND, &message) {
+                send(&frame);
+            }
+        };
+
+        let received = move || {
+            message
+                .get()
+                .and_then(|text| decode::<NewChatMessage>(NewChatMessage::KIND, &text))
+                .map(|chat| format!("{}: {}", chat.name, chat.message))
         };
 
         let status = move || ready_state.get().to_string();
@@ -44,7 +51,7 @@ pub fn WsMessages() -> impl IntoView {
                     <Button on_click=open_connection disabled=connected>"Open"</Button>
                     <Button on_click=close_connection disabled=move || !connected()>"Close"</Button>
                 </div>
-                <p class="text-4xl text-blue-400">"Receive message: " {move || format!("{:?}", message.get())}</p>
+                <p class="text-4xl text-blue-400">"Receive message: " {move || received().unwrap_or_default()}</p>
             </div>
         }
 }
@@ -1,8 +1,8 @@
-use async_bevy_web::prelude::{ABWConfigPlugin, LeptosAppPlugin};
-use bevy::{prelude::*, tasks::{TaskPool, TaskPoolBuilder}};
-use eventwork::{ConnectionId, EventworkRuntime, Network, NetworkData, NetworkEvent};
-use eventwork_websockets::{WebSocketProvider, NetworkSettings};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use async_bevy_web::prelude::{
+    ABWConfigPlugin, LeptosAppPlugin, LeptosServerConfig, WebSocketPlugin, WebSockets,
+    WsConnectionEvent, WsReceived,
+};
+use bevy::prelude::*;
 
 use shared::messages::*;
 
@@ -13,25 +13,25 @@ pub mod messages;
 use appserv::start_leptos_app;
 
 fn main () {
+    // Initialized once here, the Leptos app may be started several times
+    simple_logger::init_with_level(log::Level::Debug).expect("couldn't initialize logging");
+
     let mut app = App::new();
 
     // Base ABW plugins
     app.add_systems(Startup, print_running)
        .add_plugins(ABWConfigPlugin::fixed(60.0))
-       .add_plugins(LeptosAppPlugin::new(start_leptos_app()));
+       .add_plugins(LeptosAppPlugin::new(start_leptos_app()).with_config(leptos_config()));
+
+    // Networking plugins, the endpoint is served by the Leptos app on ws://127.0.0.1:3000/ws
+    app.add_plugins(WebSocketPlugin::new("/ws"));
 
-    // Networking plugins    
-    app.add_plugins(eventwork::EventworkPlugin::<WebSocketProvider,bevy::tasks::TaskPool,>::default())
-       .insert_resource(EventworkRuntime(TaskPoolBuilder::new().num_threads(2).build(),))
-       .insert_resource(NetworkSettings::default());
-    
     // Register the messages where they are defined
     messages::server_register_network_messages(&mut app);
 
     // Networking systems
-    app.add_systems(Startup, setup_networking)
-        .add_systems(Update, (handle_connection_events, handle_messages));
-    
+    app.add_systems(Update, (handle_connection_events, handle_messages));
+
     // Start the app
     app.run();
 }
@@ -40,68 +40,40 @@ fn print_running(){
     println!("Running!")
 }
 
-// On the server side, you need to setup networking. You do not need to do so at startup, and can start listening
-// at any time.
-fn setup_networking(
-    mut net: ResMut<Network<WebSocketProvider>>,
-    settings: Res<NetworkSettings>,
-    task_pool: Res<EventworkRuntime<TaskPool>>,
-) {
-    let ip_address = "127.0.0.1".parse().expect("Could not parse ip address");
-
-    println!("Address of the server: {}:8080", ip_address);
-
-    let _socket_address = SocketAddr::new(ip_address, 8080);
-
-    match net.listen(
-        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
-        &task_pool.0,
-        &settings,
-    ) {
-        Ok(_) => (),
-        Err(err) => {
-            error!("Could not start listening: {}", err);
-            panic!();
-        }
-    }
-
-    println!("Started listening for new connections!");
-}
-
-
-#[derive(Component)]
-struct User(ConnectionId);
-
 fn handle_connection_events(
-    mut commands: Commands,
-    net: Res<Network<WebSocketProvider>>,
-    mut network_events: EventReader<NetworkEvent>,
+    mut connection_events: MessageReader<WsConnectionEvent>,
+    sockets: WebSockets,
 ){
-    for event in network_events.read(){
-        if let NetworkEvent::Connected(conn_id) = event {
-            commands.spawn((User(*conn_id),));
-
-        // Broadcasting sends the message to all connected users! (Including the just connected one in this case)
-        net.broadcast(NewChatMessage {
-            name: String::from("SERVER"),
-            message: format!("New user connected: {}", conn_id),
-        });
-        info!("New user connected: {}",conn_id);
+    for event in connection_events.read(){
+        if let WsConnectionEvent::Connected(user) = event {
+            // Broadcasting sends the message to all connected users! (Including the just connected one in this case)
+            let _ = sockets.broadcast(&NewChatMessage {
+                name: String::from("SERVER"),
+                message: format!("New user connected: {}", user),
+            });
+            log::info!("New user connected: {}", user);
         }
     }
 }
 
 fn handle_messages(
-    mut new_messages: EventReader<NetworkData<UserChatMessage>>,
-    net: Res<Network<WebSocketProvider>>,
+    mut new_messages: MessageReader<WsReceived<UserChatMessage>>,
+    sockets: WebSockets,
 ){
-    for message in new_messages.read(){
-        let user = message.source();
-        info!("Received message from user: {}", message.message);
+    for received in new_messages.read(){
+        log::info!("Received message from user: {}", received.message.message);
 
-        net.broadcast(NewChatMessage{
-            name: format!("{}",user),
-            message: message.message.clone(),
+        let _ = sockets.broadcast(&NewChatMessage{
+            name: format!("{}", received.from),
+            message: received.message.message.clone(),
         });
     }
-}
\ No newline at end of file
+}
+
+// Read the [[workspace.metadata.leptos]] section, overridden by the LEPTOS_* variables set by cargo-leptos,
+// so the server also runs with `cargo run` from the workspace root. Ship the Cargo.toml with the
+// executable, or set the variables, when deploying:
+// <https://github.com/leptos-rs/start-axum#executing-a-server-on-a-remote-machine-without-the-toolchain>
+fn leptos_config() -> LeptosServerConfig {
+    LeptosServerConfig::from_file("Cargo.toml").expect("couldn't read the Leptos config of Cargo.toml")
+}
@@ -1,53 +1,17 @@
+use async_bevy_web::prelude::AppWebSocketExt;
 use bevy::prelude::*;
-use eventwork_websockets::WebSocketProvider;
-use shared::messages::{NewChatMessage, UserChatMessage};
+use shared::messages::UserChatMessage;
 
 /////////////////////////////////////////////////////////////////////
 // In this example the client sends `UserChatMessage`s to the server,
-// the server then broadcasts to all connected clients.
-//
-// We use two different types here, because only the server should
-// decide the identity of a given connection and thus also sends a
-// name.
-//
-// You can have a single message be sent both ways, it simply needs
-// to implement both `NetworkMessage" and both client and server can
-// send and recieve
+// the server then broadcasts `NewChatMessage`s to all connected
+// clients. The types are defined in the `shared` crate, so the
+// browser encodes and decodes them too.
 /////////////////////////////////////////////////////////////////////
 
-// #[derive(Serialize, Deserialize, Clone, Debug)]
-// pub struct UserChatMessage {
-//     pub message: String,
-// }
-
-// impl NetworkMessage for UserChatMessage {
-//     const NAME: &'static str = "example:UserChatMessage";
-// }
-
-// #[derive(Serialize, Deserialize, Clone, Debug)]
-// pub struct NewChatMessage {
-//     pub name: String,
-//     pub message: String,
-// }
-
-// impl NetworkMessage for NewChatMessage {
-//     const NAME: &'static str = "example:NewChatMessage";
-// }
-
-#[allow(unused)]
-pub fn client_register_network_messages(app: &mut App) {
-    use eventwork::AppNetworkMessage;
-
-    // The client registers messages that arrives from the server, so that
-    // it is prepared to handle them. Otherwise, an error occurs.
-    app.listen_for_message::<NewChatMessage, WebSocketProvider>();
-}
-
-#[allow(unused)]
 pub fn server_register_network_messages(app: &mut App) {
-    use eventwork::AppNetworkMessage;
-
-    // The server registers messages that arrives from a client, so that
-    // it is prepared to handle them. Otherwise, an error occurs.
-    app.listen_for_message::<UserChatMessage, WebSocketProvider>();
+    // The server registers messages that arrive from a client, so that
+    // they are written as `WsReceived` messages. Frames of unregistered
+    // kinds are logged and dropped.
+    app.add_ws_message::<UserChatMessage>();
 }
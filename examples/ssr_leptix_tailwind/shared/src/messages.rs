@@ -1,5 +1,6 @@
-pub use eventwork_common::*;
-use serde::{Serialize, Deserialize};
+use abw_client::protocol::Envelope;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
 /////////////////////////////////////////////////////////////////////
 // In this example the client sends `UserChatMessage`s to the server,
@@ -9,9 +10,9 @@ use serde::{Serialize, Deserialize};
 // decide the identity of a given connection and thus also sends a
 // name.
 //
-// You can have a single message be sent both ways, it simply needs
-// to implement both `NetworkMessage" and both client and server can
-// send and recieve
+// Every frame of the `WebSocketPlugin` endpoint is a JSON envelope
+// `{ "kind": KIND, "data": message }`, so the browser encodes and
+// decodes the same envelope with `encode` and `decode`.
 /////////////////////////////////////////////////////////////////////
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -19,8 +20,8 @@ pub struct UserChatMessage {
     pub message: String,
 }
 
-impl NetworkMessage for UserChatMessage {
-    const NAME: &'static str = "example:UserChatMessage";
+impl UserChatMessage {
+    pub const KIND: &'static str = "example:UserChatMessage";
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -29,33 +30,36 @@ pub struct NewChatMessage {
     pub message: String,
 }
 
-impl NetworkMessage for NewChatMessage {
-    const NAME: &'static str = "example:NewChatMessage";
+impl NewChatMessage {
+    pub const KIND: &'static str = "example:NewChatMessage";
 }
 
-// pub fn serialize_msg(message: NetworkMessage) -> Result<Vec<u8>, Box<bincode::ErrorKind>> {
-//     bincode::serialize(&message)
-// }
+#[cfg(feature = "server")]
+mod server {
+    use super::*;
+    use async_bevy_web::prelude::WsMessage;
 
+    impl WsMessage for UserChatMessage {
+        const NAME: &'static str = Self::KIND;
+    }
 
-pub fn serialize_msg<T: NetworkMessage + Serialize>(message: &T) -> Result<Vec<u8>, Box<bincode::ErrorKind>> {
-    bincode::serialize(message)
+    impl WsMessage for NewChatMessage {
+        const NAME: &'static str = Self::KIND;
+    }
 }
 
-// pub fn deserialize_msg<T: NetworkMessage + for<'de> Deserialize<'de>>(msg: &[u8]) -> Result<T, Box<bincode::ErrorKind>> {
-//     bincode::deserialize(msg).map_err(|e| Box::new(e))
-// }
-
-pub fn deserialize_msg<T>(
-    msg: Vec<u8>,
-    // source: ConnectionId
-) -> Option<T>
-where
-    T: NetworkMessage,
-{
-    let message = bincode::deserialize::<T>(&msg)
+/// Encode `message` in the JSON envelope of the `WebSocketPlugin` endpoint
+pub fn encode<T: Serialize>(kind: &str, message: &T) -> serde_json::Result<String> {
+    serde_json::to_string(&Envelope {
+        kind: kind.to_string(),
+        data: message,
+    })
+}
+
+/// Decode a JSON envelope, returning `None` when it doesn't carry a message of `kind`
+pub fn decode<T: DeserializeOwned>(kind: &str, text: &str) -> Option<T> {
+    serde_json::from_str::<Envelope<T>>(text)
         .ok()
-        .map(|message| message);
-    message
-    
-}
\ No newline at end of file
+        .filter(|envelope| envelope.kind == kind)
+        .map(|envelope| envelope.data)
+}